@@ -0,0 +1,134 @@
+/*
+ * Greathelm C plugin ABI.
+ *
+ * A plugin exports GHPI_ABI_VERSION and GHPI_PluginInitC. Strings are NUL-terminated UTF-8,
+ * manifests are passed as .ghm text. Everything returned to Greathelm must stay valid for as long
+ * as the plugin is loaded. Greathelm never calls into the same plugin from two threads at once,
+ * though consecutive calls (i.e. compile hooks) may come from different threads. See
+ * docs/Plugin-API.md.
+ */
+#ifndef GREATHELM_PLUGIN_H
+#define GREATHELM_PLUGIN_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#define GHPI_ABI_VERSION_CURRENT 3
+
+#define GHPI_OK 0
+#define GHPI_ERROR 1
+#define GHPI_UNSUPPORTED 2
+
+#define GHPI_LOG_INFO 0
+#define GHPI_LOG_WARNING 1
+#define GHPI_LOG_ERROR 2
+#define GHPI_LOG_OK 3
+
+/*
+ * Receives the reply to a message. Called at most once, before the call delivering the message
+ * returns. data is only valid during the call, text replies are UTF-8 without a NUL.
+ */
+typedef void (*GHPIReplyFn)(void *context, const uint8_t *data, size_t len);
+
+typedef struct GHPIHost {
+    uint32_t abi_version;
+    void (*log)(uint32_t level, const char *text);
+    /* to is a namespace:Identifier, reply may be NULL. GHPI_ERROR if the plugin doesn't exist or
+     * failed to handle the message */
+    int32_t (*send_message)(const char *to, const uint8_t *data, size_t len, GHPIReplyFn reply,
+                            void *reply_context);
+    int32_t (*send_text_message)(const char *to, const char *text, GHPIReplyFn reply,
+                                 void *reply_context);
+} GHPIHost;
+
+typedef struct GHPIBuilder {
+    const char *name;
+    const char *identifier;   /* namespace:Identifier */
+    const char *const *aliases; /* NULL-terminated */
+    void *userdata;
+    int32_t (*build)(void *userdata, const char *manifest);
+    bool (*validate)(void *userdata, const char *manifest);
+    int32_t (*cleanup)(void *userdata, const char *manifest);
+    /* optional, may be NULL */
+    int32_t (*write_compile_commands)(void *userdata, const char *manifest);
+} GHPIBuilder;
+
+typedef struct GHPIGenerator {
+    const char *name;
+    const char *identifier;
+    const char *const *aliases;
+    void *userdata;
+    int32_t (*generate)(void *userdata, const char *cwd);
+} GHPIGenerator;
+
+typedef struct GHPIAction {
+    const char *name;
+    const char *identifier;
+    const char *const *aliases;
+    void *userdata;
+    int32_t (*execute)(void *userdata, const char *manifest, size_t argc,
+                       const char *const *argv);
+} GHPIAction;
+
+#define GHPI_HOOK_BEFORE_MODULES 0
+#define GHPI_HOOK_AFTER_MODULES 1
+#define GHPI_HOOK_BEFORE_COMPILE 2 /* source and output (the object file) are set */
+#define GHPI_HOOK_AFTER_COMPILE 3  /* source and output (the object file) are set */
+#define GHPI_HOOK_AFTER_LINK 4     /* output (the artifact) is set */
+#define GHPI_HOOK_AFTER_EXPORT 5
+
+typedef struct GHPIHook {
+    uint32_t point;
+    void *userdata;
+    /* source and output are NULL unless the hook point sets them, return GHPI_OK to continue */
+    int32_t (*run)(void *userdata, uint32_t point, const char *manifest, const char *source,
+                   const char *output);
+} GHPIHook;
+
+typedef struct GHPIPlugin {
+    const char *name;
+    const char *vendor;
+    const char *description;
+    const char *identifier;
+    const char *version;
+
+    const GHPIBuilder *builders;
+    size_t builder_count;
+    const GHPIGenerator *generators;
+    size_t generator_count;
+    const GHPIAction *actions;
+    size_t action_count;
+    const char *const *provides_templates; /* NULL-terminated */
+
+    /* every callback below is optional and gets userdata as its first argument */
+    void *userdata;
+    bool (*create_template)(void *userdata, const char *identifier, const char *path);
+    void (*first_time_setup)(void *userdata);
+    void (*uninstall)(void *userdata);
+    /* call reply(reply_context, ...) to answer, return GHPI_OK if the message was handled */
+    int32_t (*plugin_message)(void *userdata, const uint8_t *data, size_t len, GHPIReplyFn reply,
+                              void *reply_context);
+    int32_t (*plugin_text_message)(void *userdata, const char *text, GHPIReplyFn reply,
+                                   void *reply_context);
+
+    const GHPIHook *hooks;
+    size_t hook_count;
+} GHPIPlugin;
+
+/*
+ * Use in exactly one source file of the plugin:
+ *
+ *   GHPI_EXPORT_ABI_VERSION;
+ *   GHPI_EXPORT_MIN_HOST_VERSION("0.1.0"); (optional)
+ *   const GHPIPlugin *GHPI_PluginInitC(const GHPIHost *host) { ... }
+ *
+ * Greathelm checks both before calling GHPI_PluginInitC and refuses plugins built for another
+ * ABI version or requiring a newer Greathelm.
+ */
+#define GHPI_EXPORT_ABI_VERSION const uint32_t GHPI_ABI_VERSION = GHPI_ABI_VERSION_CURRENT
+#define GHPI_EXPORT_MIN_HOST_VERSION(v) const char GHPI_MIN_HOST_VERSION[] = v
+
+const GHPIPlugin *GHPI_PluginInitC(const GHPIHost *host);
+
+#endif
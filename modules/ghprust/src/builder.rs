@@ -1,6 +1,4 @@
-use std::path::Path;
-
-use greathelm::{builder::{dependency,ProjectBuilder}, manifest::ProjectManifest, script, subprocess, term::*, identify::NamespacedIdentifier, warning, error, info, ok};
+use greathelm::{error::GreathelmError, builder::{dependency::DependencyGraph,ProjectBuilder}, lockfile::Lockfile, manifest::ProjectManifest, script, subprocess, term::*, identify::NamespacedIdentifier, warning, error, info, ok};
 
 /**
  * Built-in builder for Rust projects.
@@ -28,17 +26,18 @@ impl ProjectBuilder for RustBuilder {
     fn validate(&self, _manifest: &ProjectManifest) -> bool {
         return true;
     }
-    fn cleanup(&self, _manifest: &ProjectManifest) {
+    fn cleanup(&self, _manifest: &ProjectManifest) -> Result<(), GreathelmError> {
         warning!("Rust builder does not currently have a cleanup step.");
+        return Ok(());
     }
-    fn build(&self, manifest: &ProjectManifest) {
-        let build_dir = Path::new("build");
+    fn build(&self, manifest: &ProjectManifest) -> Result<(), GreathelmError> {
+        let root = manifest.project_dir();
+        let build_dir = manifest.project_path("build");
         if !build_dir.exists() {
-            match std::fs::create_dir(build_dir) {
+            match std::fs::create_dir(&build_dir) {
                 Ok(_) => {}
-                Err(_) => {
-                    error!("Failed to create build directory. Abort.");
-                    return;
+                Err(e) => {
+                    return Err(GreathelmError::io("Failed to create build directory.", e));
                 }
             }
         }
@@ -50,7 +49,7 @@ impl ProjectBuilder for RustBuilder {
         let opt_level = manifest.get_string_property("Compiler-Opt-Level", "2");
         let rust_edition = manifest.get_string_property("Rust-Edition", "2021");
 
-        script::run_script("prebuild", vec![]);
+        script::run_script(&root, "prebuild", vec![]);
 
         // main/lib rs file
         let comp_file = if crate_type == "bin" {
@@ -97,68 +96,67 @@ impl ProjectBuilder for RustBuilder {
             None => Vec::new(),
         };
 
-        // actually handle deps
-        for d in dependencies {
-            // vendored deps (rlibs in lib/rlib)
+        // vendored deps (rlibs in lib/rlib)
+        for d in &dependencies {
             if d.starts_with("vendored/") {
                 let libname = d.split_once("vendored/").unwrap().1;
                 rustc_args.push("--extern".into());
                 rustc_args.push(format!("{libname}=lib/rlib/lib{libname}.rlib"));
-            } else {
-                // Greathelm deps (dependencies in the local store)
-                let (id, version) = dependency::parse_dependency_notation(d.to_owned());
-                let path = match dependency::resolve_dependency(id.clone(), version) {
-                    Some(p) => p,
-                    None => {
-                        error!("Could not resolve a dependency. Abort.");
-                        std::process::exit(1);
-                    }
-                };
-                // build the thing
-                subprocess::build_project(&path);
-
-                // we have to read the manifest because the export could be a bunch of things here
-                // in Rust projects
-                let mut depmf = ProjectManifest::new();
-                depmf.read_and_append(Path::new(&format!("{}/Project.ghm", path.display())));
-                let dep_executable_name =
-                    depmf.get_string_property("Executable-Name", id.identifier.as_str());
-                let dep_crate_type = depmf.get_string_property("Crate-Type", "rlib");
-                let dep_prefix = if dep_crate_type.contains("lib") {
-                    "lib"
-                } else {
-                    ""
-                };
-                let dep_suffix = match dep_crate_type.as_str() {
-                    "rlib" | "dylib" | "lib" => ".rlib",
-                    "cdylib" => ".so",
-                    "staticlib" => ".a",
-                    _ => "",
-                };
-
-                // add it to the rustc args
-                rustc_args.push("--extern".into());
-                rustc_args.push(format!(
-                    "{}={}/export/{}{}{}",
-                    id.identifier.to_lowercase(),
-                    path.display(),
-                    dep_prefix,
-                    dep_executable_name,
-                    dep_suffix
-                ));
             }
         }
 
+        // Greathelm deps (dependencies in the local store) and everything they depend on
+        let mut lock = Lockfile::read(manifest)?;
+        let graph = DependencyGraph::resolve(manifest, &mut lock)?;
+        lock.save()?;
+
+        for key in &graph.order {
+            let node = graph.nodes.get(key).unwrap();
+            // let rustc find crates our dependencies depend on
+            rustc_args.push("-L".into());
+            rustc_args.push(format!("dependency={}/export", node.path.display()));
+        }
+
+        for key in &graph.roots {
+            let node = graph.nodes.get(key).unwrap();
+            // we have to read the manifest because the export could be a bunch of things here
+            // in Rust projects
+            let dep_executable_name =
+                node.manifest.get_string_property("Executable-Name", node.identifier.identifier.as_str());
+            let dep_crate_type = node.manifest.get_string_property("Crate-Type", "rlib");
+            let dep_prefix = if dep_crate_type.contains("lib") {
+                "lib"
+            } else {
+                ""
+            };
+            let dep_suffix = match dep_crate_type.as_str() {
+                "rlib" | "dylib" | "lib" => ".rlib",
+                "cdylib" => ".so",
+                "staticlib" => ".a",
+                _ => "",
+            };
+
+            // add it to the rustc args
+            rustc_args.push("--extern".into());
+            rustc_args.push(format!(
+                "{}={}/export/{}{}{}",
+                node.identifier.identifier.to_lowercase(),
+                node.path.display(),
+                dep_prefix,
+                dep_executable_name,
+                dep_suffix
+            ));
+        }
+
         // base file
         rustc_args.push(comp_file.into());
 
         // run rustc
-        let rustc = duct::cmd("rustc", rustc_args);
-        let rustc = match rustc.stderr_to_stdout().run() {
+        let rustc = duct::cmd("rustc", rustc_args).dir(&root);
+        let rustc = match subprocess::run(rustc) {
             Ok(v) => v,
-            Err(_) => {
-                error!("Failed to invoke compiler.");
-                std::process::exit(1);
+            Err(e) => {
+                return Err(GreathelmError::io("Failed to invoke compiler.", e));
             }
         };
         if rustc.status.success() {
@@ -168,18 +166,18 @@ impl ProjectBuilder for RustBuilder {
                 crate_type
             );
         } else {
-            error!(
+            return Err(GreathelmError::BuildError(format!(
                 "RUSTC \x1bc{}\x1br (\x1bc{}\x1br)",
                 project_name, crate_type
-            );
+            )));
         }
 
         // export it if its a library project
         if crate_type.contains("lib") {
             info!("Project is a library. Exporting artifact...");
             match std::fs::copy(
-                Path::new(&format!("build/{prefix}{executable_name}{suffix}")),
-                Path::new(&format!("export/{prefix}{executable_name}{suffix}")),
+                manifest.project_path(format!("build/{prefix}{executable_name}{suffix}")),
+                manifest.project_path(format!("export/{prefix}{executable_name}{suffix}")),
             ) {
                 Ok(_) => {
                     ok!("Done!");
@@ -190,6 +188,7 @@ impl ProjectBuilder for RustBuilder {
             };
         }
 
-        script::run_script("postbuild", vec![]);
+        script::run_script(&root, "postbuild", vec![]);
+        return Ok(());
     }
 }
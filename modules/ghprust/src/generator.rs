@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 
-use greathelm::{generator::{helper,ProjectGenerator},term::*, identify::NamespacedIdentifier, ok};
+use greathelm::{error::GreathelmError, generator::{helper,ProjectGenerator},term::*, identify::NamespacedIdentifier, ok};
 
 /**
  * Project generator for Rust projects.
@@ -25,15 +25,12 @@ impl ProjectGenerator for RustGenerator {
     fn get_aliases(&self) -> Vec<String> {
         vec!["rust".into(), "rs".into()]
     }
-    fn should_make_ibht_stub(&self) -> bool {
-        true
-    }
-    fn generate(&self, _cwd: PathBuf) {
-        helper::create_directory("src");
-        helper::create_directory("lib");
-        helper::create_directory("lib/rlib");
-        helper::create_directory("lib/crates");
-        helper::create_directory("export");
+    fn generate(&self, _cwd: PathBuf) -> Result<(), GreathelmError> {
+        helper::create_directory("src")?;
+        helper::create_directory("lib")?;
+        helper::create_directory("lib/rlib")?;
+        helper::create_directory("lib/crates")?;
+        helper::create_directory("export")?;
 
         let mut project_name = question("Project name?".into());
         let mut project_namespace = question("Project namespace?".into());
@@ -57,12 +54,12 @@ impl ProjectGenerator for RustGenerator {
             let main_rs_contents = "fn main() {\n\
                                     \tprintln!(\"Hello World!\");\n\
         }\n";
-            helper::create_file("src/main.rs", main_rs_contents);
+            helper::create_file("src/main.rs", main_rs_contents)?;
         } else if project_crate_type.contains("lib") {
             let lib_rs_contents = "pub fn sayhello() {\n\
                                     \tprintln!(\"Hello World!\");\n\
         }\n";
-            helper::create_file("src/lib.rs", lib_rs_contents);
+            helper::create_file("src/lib.rs", lib_rs_contents)?;
         }
 
         // i have no idea why rustfmt is indenting this in such an ugly fashion but it is and we
@@ -83,8 +80,9 @@ impl ProjectGenerator for RustGenerator {
                 Greathelm-Version=0.1.0\n", // target greathelm version
             )
             .as_str(),
-        );
+        )?;
 
         ok!("Succeeded in generating project from template.");
+        return Ok(());
     }
 }
@@ -1,13 +1,13 @@
 use std::path::PathBuf;
 
-use greathelm::{plugin::GreathelmPlugin, identify::NamespacedIdentifier, version::Version, warning, term::*};
+use greathelm::{plugin::GreathelmPlugin, error::GreathelmError, identify::NamespacedIdentifier, version::Version, warning, term::*};
 
 mod builder;
 mod generator;
 
-#[allow(non_snake_case)]
-#[no_mangle]
-pub unsafe fn GHPI_PluginInit() -> GreathelmPlugin {
+greathelm::greathelm_plugin!(create_plugin);
+
+fn create_plugin() -> GreathelmPlugin {
     return GreathelmPlugin { 
         // plugin metadata
         name: "Greathelm Rust Support".into(),
@@ -24,6 +24,7 @@ pub unsafe fn GHPI_PluginInit() -> GreathelmPlugin {
         generators: vec![ Box::new(generator::RustGenerator::create()) ],
         actions: vec![],
         provides_templates: vec![],
+        hooks: vec![],
 
         // ghpi function pointers
         ghpi_create_template: &create_template,
@@ -51,11 +52,13 @@ fn uninstall() {
 }
 
 // we literally do not care about these
-fn pluginmessage(_data: &[u8]) {
+fn pluginmessage(_data: &[u8]) -> Result<Option<Vec<u8>>, GreathelmError> {
     warning!("GHP-Rust received a plugin message.");
     warning!("This is likely in error as GHP-Rust does not provide any functionality via plugin messages.");
+    Ok(None)
 }
-fn plugintextmessage(_text: &str) {
+fn plugintextmessage(_text: &str) -> Result<Option<String>, GreathelmError> {
     warning!("GHP-Rust received a plugin text message.");
     warning!("This is likely in error as GHP-Rust does not provide any functionality via plugin messages.");
+    Ok(None)
 }
@@ -1,4 +1,4 @@
-use crate::{config, store, template, term::*};
+use crate::{config, error::GreathelmError, store, template, term::*};
 
 use super::Action;
 
@@ -27,7 +27,11 @@ impl Action for AboutAction {
         }
     }
 
-    fn execute(&self, state: &crate::state::GreathelmState) {
+    fn requires_manifest(&self) -> bool {
+        false
+    }
+
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
         info!("== Build Information ==");
         info!("Name: \x1bc{}\x1br", env!("CARGO_PKG_NAME"));
         info!("Version: \x1bc{}\x1br", env!("CARGO_PKG_VERSION"));
@@ -100,5 +104,7 @@ impl Action for AboutAction {
                 g.get_identifier()
             );
         }
+
+        return Ok(());
     }
 }
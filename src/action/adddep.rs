@@ -0,0 +1,92 @@
+use crate::{
+    builder::dependency, error::GreathelmError, identify::NamespacedIdentifier,
+    manifest::document::ManifestDocument, term::*,
+};
+
+use super::Action;
+
+/**
+ * Built-in (io.github.madelynwith5ns.greathelm:AddDependency) action adding @Dependency
+ * directives to Project.ghm (or Project.local.ghm with `--local`), like `greathelm add-dep
+ * com.example:Lib@^1.0`. A store dependency that is already there has its version changed instead.
+ */
+pub struct AddDependencyAction {}
+impl AddDependencyAction {
+    pub fn create() -> Self {
+        Self {}
+    }
+}
+
+impl Action for AddDependencyAction {
+    fn get_name(&self) -> String {
+        "AddDependency".into()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        vec!["add-dep".into(), "add-dependency".into()]
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        NamespacedIdentifier {
+            namespace: "io.github.madelynwith5ns.greathelm".into(),
+            identifier: "AddDependency".into(),
+        }
+    }
+    fn checks_plugin_requirements(&self) -> bool {
+        false
+    }
+    fn requires_manifest(&self) -> bool {
+        false
+    }
+
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
+        let deps: Vec<&String> = state
+            .cli_args
+            .iter()
+            .skip(2)
+            .filter(|a| !a.starts_with("--"))
+            .collect();
+        if deps.is_empty() {
+            return Err(GreathelmError::ActionError(
+                "Please provide a dependency to add.".into(),
+            ));
+        }
+
+        let (path, mut document) =
+            ManifestDocument::read_project(state.manifest.get_bool_property("local", false))?;
+        for dep in deps {
+            if !dependency::is_store_dependency(dep) {
+                if document.get_directives("Dependency").contains(dep) {
+                    info!("\x1bc{dep}\x1br is already a dependency.");
+                } else {
+                    document.add_directive("Dependency", dep);
+                    ok!("Added dependency \x1bc{dep}\x1br.");
+                }
+                continue;
+            }
+
+            let (id, _) = dependency::parse_dependency_notation(dep.clone())?;
+            let replaced =
+                document.replace_directives("Dependency", |v| same_store_dependency(v, &id), dep);
+            if replaced > 0 {
+                ok!("Changed dependency \x1bc{id}\x1br to \x1bc{dep}\x1br.");
+            } else {
+                document.add_directive("Dependency", dep);
+                ok!("Added dependency \x1bc{dep}\x1br.");
+            }
+        }
+        document.write(&path)?;
+        return Ok(());
+    }
+}
+
+/**
+ * Whether the @Dependency value `dep` is a store dependency on `id`, at any version.
+ */
+pub fn same_store_dependency(dep: &str, id: &NamespacedIdentifier) -> bool {
+    if !dependency::is_store_dependency(dep) {
+        return false;
+    }
+    return match dependency::parse_dependency_notation(dep.trim().into()) {
+        Ok((d, _)) => &d == id,
+        Err(_) => false,
+    };
+}
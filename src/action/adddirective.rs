@@ -0,0 +1,91 @@
+use crate::{
+    error::GreathelmError, identify::NamespacedIdentifier, manifest::document::ManifestDocument,
+    term::*,
+};
+
+use super::Action;
+
+/**
+ * Built-in (io.github.madelynwith5ns.greathelm:AddDirective) action adding a directive to
+ * Project.ghm (or Project.local.ghm with `--local`), like `greathelm add-directive Export
+ * build/tool bin/tool`.
+ */
+pub struct AddDirectiveAction {}
+impl AddDirectiveAction {
+    pub fn create() -> Self {
+        Self {}
+    }
+}
+
+impl Action for AddDirectiveAction {
+    fn get_name(&self) -> String {
+        "AddDirective".into()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        vec!["add-directive".into(), "add".into()]
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        NamespacedIdentifier {
+            namespace: "io.github.madelynwith5ns.greathelm".into(),
+            identifier: "AddDirective".into(),
+        }
+    }
+    fn checks_plugin_requirements(&self) -> bool {
+        false
+    }
+    fn requires_manifest(&self) -> bool {
+        false
+    }
+
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
+        let (name, value) = directive_args(&state.cli_args)?;
+        let (path, mut document) =
+            ManifestDocument::read_project(state.manifest.get_bool_property("local", false))?;
+        if document.get_directives(&name).contains(&value) {
+            info!(
+                "\x1bc@{name} {value}\x1br is already in {}.",
+                path.display()
+            );
+            return Ok(());
+        }
+        document.add_directive(&name, &value);
+        document.write(&path)?;
+        ok!("Added \x1bc@{name} {value}\x1br to {}.", path.display());
+        return Ok(());
+    }
+}
+
+/**
+ * Gets the directive name (with or without its `@`) and value from the arguments of
+ * add-directive and remove-directive.
+ */
+pub fn directive_args(args: &[String]) -> Result<(String, String), GreathelmError> {
+    let args: Vec<&str> = args
+        .iter()
+        .skip(2)
+        .filter(|a| !a.starts_with("--"))
+        .map(|a| a.as_str())
+        .collect();
+    let (name, value) = match args.split_first() {
+        Some((name, value)) if !value.is_empty() => (name.trim_start_matches("@"), value.join(" ")),
+        _ => {
+            return Err(GreathelmError::ActionError(
+                "Please provide a directive and its value.".into(),
+            ));
+        }
+    };
+    if name.is_empty()
+        || name.contains(char::is_whitespace)
+        || ["If", "ElseIf", "Else", "EndIf"].contains(&name)
+    {
+        return Err(GreathelmError::ActionError(format!(
+            "\x1bc{name}\x1br is not a directive that can be added or removed."
+        )));
+    }
+    if value.contains("\n") {
+        return Err(GreathelmError::ActionError(
+            "Directive values can't contain line breaks.".into(),
+        ));
+    }
+    return Ok((name.to_string(), value));
+}
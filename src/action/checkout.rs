@@ -1,4 +1,7 @@
-use crate::{builder::dependency, identify::NamespacedIdentifier, term::*};
+use crate::{
+    builder::dependency, error::GreathelmError, identify::NamespacedIdentifier, lockfile::Lockfile,
+    store, term::*,
+};
 
 use super::Action;
 
@@ -28,32 +31,28 @@ impl Action for CheckoutAction {
         }
     }
 
-    fn execute(&self, state: &crate::state::GreathelmState) {
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
         let package = match state.cli_args.get(2) {
             Some(v) => v,
             None => {
-                error!("Please provide a package.");
-                std::process::exit(1);
+                return Err(GreathelmError::ActionError(
+                    "Please provide a package.".into(),
+                ));
             }
         };
         // resolve the package
         info!("Attempting to resolve {package}");
-        let (id, ver) = dependency::parse_dependency_notation(package.clone());
-        let path = dependency::resolve_dependency(id.clone(), ver);
-        let path = match path {
-            Some(p) => p,
-            None => {
-                error!("Could not resolve. Abort.");
-                std::process::exit(1);
-            }
-        };
+        let (id, ver) = dependency::parse_dependency_notation(package.clone())?;
+        // honor the current project's lockfile if there is one
+        let mut lock = Lockfile::read(&state.manifest)?;
+        let path =
+            dependency::resolve_locked(&store::get_store_path(), id.clone(), ver, &mut lock)?;
         info!("Checking out to current directory...");
         // current directory
         let dir = match std::env::current_dir() {
             Ok(v) => v,
-            Err(_) => {
-                error!("Could not get current directory.");
-                std::process::exit(1);
+            Err(e) => {
+                return Err(GreathelmError::io("Could not get current directory.", e));
             }
         };
         // copy the things
@@ -62,9 +61,10 @@ impl Action for CheckoutAction {
                 ok!("Finished checking out \x1bc{id}\x1br");
             }
             Err(e) => {
-                error!("Failed to checkout.");
-                eprintln!("{e}");
+                return Err(GreathelmError::io("Failed to checkout.", e));
             }
         };
+
+        return Ok(());
     }
 }
@@ -1,7 +1,7 @@
 use crate::term::*;
 use std::path::Path;
 
-use crate::{builder::ProjectBuilder, identify::NamespacedIdentifier};
+use crate::{builder::ProjectBuilder, error::GreathelmError, identify::NamespacedIdentifier};
 
 use super::Action;
 
@@ -29,7 +29,7 @@ impl Action for CleanAction {
         }
     }
 
-    fn execute(&self, state: &crate::state::GreathelmState) {
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
         // make sure we have working settings
         let project_name = state
             .manifest
@@ -39,8 +39,9 @@ impl Action for CleanAction {
             .get_string_property("Project-Type", "Unknown");
         // we cant clean a project if it doesnt have a Project-Type.
         if project_type == "Unknown" {
-            error!("This project does not have a set \x1bcProject-Type\x1br property.");
-            std::process::exit(1);
+            return Err(GreathelmError::ManifestError(
+                "This project does not have a set \x1bcProject-Type\x1br property.".into(),
+            ));
         }
 
         info!("Cleaning up after \x1bc{project_name}\x1br");
@@ -53,13 +54,12 @@ impl Action for CleanAction {
             if b.get_aliases().contains(&project_type.to_lowercase()) {
                 // fail out if we already have a builder found
                 if use_builder.is_some() {
-                    error!(
-                        "Builder name \x1bc{project_type}\x1br is ambiguous in your configuration."
-                    );
-                    error!("Please specify which one you would like to use either on the command line,");
-                    error!("like so \x1bc--Project-Type=<full.namespaced:Identifier>\x1br");
-                    error!("or in your project manifest.");
-                    std::process::exit(1);
+                    return Err(GreathelmError::ActionError(format!(
+                        "Builder name \x1bc{project_type}\x1br is ambiguous in your configuration.\n\
+                         Please specify which one you would like to use either on the command line,\n\
+                         like so \x1bc--Project-Type=<full.namespaced:Identifier>\x1br\n\
+                         or in your project manifest."
+                    )));
                 } else {
                     use_builder = Some(b);
                 }
@@ -81,17 +81,20 @@ impl Action for CleanAction {
             Some(builder) => {
                 let path = Path::new("build");
                 if !path.exists() {
-                    warning!("No build directory. Nothing to clean.");
-                    std::process::exit(1);
+                    return Err(GreathelmError::ActionError(
+                        "No build directory. Nothing to clean.".into(),
+                    ));
                 }
 
-                builder.cleanup(&state.manifest);
+                builder.cleanup(&state.manifest)?;
             }
             None => {
-                error!("Could not find the required builder \x1bc{project_type}\x1br.");
-                error!("Are you missing a plugin?");
-                std::process::exit(1);
+                return Err(GreathelmError::ActionError(format!(
+                    "Could not find the required builder \x1bc{project_type}\x1br.\nAre you missing a plugin?"
+                )));
             }
         }
+
+        return Ok(());
     }
 }
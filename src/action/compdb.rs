@@ -0,0 +1,64 @@
+use crate::term::*;
+
+use crate::{error::GreathelmError, identify::NamespacedIdentifier, project};
+
+use super::Action;
+
+/**
+ * Built-in action (io.github.madelynwith5ns.greathelm:CompileCommands) for writing a compilation
+ * database (compile_commands.json) for the current project without building it.
+ */
+pub struct CompileCommandsAction {}
+impl CompileCommandsAction {
+    pub fn create() -> Self {
+        Self {}
+    }
+}
+
+impl Action for CompileCommandsAction {
+    fn get_name(&self) -> String {
+        "CompileCommands".into()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        vec!["compdb".into(), "compile-commands".into()]
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        NamespacedIdentifier {
+            namespace: "io.github.madelynwith5ns.greathelm".into(),
+            identifier: "CompileCommands".into(),
+        }
+    }
+
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
+        let project_type = state
+            .manifest
+            .get_string_property("Project-Type", "Unknown");
+        if project_type == "Unknown" {
+            return Err(GreathelmError::ManifestError(
+                "This project does not have a set \x1bcProject-Type\x1br property.".into(),
+            ));
+        }
+
+        // find the builder, fail out if ambiguous.
+        let use_builder = project::find_builder(state, &project_type)?;
+
+        match use_builder {
+            Some(builder) => {
+                info!("Generating compilation database...");
+                if !builder.write_compile_commands(&state.manifest)? {
+                    return Err(GreathelmError::ActionError(format!(
+                        "Builder \x1bc{}\x1br does not support compilation databases.",
+                        builder.get_identifier()
+                    )));
+                }
+            }
+            None => {
+                return Err(GreathelmError::ActionError(format!(
+                    "Could not find the required builder \x1bc{project_type}\x1br.\nAre you missing a plugin?"
+                )));
+            }
+        }
+
+        return Ok(());
+    }
+}
@@ -0,0 +1,154 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    config, error::GreathelmError, identify::NamespacedIdentifier, probe, project, store, template,
+    term::*,
+};
+
+use super::Action;
+
+/**
+ * Built-in action (io.github.madelynwith5ns.greathelm:Doctor) that checks the Greathelm install
+ * and the tools the current project needs, reporting everything it finds wrong.
+ */
+pub struct DoctorAction {}
+impl DoctorAction {
+    pub fn create() -> Self {
+        Self {}
+    }
+}
+
+impl Action for DoctorAction {
+    fn get_name(&self) -> String {
+        "Doctor".into()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        vec!["doctor".into()]
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        NamespacedIdentifier {
+            namespace: "io.github.madelynwith5ns.greathelm".into(),
+            identifier: "Doctor".into(),
+        }
+    }
+    fn checks_plugin_requirements(&self) -> bool {
+        false
+    }
+    fn requires_manifest(&self) -> bool {
+        false
+    }
+
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
+        let mut problems = 0;
+
+        info!("== Directories ==");
+        let config_dir = config::get_config_base_dir();
+        let dirs: Vec<(&str, PathBuf)> = vec![
+            ("Config Directory", config_dir.clone()),
+            ("Plugins Directory", config_dir.join("plugins")),
+            ("Scripts Directory", config_dir.join("scripts")),
+            ("Data Directory", config::get_data_base_dir()),
+            ("Local Store Path", store::get_store_path()),
+            ("Templates Path", template::get_templates_path()),
+        ];
+        for (name, dir) in dirs {
+            if !dir.is_dir() {
+                error!("{name}: \x1bc{}/\x1br does not exist.", dir.display());
+                problems += 1;
+            } else if !is_writable(&dir) {
+                error!("{name}: \x1bc{}/\x1br is not writable.", dir.display());
+                problems += 1;
+            } else {
+                ok!("{name}: \x1bc{}/\x1br", dir.display());
+            }
+        }
+
+        info!("== Store ==");
+        let (packages, store_problems) = store::check_store();
+        if store_problems.is_empty() {
+            ok!("\x1bc{packages}\x1br packages, all healthy.");
+        } else {
+            warning!(
+                "\x1bc{packages}\x1br packages, \x1bc{}\x1br broken:",
+                store_problems.len()
+            );
+            for p in &store_problems {
+                error!("{p}");
+            }
+            problems += store_problems.len();
+        }
+
+        info!("== Plugins ==");
+        ok!("\x1bc{}\x1br plugins loaded.", state.plugins.len());
+        for p in &state.incompatible_plugins {
+            warning!(
+                "\x1bc{}\x1br was not loaded: It {}.",
+                p.path.display(),
+                p.compatibility
+            );
+        }
+        for p in &state.failed_plugins {
+            error!(
+                "\x1bc{}\x1br failed to load: {}",
+                p.path.display(),
+                p.reason
+            );
+            problems += 1;
+        }
+
+        if Path::new("Project.ghm").exists() {
+            let project_type = state.manifest.get_string_property("Project-Type", "");
+            info!("== Tools (\x1bc{project_type}\x1br) ==");
+            match project::find_builder(state, &project_type)? {
+                None => {
+                    error!("No builder for project type \x1bc{project_type}\x1br is installed.");
+                    problems += 1;
+                }
+                Some(builder) => {
+                    let requirements = builder.get_required_tools(&state.manifest);
+                    if requirements.is_empty() {
+                        info!("The builder does not name any tools it needs.");
+                    }
+                    for r in requirements {
+                        let tool = probe::probe(&r.program);
+                        match (&tool.path, &tool.version) {
+                            (Some(path), Some(version)) => ok!(
+                                "{}: \x1bc{}\x1br ({}, {version})",
+                                r.role,
+                                r.program,
+                                path.display()
+                            ),
+                            (Some(path), None) => {
+                                ok!("{}: \x1bc{}\x1br ({})", r.role, r.program, path.display())
+                            }
+                            (None, _) => {
+                                error!("{}: \x1bc{}\x1br could not be found.", r.role, r.program);
+                                problems += 1;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if problems > 0 {
+            return Err(GreathelmError::ActionError(format!(
+                "Found \x1bc{problems}\x1br problems."
+            )));
+        }
+        ok!("No problems found.");
+        return Ok(());
+    }
+}
+
+/**
+ * Whether files can be created in `dir`.
+ */
+fn is_writable(dir: &Path) -> bool {
+    let probe_file = dir.join(format!(".greathelm-doctor-{}", std::process::id()));
+    if std::fs::write(&probe_file, "").is_err() {
+        return false;
+    }
+    std::fs::remove_file(&probe_file).ok();
+    return true;
+}
@@ -0,0 +1,53 @@
+use crate::{error::GreathelmError, identify::NamespacedIdentifier};
+
+use super::Action;
+
+/**
+ * Built-in (io.github.madelynwith5ns.greathelm:Get) action printing the value of a property for
+ * scripts. The value is the one builders see: every manifest merged, references resolved and
+ * command line properties applied.
+ */
+pub struct GetAction {}
+impl GetAction {
+    pub fn create() -> Self {
+        Self {}
+    }
+}
+
+impl Action for GetAction {
+    fn get_name(&self) -> String {
+        "Get".into()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        vec!["get".into()]
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        NamespacedIdentifier {
+            namespace: "io.github.madelynwith5ns.greathelm".into(),
+            identifier: "Get".into(),
+        }
+    }
+    fn checks_plugin_requirements(&self) -> bool {
+        false
+    }
+
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
+        let key = match state.cli_args.get(2) {
+            Some(k) if !k.starts_with("--") => k,
+            _ => {
+                return Err(GreathelmError::ActionError(
+                    "Please provide a property to get.".into(),
+                ));
+            }
+        };
+        match state.manifest.properties.get(key) {
+            Some(v) => println!("{v}"),
+            None => {
+                return Err(GreathelmError::ActionError(format!(
+                    "Property \x1bc{key}\x1br is not set."
+                )));
+            }
+        }
+        return Ok(());
+    }
+}
@@ -1,6 +1,8 @@
 use std::{path::PathBuf, str::FromStr};
 
-use crate::{identify::NamespacedIdentifier, script, store, term::*, version::Version};
+use crate::{
+    error::GreathelmError, identify::NamespacedIdentifier, script, store, term::*, version::Version,
+};
 
 use super::Action;
 
@@ -33,27 +35,31 @@ impl Action for ImportAction {
         }
     }
 
-    fn execute(&self, state: &crate::state::GreathelmState) {
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
         // get all of our settings
         let namespace = state
             .manifest
             .get_string_property("Project-Namespace", "unnamespaced");
         if namespace == "unnamespaced" {
-            error!("Project does not have a Project-Namespace. Cannot be imported.");
-            return;
+            return Err(GreathelmError::ManifestError(
+                "Project does not have a Project-Namespace. Cannot be imported.".into(),
+            ));
         }
         let name = state
             .manifest
             .get_string_property("Project-Name", "unnamed");
         if name == "unnamed" {
-            error!("Project does not have a Project-Name. Cannot be imported.");
-            return;
+            return Err(GreathelmError::ManifestError(
+                "Project does not have a Project-Name. Cannot be imported.".into(),
+            ));
         }
         let version = state
             .manifest
             .get_string_property("Project-Version", "unversioned");
         if version == "unversioned" {
-            error!("Project does not have a Project-Version. Cannot be imported");
+            return Err(GreathelmError::ManifestError(
+                "Project does not have a Project-Version. Cannot be imported".into(),
+            ));
         }
         // make the version and identifier structs
         let version = Version::parse(version);
@@ -67,7 +73,11 @@ impl Action for ImportAction {
         let path = PathBuf::from_str(&format!("{}/@{version}", path.display())).unwrap();
 
         // people run scripts or something i dont know
-        script::run_script("pre-import", vec![format!("{}", path.display())]);
+        script::run_script(
+            &state.manifest.project_dir(),
+            "pre-import",
+            vec![format!("{}", path.display())],
+        );
 
         // import time
         info!("Importing project to \x1bc{}\x1br", path.display());
@@ -77,11 +87,10 @@ impl Action for ImportAction {
             match std::fs::remove_dir_all(&path) {
                 Ok(_) => {}
                 Err(e) => {
-                    print_error_obj(
-                        Some("Failed to remove old copy of this project in the store.".into()),
-                        Box::new(e),
-                    );
-                    return;
+                    return Err(GreathelmError::io(
+                        "Failed to remove old copy of this project in the store.",
+                        e,
+                    ));
                 }
             };
         }
@@ -89,18 +98,16 @@ impl Action for ImportAction {
         // create the destination
         match std::fs::create_dir_all(&path) {
             Ok(_) => {}
-            Err(_) => {
-                error!("Failed to create path in store.");
-                return;
+            Err(e) => {
+                return Err(GreathelmError::io("Failed to create path in store.", e));
             }
         };
 
         // current dir
         let cd = match std::env::current_dir() {
             Ok(v) => v,
-            Err(_) => {
-                error!("Failed to get current directory.");
-                return;
+            Err(e) => {
+                return Err(GreathelmError::io("Failed to get current directory.", e));
             }
         };
 
@@ -121,8 +128,10 @@ impl Action for ImportAction {
                 ok!("Successfully imported project \x1bc{identifier}@{version}\x1br");
             }
             Err(e) => {
-                print_error_obj(Some("Failed to import project.".into()), Box::new(e));
+                return Err(GreathelmError::io("Failed to import project.", e));
             }
         };
+
+        return Ok(());
     }
 }
@@ -1,4 +1,6 @@
-use crate::{generator::ProjectGenerator, identify::NamespacedIdentifier, term::*};
+use crate::{
+    error::GreathelmError, generator::ProjectGenerator, identify::NamespacedIdentifier, term::*,
+};
 
 use super::Action;
 
@@ -29,13 +31,16 @@ impl Action for InitAction {
         }
     }
 
-    fn execute(&self, state: &crate::state::GreathelmState) {
+    fn requires_manifest(&self) -> bool {
+        false
+    }
+
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
         // cwd
         let cdir = match std::env::current_dir() {
             Ok(dir) => dir,
-            Err(_) => {
-                error!("Current directory is invalid.");
-                return;
+            Err(e) => {
+                return Err(GreathelmError::io("Current directory is invalid.", e));
             }
         };
 
@@ -63,10 +68,11 @@ impl Action for InitAction {
         for g in &state.generators {
             if g.get_aliases().contains(&project_type.to_lowercase()) {
                 if use_generator.is_some() {
-                    error!("Generator name \x1bc{project_type}\x1br is ambiguous in your configuration.");
-                    error!("Please specify which one you would like to use on the command line");
-                    error!("like so: \x1bc--project-type=<full.namespaced:Identifier>\x1br");
-                    std::process::exit(1);
+                    return Err(GreathelmError::ActionError(format!(
+                        "Generator name \x1bc{project_type}\x1br is ambiguous in your configuration.\n\
+                         Please specify which one you would like to use on the command line\n\
+                         like so: \x1bc--project-type=<full.namespaced:Identifier>\x1br"
+                    )));
                 } else {
                     use_generator = Some(g);
                 }
@@ -85,13 +91,15 @@ impl Action for InitAction {
         let generator = match use_generator {
             Some(generator) => generator,
             None => {
-                error!("Could not find requested generator \x1bc{project_type}\x1br");
-                error!("Are you missing a plugin?");
-                std::process::exit(1);
+                return Err(GreathelmError::ActionError(format!(
+                    "Could not find requested generator \x1bc{project_type}\x1br\nAre you missing a plugin?"
+                )));
             }
         };
         info!("Initializing current directory as Greathelm project \x1bc{project_name}\x1br");
 
-        generator.generate(std::env::current_dir().unwrap());
+        generator.generate(cdir)?;
+
+        return Ok(());
     }
 }
@@ -1,4 +1,4 @@
-use crate::term::*;
+use crate::error::GreathelmError;
 
 use super::Action;
 
@@ -23,11 +23,18 @@ impl Action for LSAction {
     fn get_aliases(&self) -> Vec<String> {
         vec!["ls".into()]
     }
-    fn execute(&self, state: &crate::state::GreathelmState) {
+    fn checks_plugin_requirements(&self) -> bool {
+        false
+    }
+    fn requires_manifest(&self) -> bool {
+        false
+    }
+
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
         if state.cli_args.len() < 3 {
-            error!("Please input a type of component to list.");
-            error!("Example: greathelm ls action");
-            return;
+            return Err(GreathelmError::ActionError(
+                "Please input a type of component to list.\nExample: greathelm ls action".into(),
+            ));
         }
 
         let lstype = state.cli_args.get(2).unwrap();
@@ -68,13 +75,24 @@ impl Action for LSAction {
             }
             "plugin" => {
                 for p in &state.plugins {
-                    println!("{};{};{};{}", p.identifier, p.name, p.vendor, p.version);
+                    println!(
+                        "{};{};{};{};compatible",
+                        p.identifier, p.name, p.vendor, p.version
+                    );
+                }
+                for p in &state.incompatible_plugins {
+                    println!("{};;;;incompatible ({})", p.path.display(), p.compatibility);
+                }
+                for p in &state.failed_plugins {
+                    println!("{};;;;failed ({})", p.path.display(), p.reason);
                 }
             }
 
             _ => {
-                error!("Unrecognized ls type.");
+                return Err(GreathelmError::ActionError("Unrecognized ls type.".into()));
             }
         }
+
+        return Ok(());
     }
 }
@@ -1,9 +1,14 @@
-use crate::{identify::NamespacedIdentifier, state::GreathelmState};
+use crate::{error::GreathelmError, identify::NamespacedIdentifier, state::GreathelmState};
 
 pub mod about;
+pub mod adddep;
+pub mod adddirective;
 pub mod build;
 pub mod checkout;
 pub mod clean;
+pub mod compdb;
+pub mod doctor;
+pub mod get;
 pub mod import;
 pub mod init;
 pub mod ls;
@@ -11,15 +16,21 @@ pub mod new;
 pub mod pkgscript;
 pub mod pkgshell;
 pub mod plugininstall;
+pub mod pluginmsg;
 pub mod pluginuninstall;
 pub mod remove;
+pub mod removedep;
+pub mod removedirective;
 pub mod resolve;
 pub mod script;
+pub mod set;
+pub mod unset;
+pub mod update;
 
 /**
  * Trait defining actions. Impl this to create command-line actions.
  */
-pub trait Action {
+pub trait Action: Send + Sync {
     /**
      * Action name. Currently unused. Will be used in a future plugin-tree.
      */
@@ -35,7 +46,22 @@ pub trait Action {
      */
     fn get_identifier(&self) -> NamespacedIdentifier;
     /**
-     * Actual action code.
+     * Actual action code. Errors are reported by the CLI, which then exits.
      */
-    fn execute(&self, state: &GreathelmState);
+    fn execute(&self, state: &GreathelmState) -> Result<(), GreathelmError>;
+    /**
+     * Whether the project's @RequirePlugin directives have to be met before this action runs.
+     * Actions managing plugins turn this off so missing plugins can be installed.
+     */
+    fn checks_plugin_requirements(&self) -> bool {
+        true
+    }
+    /**
+     * Whether this action needs the project manifest to load. Actions that don't (i.e. `set`,
+     * which may be what repairs it) still run if it is broken, with the error as a warning and a
+     * manifest holding only the command line.
+     */
+    fn requires_manifest(&self) -> bool {
+        true
+    }
 }
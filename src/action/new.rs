@@ -1,4 +1,4 @@
-use crate::term::*;
+use crate::error::GreathelmError;
 
 use super::Action;
 
@@ -27,14 +27,22 @@ impl Action for NewAction {
         }
     }
 
-    fn execute(&self, state: &crate::state::GreathelmState) {
+    fn requires_manifest(&self) -> bool {
+        false
+    }
+
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
         match state.cli_args.get(2) {
             Some(v) => {
-                crate::template::generate_from_template(&state.manifest, v.to_owned());
+                crate::template::generate_from_template(&state.manifest, v.to_owned())?;
             }
             None => {
-                error!("Please provide a template identifier.");
+                return Err(GreathelmError::ActionError(
+                    "Please provide a template identifier.".into(),
+                ));
             }
         }
+
+        return Ok(());
     }
 }
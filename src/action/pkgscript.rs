@@ -1,4 +1,7 @@
-use crate::{builder::dependency, identify::NamespacedIdentifier, term::*};
+use crate::{
+    builder::dependency, error::GreathelmError, identify::NamespacedIdentifier, lockfile::Lockfile,
+    store, term::*,
+};
 
 use super::Action;
 
@@ -27,29 +30,28 @@ impl Action for PackageScript {
         }
     }
 
-    fn execute(&self, state: &crate::state::GreathelmState) {
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
         let package = match state.cli_args.get(2) {
             Some(v) => v,
             None => {
-                error!("Please provide an identifier.");
-                std::process::exit(1);
+                return Err(GreathelmError::ActionError(
+                    "Please provide an identifier.".into(),
+                ));
             }
         };
         info!("Attempting to resolve {package}");
-        let (id, ver) = dependency::parse_dependency_notation(package.clone());
-        let path = dependency::resolve_dependency(id.clone(), ver);
-        let path = match path {
-            Some(p) => p,
-            None => {
-                error!("Could not resolve. Abort.");
-                std::process::exit(1);
-            }
-        };
+        let (id, ver) = dependency::parse_dependency_notation(package.clone())?;
+        // honor the current project's lockfile if there is one
+        let mut lock = Lockfile::read(&state.manifest)?;
+        let path =
+            dependency::resolve_locked(&store::get_store_path(), id.clone(), ver, &mut lock)?;
         let exec = match std::env::current_exe() {
             Ok(e) => e,
-            Err(_) => {
-                error!("Could not resolve the current executable path. Abort.");
-                std::process::exit(1);
+            Err(e) => {
+                return Err(GreathelmError::io(
+                    "Could not resolve the current executable path.",
+                    e,
+                ));
             }
         };
         let mut args: Vec<String> = state
@@ -72,5 +74,7 @@ impl Action for PackageScript {
             .dir(path)
             .run()
             .ok();
+
+        return Ok(());
     }
 }
@@ -1,4 +1,4 @@
-use crate::{builder::dependency, term::*};
+use crate::{builder::dependency, error::GreathelmError, lockfile::Lockfile, store, term::*};
 
 use super::Action;
 
@@ -28,7 +28,7 @@ impl Action for PackageShell {
         }
     }
 
-    fn execute(&self, state: &crate::state::GreathelmState) {
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
         let ps1 = state
             .manifest
             .get_string_property("PackageShellPS1", "[pkgshell] ");
@@ -36,28 +36,32 @@ impl Action for PackageShell {
         match state.cli_args.get(2) {
             Some(v) => {
                 info!("Attempting to resolve {v}");
-                let (id, ver) = dependency::parse_dependency_notation(v.clone());
+                let (id, ver) = dependency::parse_dependency_notation(v.clone())?;
 
-                let path = dependency::resolve_dependency(id.clone(), ver);
-                match path {
-                    Some(p) => {
-                        duct::cmd!("sh")
-                            .stderr_to_stdout()
-                            .dir(p)
-                            .env("PS1", ps1)
-                            .env("GH_PKGSHELL_NAMESPACE", id.namespace)
-                            .env("GH_PKGSHELL_IDENTIFIER", id.identifier)
-                            .run()
-                            .ok();
-                    }
-                    None => {
-                        error!("Could not resolve. Abort.");
-                    }
-                }
+                // honor the current project's lockfile if there is one
+                let mut lock = Lockfile::read(&state.manifest)?;
+                let path = dependency::resolve_locked(
+                    &store::get_store_path(),
+                    id.clone(),
+                    ver,
+                    &mut lock,
+                )?;
+                duct::cmd!("sh")
+                    .stderr_to_stdout()
+                    .dir(path)
+                    .env("PS1", ps1)
+                    .env("GH_PKGSHELL_NAMESPACE", id.namespace)
+                    .env("GH_PKGSHELL_IDENTIFIER", id.identifier)
+                    .run()
+                    .ok();
             }
             None => {
-                error!("Please provide an identifier.");
+                return Err(GreathelmError::ActionError(
+                    "Please provide an identifier.".into(),
+                ));
             }
         }
+
+        return Ok(());
     }
 }
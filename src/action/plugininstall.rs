@@ -1,11 +1,25 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use crate::{config, plugin, term::*};
+use crate::{
+    config,
+    error::GreathelmError,
+    plugin::{
+        self,
+        package::{self, PluginPackage},
+        GreathelmPlugin, PluginCompatibility, PluginLoadError,
+    },
+    state::GreathelmState,
+    term::*,
+};
 
 use super::Action;
 
 /**
- * Built-in (com.mw5ns.greathelm:PluginInstall) action to install a plugin.
+ * Built-in (com.mw5ns.greathelm:PluginInstall) action to install a plugin. Takes a single plugin
+ * library or executable, a plugin package directory or a tarball of one.
  */
 pub struct PluginInstallAction {}
 impl PluginInstallAction {
@@ -28,65 +42,191 @@ impl Action for PluginInstallAction {
         }
     }
 
-    fn execute(&self, state: &crate::state::GreathelmState) {
-        match state.cli_args.get(2) {
-            Some(v) => {
-                let path = PathBuf::from_str(v).unwrap();
-                if !path.exists() {
-                    error!("Could not access provided file.");
-                }
-                let pl = match unsafe { plugin::load_plugin_rs(path.clone()) } {
-                    Ok(p) => p,
-                    Err(_) => {
-                        error!("Failed installing plugin.");
-                        std::process::exit(1);
-                    }
-                };
-
-                let fts = pl.ghpi_first_time_setup;
-
-                info!("Running plugin setup...");
-                fts();
-
-                info!("Installing...");
-                let config_dir = config::get_config_base_dir();
-                let plugin_file = format!(
-                    "{}/plugins/{}",
-                    config_dir.display(),
-                    path.file_name().unwrap().to_str().unwrap()
-                );
-                let plugin_file = PathBuf::from_str(plugin_file.as_str()).unwrap();
-
-                if plugin_file.exists() {
-                    info!("You already have this plugin installed.");
-                    let r = question("Would you like to update it? (y/N)".into()).to_lowercase();
-                    if !r.starts_with("y") {
-                        ok!("Exitting.");
-                        return;
-                    }
-                    match std::fs::remove_file(&plugin_file) {
-                        Ok(_) => {
-                            ok!("Old copy removed.");
-                        }
-                        Err(_) => {
-                            error!("There was an error removing the old copy of the plugin!");
-                            return;
-                        }
-                    }
-                }
-
-                match std::fs::copy(path, plugin_file) {
-                    Ok(_) => {
-                        ok!("Successfully installed \x1bc{}\x1br", pl.identifier);
-                    }
-                    Err(_) => {
-                        error!("Failed to install plugin.");
-                    }
-                };
-            }
+    fn checks_plugin_requirements(&self) -> bool {
+        false
+    }
+    fn requires_manifest(&self) -> bool {
+        false
+    }
+
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
+        let path = match state.cli_args.get(2) {
+            Some(v) => PathBuf::from_str(v).unwrap(),
             None => {
-                error!("Please provide a plugin file.");
+                return Err(GreathelmError::ActionError(
+                    "Please provide a plugin file.".into(),
+                ));
+            }
+        };
+        if !path.exists() {
+            return Err(GreathelmError::ActionError(
+                "Could not access provided file.".into(),
+            ));
+        }
+
+        if package::is_archive(&path) {
+            info!("Unpacking...");
+            let root = package::unpack(&path);
+            let result = root.and_then(|root| install_package(state, &root));
+            std::fs::remove_dir_all(package::unpack_dir()).ok();
+            return result;
+        }
+        if package::is_package(&path) {
+            return install_package(state, &path);
+        }
+        return install_file(&path);
+    }
+}
+
+fn load(path: &Path) -> Result<GreathelmPlugin, GreathelmError> {
+    return match unsafe { plugin::load_plugin(path.to_path_buf()) } {
+        Ok(p) => Ok(p),
+        Err(PluginLoadError::Incompatible(c)) => Err(GreathelmError::ActionError(format!(
+            "Failed installing plugin: It {c}."
+        ))),
+        Err(PluginLoadError::Invalid(e)) => Err(GreathelmError::ActionError(format!(
+            "Failed installing plugin: {e}."
+        ))),
+    };
+}
+
+/**
+ * Installs the plugin package in `root` after checking it against this Greathelm and the
+ * installed plugins.
+ */
+fn install_package(state: &GreathelmState, root: &Path) -> Result<(), GreathelmError> {
+    let pkg = PluginPackage::read(root)?;
+    if let Some(min) = pkg.min_host_version {
+        if plugin::host_version() < min {
+            return Err(GreathelmError::ActionError(format!(
+                "Failed installing plugin: It {}.",
+                PluginCompatibility::HostTooOld(min)
+            )));
+        }
+    }
+    for (id, range) in &pkg.dependencies {
+        let installed = state.plugins.iter().find(|p| &p.identifier == id);
+        match (installed, range) {
+            (None, _) => {
+                return Err(GreathelmError::ActionError(format!(
+                    "Failed installing plugin: It requires plugin \x1bc{id}\x1br which is not installed."
+                )));
+            }
+            (Some(p), Some(r)) if !r.matches(&p.version) => {
+                return Err(GreathelmError::ActionError(format!(
+                    "Failed installing plugin: It requires plugin \x1bc{id}@{r}\x1br but version \x1bc{}\x1br is installed.",
+                    p.version
+                )));
+            }
+            _ => {}
+        }
+    }
+
+    let force = state.manifest.get_bool_property("force", false);
+    let conflicts = package::find_conflicts(&pkg)?;
+    if !conflicts.is_empty() {
+        if !force {
+            return Err(package::conflict_error(&conflicts));
+        }
+        for c in &conflicts {
+            warning!("Replacing \x1bc{}\x1br.", c.display());
+        }
+    }
+
+    // only run the plugin's code once everything the package declares checks out, and before
+    // the old copy is removed so a refused install leaves everything as it was
+    let pl = load(root)?;
+    if pl.identifier != pkg.identifier || pl.version != pkg.version {
+        return Err(GreathelmError::ActionError(format!(
+            "Failed installing plugin: The package is \x1bc{} {}\x1br but its plugin is \x1bc{} {}\x1br.",
+            pkg.identifier, pkg.version, pl.identifier, pl.version
+        )));
+    }
+
+    let dir = package::install_dir(&pkg.identifier);
+    if dir.exists() {
+        match PluginPackage::read(&dir) {
+            Ok(old) => info!(
+                "Version \x1bc{}\x1br of this plugin is installed.",
+                old.version
+            ),
+            Err(_) => info!("You already have this plugin installed."),
+        }
+        let r = question(format!(
+            "Would you like to replace it with \x1bc{}\x1br? (y/N)",
+            pkg.version
+        ))
+        .to_lowercase();
+        if !r.starts_with("y") {
+            ok!("Exitting.");
+            return Ok(());
+        }
+        package::uninstall(&dir)?;
+        ok!("Old copy removed.");
+    }
+
+    info!("Installing...");
+    package::install(&pkg, force)?;
+
+    info!("Running plugin setup...");
+    let fts = pl.ghpi_first_time_setup;
+    fts();
+
+    ok!(
+        "Successfully installed \x1bc{}\x1br \x1bc{}\x1br",
+        pl.identifier,
+        pl.version
+    );
+    return Ok(());
+}
+
+/**
+ * Installs a plugin consisting of a single library or executable.
+ */
+fn install_file(path: &Path) -> Result<(), GreathelmError> {
+    let pl = load(path)?;
+
+    let fts = pl.ghpi_first_time_setup;
+
+    info!("Running plugin setup...");
+    fts();
+
+    info!("Installing...");
+    let config_dir = config::get_config_base_dir();
+    let plugin_file = format!(
+        "{}/plugins/{}",
+        config_dir.display(),
+        path.file_name().unwrap().to_str().unwrap()
+    );
+    let plugin_file = PathBuf::from_str(plugin_file.as_str()).unwrap();
+
+    if plugin_file.exists() {
+        info!("You already have this plugin installed.");
+        let r = question("Would you like to update it? (y/N)".into()).to_lowercase();
+        if !r.starts_with("y") {
+            ok!("Exitting.");
+            return Ok(());
+        }
+        match std::fs::remove_file(&plugin_file) {
+            Ok(_) => {
+                ok!("Old copy removed.");
+            }
+            Err(e) => {
+                return Err(GreathelmError::io(
+                    "There was an error removing the old copy of the plugin!",
+                    e,
+                ));
             }
         }
     }
+
+    match std::fs::copy(path, plugin_file) {
+        Ok(_) => {
+            ok!("Successfully installed \x1bc{}\x1br", pl.identifier);
+        }
+        Err(e) => {
+            return Err(GreathelmError::io("Failed to install plugin.", e));
+        }
+    };
+    return Ok(());
 }
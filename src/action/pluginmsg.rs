@@ -0,0 +1,71 @@
+use crate::{error::GreathelmError, identify::NamespacedIdentifier, plugin::bus, term::*};
+
+use super::Action;
+
+/**
+ * Built-in (com.mw5ns.greathelm:PluginMessage) action to send a text message to a plugin and
+ * print its reply.
+ */
+pub struct PluginMessageAction {}
+impl PluginMessageAction {
+    pub fn create() -> Self {
+        Self {}
+    }
+}
+
+impl Action for PluginMessageAction {
+    fn get_name(&self) -> String {
+        "PluginMessage".into()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        vec!["plugin-msg".into(), "pluginmsg".into(), "plugmsg".into()]
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        NamespacedIdentifier {
+            namespace: "com.mw5ns.greathelm".into(),
+            identifier: "PluginMessage".into(),
+        }
+    }
+
+    fn requires_manifest(&self) -> bool {
+        false
+    }
+
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
+        let id = match state.cli_args.get(2) {
+            Some(v) => match NamespacedIdentifier::parse_text(v) {
+                Some(v) => v,
+                None => {
+                    return Err(GreathelmError::ActionError(
+                        "Could not parse provided identifier.".into(),
+                    ));
+                }
+            },
+            None => {
+                return Err(GreathelmError::ActionError(
+                    "Please provide a plugin identifier.".into(),
+                ));
+            }
+        };
+        // properties and directives on the command line aren't part of the message
+        let words: Vec<&str> = state
+            .cli_args
+            .iter()
+            .skip(3)
+            .filter(|a| !a.starts_with("--") && !a.starts_with("@"))
+            .map(|a| a.as_str())
+            .collect();
+        if words.is_empty() {
+            return Err(GreathelmError::ActionError(
+                "Please provide a message to send.".into(),
+            ));
+        }
+        let text = words.join(" ");
+
+        match bus::send_text_message(&id, &text)? {
+            Some(reply) => println!("{reply}"),
+            None => info!("Plugin \x1bc{id}\x1br did not reply."),
+        }
+        return Ok(());
+    }
+}
@@ -1,9 +1,19 @@
-use crate::{config, identify::NamespacedIdentifier, plugin, term::*};
+use crate::{
+    config,
+    error::GreathelmError,
+    identify::NamespacedIdentifier,
+    plugin::{
+        self,
+        package::{self, PluginPackage},
+    },
+    term::*,
+};
 
 use super::Action;
 
 /**
- * Built-in (com.mw5ns.greathelm:PluginUninstall) action to uninstall an installed plugin.
+ * Built-in (com.mw5ns.greathelm:PluginUninstall) action to uninstall an installed plugin. Packages
+ * are removed along with every template, script and configuration file they installed.
  */
 pub struct PluginUninstallAction {}
 impl PluginUninstallAction {
@@ -30,55 +40,86 @@ impl Action for PluginUninstallAction {
         }
     }
 
-    fn execute(&self, state: &crate::state::GreathelmState) {
-        'outer: {
-            match state.cli_args.get(2) {
-                Some(v) => {
-                    let id = match NamespacedIdentifier::parse_text(v) {
-                        Some(v) => v,
-                        None => {
-                            error!("Could not parse provided identifier.");
-                            std::process::exit(1);
-                        }
-                    };
+    fn checks_plugin_requirements(&self) -> bool {
+        false
+    }
+    fn requires_manifest(&self) -> bool {
+        false
+    }
 
-                    let plugins_dir = config::get_config_base_dir();
-                    let plugins_dir = format!("{}/plugins", plugins_dir.display());
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
+        let id = match state.cli_args.get(2) {
+            Some(v) => match NamespacedIdentifier::parse_text(v) {
+                Some(v) => v,
+                None => {
+                    return Err(GreathelmError::ActionError(
+                        "Could not parse provided identifier.".into(),
+                    ));
+                }
+            },
+            None => {
+                return Err(GreathelmError::ActionError(
+                    "Please provide a plugin identifier.".into(),
+                ));
+            }
+        };
 
-                    for f in std::fs::read_dir(plugins_dir).unwrap() {
-                        let f = match f {
-                            Ok(f) => f,
-                            Err(_) => {
-                                continue;
-                            }
-                        };
-                        let pl = match unsafe { plugin::load_plugin_rs(f.path()) } {
-                            Ok(p) => p,
-                            Err(_) => {
-                                continue;
-                            }
-                        };
-                        if pl.identifier == id {
-                            match std::fs::remove_file(f.path()) {
-                                Ok(_) => {
-                                    let func = pl.ghpi_uninstall;
-                                    func();
-                                    ok!("Successfully removed plugin.");
-                                    break 'outer;
-                                }
-                                Err(_) => {
-                                    error!("Failed to delete plugin file");
-                                }
-                            };
-                        }
-                    }
+        let plugins_dir = config::get_config_base_dir();
+        let plugins_dir = format!("{}/plugins", plugins_dir.display());
 
-                    error!("Failed to remove plugin: No plugin installed with the provided identifier.");
+        let entries = match std::fs::read_dir(plugins_dir) {
+            Ok(rd) => rd,
+            Err(e) => {
+                return Err(GreathelmError::io(
+                    "Failed to read the plugins directory.",
+                    e,
+                ));
+            }
+        };
+        for f in entries {
+            let f = match f {
+                Ok(f) => f,
+                Err(_) => {
+                    continue;
                 }
-                None => {
-                    error!("Please provide a plugin identifier.");
+            };
+            if package::is_package(&f.path()) {
+                match PluginPackage::read(&f.path()) {
+                    Ok(pkg) if pkg.identifier == id => {}
+                    _ => continue,
+                }
+                // let the plugin clean up after itself if it still loads, the package goes either way
+                if let Ok(pl) = unsafe { plugin::load_plugin(f.path()) } {
+                    let func = pl.ghpi_uninstall;
+                    func();
                 }
+                package::uninstall(&f.path())?;
+                ok!("Successfully removed plugin.");
+                return Ok(());
+            }
+            let pl = match unsafe { plugin::load_plugin(f.path()) } {
+                Ok(p) => p,
+                Err(_) => {
+                    continue;
+                }
+            };
+            if pl.identifier == id {
+                match std::fs::remove_file(f.path()) {
+                    Ok(_) => {
+                        let func = pl.ghpi_uninstall;
+                        func();
+                        ok!("Successfully removed plugin.");
+                        return Ok(());
+                    }
+                    Err(_) => {
+                        error!("Failed to delete plugin file");
+                    }
+                };
             }
         }
+
+        return Err(GreathelmError::ActionError(
+            "Failed to remove plugin: No plugin installed with the provided identifier.".into(),
+        ));
     }
 }
@@ -1,4 +1,4 @@
-use crate::{identify::NamespacedIdentifier, store, term::*};
+use crate::{error::GreathelmError, identify::NamespacedIdentifier, store, term::*};
 
 use super::Action;
 
@@ -27,20 +27,22 @@ impl Action for RemoveAction {
         }
     }
 
-    fn execute(&self, state: &crate::state::GreathelmState) {
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
         let identifier = match state.cli_args.get(2) {
             Some(v) => v,
             None => {
-                error!("Please provide an identifier.");
-                std::process::exit(1);
+                return Err(GreathelmError::ActionError(
+                    "Please provide an identifier.".into(),
+                ));
             }
         };
         info!("Attempting to resolve \x1bc{identifier}\x1br");
         let id = match NamespacedIdentifier::parse_text(identifier) {
             Some(v) => v,
             None => {
-                error!("Could not resolve \x1bc{identifier}\x1br");
-                std::process::exit(1);
+                return Err(GreathelmError::ActionError(format!(
+                    "Could not resolve \x1bc{identifier}\x1br"
+                )));
             }
         };
         let path = store::get_path(&id);
@@ -49,12 +51,16 @@ impl Action for RemoveAction {
                 Ok(_) => {
                     ok!("Succeeded in removing from store.");
                 }
-                Err(_) => {
-                    error!("Failed to remove from store.");
+                Err(e) => {
+                    return Err(GreathelmError::io("Failed to remove from store.", e));
                 }
             };
         } else {
-            error!("{identifier} is not in store.");
+            return Err(GreathelmError::ActionError(format!(
+                "{identifier} is not in store."
+            )));
         }
+
+        return Ok(());
     }
 }
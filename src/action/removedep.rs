@@ -0,0 +1,83 @@
+use crate::{
+    builder::dependency, error::GreathelmError, identify::NamespacedIdentifier,
+    manifest::document::ManifestDocument, term::*,
+};
+
+use super::{adddep::same_store_dependency, Action};
+
+/**
+ * Built-in (io.github.madelynwith5ns.greathelm:RemoveDependency) action removing @Dependency
+ * directives from Project.ghm (or Project.local.ghm with `--local`). Store dependencies are
+ * removed by identifier whatever version they ask for.
+ */
+pub struct RemoveDependencyAction {}
+impl RemoveDependencyAction {
+    pub fn create() -> Self {
+        Self {}
+    }
+}
+
+impl Action for RemoveDependencyAction {
+    fn get_name(&self) -> String {
+        "RemoveDependency".into()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        vec![
+            "remove-dep".into(),
+            "rm-dep".into(),
+            "remove-dependency".into(),
+        ]
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        NamespacedIdentifier {
+            namespace: "io.github.madelynwith5ns.greathelm".into(),
+            identifier: "RemoveDependency".into(),
+        }
+    }
+    fn checks_plugin_requirements(&self) -> bool {
+        false
+    }
+    fn requires_manifest(&self) -> bool {
+        false
+    }
+
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
+        let deps: Vec<&String> = state
+            .cli_args
+            .iter()
+            .skip(2)
+            .filter(|a| !a.starts_with("--"))
+            .collect();
+        if deps.is_empty() {
+            return Err(GreathelmError::ActionError(
+                "Please provide a dependency to remove.".into(),
+            ));
+        }
+
+        let (path, mut document) =
+            ManifestDocument::read_project(state.manifest.get_bool_property("local", false))?;
+        let mut missing = Vec::new();
+        for dep in deps {
+            let removed = if dependency::is_store_dependency(dep) {
+                let (id, _) = dependency::parse_dependency_notation(dep.clone())?;
+                document.remove_directives("Dependency", |v| same_store_dependency(v, &id))
+            } else {
+                document.remove_directives("Dependency", |v| v.trim() == dep)
+            };
+            if removed == 0 {
+                missing.push(dep.clone());
+            } else {
+                ok!("Removed dependency \x1bc{dep}\x1br.");
+            }
+        }
+        document.write(&path)?;
+        if !missing.is_empty() {
+            return Err(GreathelmError::ActionError(format!(
+                "Not a dependency in {}: \x1bc{}\x1br",
+                path.display(),
+                missing.join(", ")
+            )));
+        }
+        return Ok(());
+    }
+}
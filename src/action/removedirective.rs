@@ -0,0 +1,53 @@
+use crate::{
+    error::GreathelmError, identify::NamespacedIdentifier, manifest::document::ManifestDocument,
+    term::*,
+};
+
+use super::{adddirective::directive_args, Action};
+
+/**
+ * Built-in (io.github.madelynwith5ns.greathelm:RemoveDirective) action removing every directive
+ * with the given value from Project.ghm (or Project.local.ghm with `--local`).
+ */
+pub struct RemoveDirectiveAction {}
+impl RemoveDirectiveAction {
+    pub fn create() -> Self {
+        Self {}
+    }
+}
+
+impl Action for RemoveDirectiveAction {
+    fn get_name(&self) -> String {
+        "RemoveDirective".into()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        vec!["remove-directive".into(), "rm-directive".into()]
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        NamespacedIdentifier {
+            namespace: "io.github.madelynwith5ns.greathelm".into(),
+            identifier: "RemoveDirective".into(),
+        }
+    }
+    fn checks_plugin_requirements(&self) -> bool {
+        false
+    }
+    fn requires_manifest(&self) -> bool {
+        false
+    }
+
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
+        let (name, value) = directive_args(&state.cli_args)?;
+        let (path, mut document) =
+            ManifestDocument::read_project(state.manifest.get_bool_property("local", false))?;
+        if document.remove_directives(&name, |v| v.trim() == value) == 0 {
+            return Err(GreathelmError::ActionError(format!(
+                "There is no \x1bc@{name} {value}\x1br in {}.",
+                path.display()
+            )));
+        }
+        document.write(&path)?;
+        ok!("Removed \x1bc@{name} {value}\x1br from {}.", path.display());
+        return Ok(());
+    }
+}
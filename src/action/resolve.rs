@@ -1,4 +1,6 @@
-use crate::{builder::dependency, identify::NamespacedIdentifier, term::*};
+use crate::{
+    builder::dependency, error::GreathelmError, identify::NamespacedIdentifier, store, term::*,
+};
 
 use super::Action;
 
@@ -27,26 +29,39 @@ impl Action for ResolveAction {
         }
     }
 
-    fn execute(&self, state: &crate::state::GreathelmState) {
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
         let package = match state.cli_args.get(2) {
             Some(v) => v,
             None => {
-                error!("Please provide a package.");
-                std::process::exit(1);
+                return Err(GreathelmError::ActionError(
+                    "Please provide a package.".into(),
+                ));
             }
         };
         // resolve the package
         info!("Parsing notation \x1bc{package}\x1br");
-        let (id, _) = dependency::parse_dependency_notation(package.clone());
+        let (id, range) = dependency::parse_dependency_notation(package.clone())?;
 
         info!("Querying versions...");
-        let versions = dependency::get_all_versions(&id);
+        let mut versions = dependency::get_all_versions(&store::get_store_path(), &id)?;
         if versions.is_empty() {
             warning!("There are no versions of this package present!");
-            return;
+            return Ok(());
         }
-        for v in versions {
-            info!("Version: @\x1bc{v}\x1br (include via: \x1bc{id}@{v}\x1br)");
+        versions.sort();
+        for v in &versions {
+            match range {
+                Some(ref r) if !r.matches(v) => {}
+                _ => info!("Version: @\x1bc{v}\x1br (include via: \x1bc{id}@{v}\x1br)"),
+            }
+        }
+        if let Some(r) = range {
+            match r.select(&versions) {
+                Some(v) => ok!("\x1bc{id}@{r}\x1br resolves to \x1bc{v}\x1br"),
+                None => warning!("No present version satisfies \x1bc{r}\x1br."),
+            }
         }
+
+        return Ok(());
     }
 }
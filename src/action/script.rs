@@ -1,4 +1,4 @@
-use crate::{script, term::*};
+use crate::{error::GreathelmError, script};
 
 use super::Action;
 
@@ -27,17 +27,22 @@ impl Action for ScriptAction {
         }
     }
 
-    fn execute(&self, state: &crate::state::GreathelmState) {
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
         match state.cli_args.get(2) {
             Some(v) => {
                 script::run_script(
+                    &state.manifest.project_dir(),
                     v,
                     state.cli_args.iter().skip(3).map(|f| f.clone()).collect(),
                 );
             }
             None => {
-                error!("Please provide a script name.");
+                return Err(GreathelmError::ActionError(
+                    "Please provide a script name.".into(),
+                ));
             }
         }
+
+        return Ok(());
     }
 }
@@ -0,0 +1,89 @@
+use crate::{
+    error::GreathelmError, identify::NamespacedIdentifier, manifest::document::ManifestDocument,
+    term::*,
+};
+
+use super::Action;
+
+/**
+ * Built-in (io.github.madelynwith5ns.greathelm:Set) action setting a property in Project.ghm (or
+ * Project.local.ghm with `--local`). Takes either `<Key> <value>` or `<Key>=<value>`.
+ */
+pub struct SetAction {}
+impl SetAction {
+    pub fn create() -> Self {
+        Self {}
+    }
+}
+
+impl Action for SetAction {
+    fn get_name(&self) -> String {
+        "Set".into()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        vec!["set".into()]
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        NamespacedIdentifier {
+            namespace: "io.github.madelynwith5ns.greathelm".into(),
+            identifier: "Set".into(),
+        }
+    }
+    fn checks_plugin_requirements(&self) -> bool {
+        false
+    }
+    fn requires_manifest(&self) -> bool {
+        false
+    }
+
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
+        // flags like --local aren't part of the value
+        let args: Vec<&str> = state
+            .cli_args
+            .iter()
+            .skip(2)
+            .filter(|a| !a.starts_with("--"))
+            .map(|a| a.as_str())
+            .collect();
+        let (key, value) = match args.split_first() {
+            Some((first, rest)) => match first.split_once("=") {
+                Some((k, v)) if rest.is_empty() => (k.to_string(), v.to_string()),
+                _ if !rest.is_empty() => (first.to_string(), rest.join(" ")),
+                _ => {
+                    return Err(GreathelmError::ActionError(format!(
+                        "Please provide a value for \x1bc{first}\x1br."
+                    )));
+                }
+            },
+            None => {
+                return Err(GreathelmError::ActionError(
+                    "Please provide a property and a value to set.".into(),
+                ));
+            }
+        };
+        if key.is_empty()
+            || key.contains(char::is_whitespace)
+            || key.ends_with("+")
+            || key.starts_with(['@', '#'])
+        {
+            return Err(GreathelmError::ActionError(format!(
+                "\x1bc{key}\x1br is not a valid property name."
+            )));
+        }
+        if value.contains("\n") {
+            return Err(GreathelmError::ActionError(
+                "Property values can't contain line breaks.".into(),
+            ));
+        }
+
+        let (path, mut document) =
+            ManifestDocument::read_project(state.manifest.get_bool_property("local", false))?;
+        document.set_property(&key, &value);
+        document.write(&path)?;
+        ok!(
+            "Set \x1bc{key}\x1br to \x1bc{value}\x1br in {}.",
+            path.display()
+        );
+        return Ok(());
+    }
+}
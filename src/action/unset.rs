@@ -0,0 +1,64 @@
+use crate::{
+    error::GreathelmError, identify::NamespacedIdentifier, manifest::document::ManifestDocument,
+    term::*,
+};
+
+use super::Action;
+
+/**
+ * Built-in (io.github.madelynwith5ns.greathelm:Unset) action removing properties from Project.ghm
+ * (or Project.local.ghm with `--local`).
+ */
+pub struct UnsetAction {}
+impl UnsetAction {
+    pub fn create() -> Self {
+        Self {}
+    }
+}
+
+impl Action for UnsetAction {
+    fn get_name(&self) -> String {
+        "Unset".into()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        vec!["unset".into()]
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        NamespacedIdentifier {
+            namespace: "io.github.madelynwith5ns.greathelm".into(),
+            identifier: "Unset".into(),
+        }
+    }
+    fn checks_plugin_requirements(&self) -> bool {
+        false
+    }
+    fn requires_manifest(&self) -> bool {
+        false
+    }
+
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
+        let keys: Vec<&String> = state
+            .cli_args
+            .iter()
+            .skip(2)
+            .filter(|a| !a.starts_with("--"))
+            .collect();
+        if keys.is_empty() {
+            return Err(GreathelmError::ActionError(
+                "Please provide a property to unset.".into(),
+            ));
+        }
+
+        let (path, mut document) =
+            ManifestDocument::read_project(state.manifest.get_bool_property("local", false))?;
+        for k in keys {
+            if document.unset_property(k) {
+                ok!("Unset \x1bc{k}\x1br in {}.", path.display());
+            } else {
+                warning!("\x1bc{k}\x1br is not set in {}.", path.display());
+            }
+        }
+        document.write(&path)?;
+        return Ok(());
+    }
+}
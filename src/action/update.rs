@@ -0,0 +1,115 @@
+use crate::{
+    builder::dependency::DependencyGraph, error::GreathelmError, identify::NamespacedIdentifier,
+    lockfile::Lockfile, term::*,
+};
+
+use super::Action;
+
+/**
+ * Built-in action (io.github.madelynwith5ns.greathelm:Update) for deliberately re-resolving the
+ * store dependencies recorded in Project.lock.ghm. With no arguments every dependency is
+ * re-resolved, otherwise only the identifiers given (and whatever they depend on) are.
+ */
+pub struct UpdateAction {}
+impl UpdateAction {
+    pub fn create() -> Self {
+        Self {}
+    }
+}
+
+impl Action for UpdateAction {
+    fn get_name(&self) -> String {
+        "Update".into()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        vec!["update".into()]
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        NamespacedIdentifier {
+            namespace: "io.github.madelynwith5ns.greathelm".into(),
+            identifier: "Update".into(),
+        }
+    }
+
+    fn execute(&self, state: &crate::state::GreathelmState) -> Result<(), GreathelmError> {
+        let old = Lockfile::read(&state.manifest)?;
+        let mut lock = Lockfile::read(&state.manifest)?;
+
+        let mut targets: Vec<NamespacedIdentifier> = Vec::new();
+        for a in state.cli_args.iter().skip(2) {
+            if a.starts_with("--") || a.starts_with("@") {
+                continue;
+            }
+            match NamespacedIdentifier::parse_text(a) {
+                Some(id) => targets.push(id),
+                None => {
+                    return Err(GreathelmError::ActionError(format!(
+                        "Could not parse identifier \x1bc{a}\x1br."
+                    )));
+                }
+            }
+        }
+
+        if targets.is_empty() {
+            info!("Re-resolving all dependencies...");
+            lock.entries.clear();
+        } else {
+            let keys: Vec<String> = targets.iter().map(|t| format!("{t}")).collect();
+            for k in &keys {
+                if !lock.entries.contains_key(k) {
+                    warning!("\x1bc{k}\x1br is not in the lockfile.");
+                }
+            }
+            // whatever the updated packages depend on moves along with them, so find out what
+            // that is with them re-resolved
+            let mut scratch = lock.clone();
+            scratch.retain(|id| !keys.contains(id));
+            let graph = match DependencyGraph::resolve(&state.manifest, &mut scratch) {
+                Ok(g) => g,
+                Err(e) => {
+                    return Err(GreathelmError::DependencyError(format!(
+                        "{}\nFailed to resolve dependencies. The lockfile was not changed.",
+                        e.message()
+                    )));
+                }
+            };
+            let closure = graph.closure(&keys);
+            lock.retain(|id| !keys.contains(id) && !closure.contains(id));
+        }
+        lock.changed = true;
+
+        match DependencyGraph::resolve(&state.manifest, &mut lock) {
+            Ok(graph) => graph.prune_lock(&mut lock),
+            Err(e) => {
+                return Err(GreathelmError::DependencyError(format!(
+                    "{}\nFailed to resolve dependencies. The lockfile was not changed.",
+                    e.message()
+                )));
+            }
+        }
+
+        // report what changed
+        for (id, entry) in &lock.entries {
+            match old.entries.get(id) {
+                Some(o) if o == entry => {}
+                Some(o) => {
+                    ok!(
+                        "Updated \x1bc{id}\x1br \x1bc{}\x1br -> \x1bc{}\x1br",
+                        o.version,
+                        entry.version
+                    );
+                }
+                None => {
+                    ok!("Locked \x1bc{id}@{}\x1br", entry.version);
+                }
+            }
+        }
+        for id in old.entries.keys() {
+            if !lock.entries.contains_key(id) {
+                info!("Removed \x1bc{id}\x1br from the lockfile.");
+            }
+        }
+
+        return lock.save();
+    }
+}
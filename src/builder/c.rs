@@ -1,18 +1,22 @@
-use std::{
-    collections::HashMap,
-    io::Write,
-    path::{Path, PathBuf},
-    process::Command,
-    str::FromStr,
-    sync::Arc,
-};
+use std::{collections::HashMap, path::PathBuf, process::Command, str::FromStr, sync::Arc};
 
 use crate::{
-    builder::{dependency, parallel::ParallelBuild},
+    builder::{
+        compdb::{self, CompileCommand, CompileFlags},
+        dependency::{self, DependencyGraph},
+        jobserver,
+        parallel::{self, ParallelBuild},
+        schema::{self, PropertyKind, PropertySchema},
+    },
+    error::GreathelmError,
     ibht,
+    lockfile::Lockfile,
     manifest::ProjectManifest,
-    script, subprocess,
-    term::*,
+    plugin::hooks::{self, HookPoint},
+    probe::{self, ToolRequirement},
+    profile, script, subprocess,
+    target::Toolchain,
+    term::{self, *},
 };
 
 use super::ProjectBuilder;
@@ -41,15 +45,22 @@ impl ProjectBuilder for CBuilder {
             identifier: "C".into(),
         };
     }
-    fn build(&self, manifest: &ProjectManifest) {
-        script::run_script("prebuild", vec![]);
+    fn build(&self, manifest: &ProjectManifest) -> Result<(), GreathelmError> {
+        // compilers and scripts run in the project, files are read through it
+        let root = manifest.project_dir();
+        // a compilation database is written without building anything, so no scripts either
+        let compdb_only = manifest.get_bool_property("compile-commands-only", false);
+        if !compdb_only {
+            script::run_script(&root, "prebuild", vec![]);
+        }
 
         // Settings
-        let cc = manifest.get_string_property("Override-C-Compiler", "cc");
-        let ld = manifest.get_string_property("Override-C-Linker", "cc");
+        let toolchain = Toolchain::from_manifest(manifest, "C");
+        let cc = toolchain.compiler.clone();
+        let ld = toolchain.linker.clone();
         let opt = manifest.get_string_property("Compiler-Opt-Level", "2");
         let artifact = manifest.get_string_property("Executable-Name", "binary");
-        let cflags = match manifest.properties.get("Additional-CC-Flags".into()) {
+        let cflags: Vec<String> = match manifest.properties.get("Additional-CC-Flags".into()) {
             Some(cf) => cf.split(",").map(|f| f.to_string()).collect(),
             None => {
                 vec![]
@@ -61,6 +72,14 @@ impl ProjectBuilder for CBuilder {
                 vec![]
             }
         }; // LDFLAGS comma separated
+           // the target's flags go first so the project's own flags can override them
+        let target_flags = toolchain.target_flags();
+        let cflags: Vec<String> = target_flags.iter().cloned().chain(cflags).collect();
+        let ldflags: Vec<&str> = target_flags
+            .iter()
+            .map(|f| f.as_str())
+            .chain(ldflags)
+            .collect();
         let mut emit = manifest.get_string_property("Emit", "binary");
         if emit == "binary" || emit == "executable" {
             info!("Emitting an \x1bcExecutable Binary\x1br");
@@ -77,14 +96,24 @@ impl ProjectBuilder for CBuilder {
         let debug_info = manifest.get_bool_property("debug-info", false);
         let force_full_rebuild = manifest.get_bool_property("force-full-rebuild", false);
         let inspect = manifest.get_bool_property("inspect-commands", false);
+        let keep_going = manifest.get_bool_property("keep-going", false);
+        let write_compdb = compdb_only || manifest.get_bool_property("compile-commands", false);
+        let build_dir = profile::build_dir(manifest).display().to_string();
 
         info!("Using CC \x1bc{cc}\x1br");
         info!("Using LD \x1bc{ld}\x1br");
+        if let Some(triple) = &toolchain.triple {
+            info!("Targeting \x1bc{triple}\x1br");
+        }
+        if !compdb_only {
+            probe::require(&self.get_required_tools(manifest))?;
+        }
 
         // find things that changed and should be rebuilt
         info!("Hashing project files...");
-        let hashes = ibht::gen_hashtable();
-        let ibht = ibht::read_ibht();
+        let hashes = ibht::gen_hashtable(&root)?;
+        let ibht = ibht::read_ibht(&manifest.project_path(profile::ibht_path(manifest)))?;
+        let mut ibdt = ibht::read_ibdt(&manifest.project_path(profile::ibdt_path(manifest)))?;
 
         let mut rebuild: HashMap<PathBuf, String> = HashMap::new();
         let mut link: Vec<String> = Vec::new();
@@ -96,7 +125,7 @@ impl ProjectBuilder for CBuilder {
 
             let path = PathBuf::from_str(k).unwrap();
             link.push(format!(
-                "build/{}-{}.o",
+                "{build_dir}/{}-{}.o",
                 str::replace(path.display().to_string().as_str(), "/", "_"),
                 hashes.get(k).unwrap()
             ));
@@ -124,6 +153,17 @@ impl ProjectBuilder for CBuilder {
                     PathBuf::from_str(k).unwrap(),
                     hashes.get(k).unwrap().to_owned(),
                 );
+                continue;
+            }
+            // headers included by this file (directly or through other headers)
+            if let Some(deps) = ibdt.get(k) {
+                if let Some(header) = ibht::find_changed_header(&root, deps) {
+                    info!("Header \x1bc{header}\x1br changed. \x1bc{k}\x1br will be rebuilt.");
+                    rebuild.insert(
+                        PathBuf::from_str(k).unwrap(),
+                        hashes.get(k).unwrap().to_owned(),
+                    );
+                }
             }
         }
 
@@ -132,7 +172,12 @@ impl ProjectBuilder for CBuilder {
         // Resolve Dependencies
         let mut link_dep_args: Vec<String> = Vec::new();
         let mut cc_dep_args: Vec<String> = Vec::new();
-        let dependencies = manifest.directives.get("Dependency").unwrap();
+        let no_dependencies = Vec::new();
+        let dependencies = manifest
+            .directives
+            .get("Dependency")
+            .unwrap_or(&no_dependencies);
+        let mut lock = Lockfile::read(manifest)?;
 
         // raw object (.o) dependencies
         for dep in dependencies {
@@ -146,119 +191,133 @@ impl ProjectBuilder for CBuilder {
 
         // normal dependencies
         for dep in dependencies {
-            if dep.starts_with("raw/") {
-                continue;
-            }
-            if dep.starts_with("sys/") {
-                let dep = dep.split_once("sys/").unwrap().1;
-                let pkgconf = Command::new("pkgconf")
-                    .arg("--libs")
-                    .arg("--cflags")
-                    .arg(dep)
-                    .output()
-                    .unwrap();
-                let dep_ld_flags = String::from_utf8(pkgconf.stdout).unwrap();
-                let dep_ld_flags = dep_ld_flags.split(" ");
-                for flag in dep_ld_flags {
-                    if flag == " " {
-                        continue;
-                    }
-                    if flag == "\n" {
-                        continue;
-                    }
-                    link_dep_args.push(flag.into());
+            if let Some(sys) = dep.strip_prefix("sys/") {
+                for flag in dependency::system_dependency_flags(sys, &toolchain)? {
                     if flag.starts_with("-I") {
-                        cc_dep_args.push(flag.into());
+                        cc_dep_args.push(flag.clone());
                     }
+                    link_dep_args.push(flag);
                 }
             } else if dep.starts_with("provided/") {
                 link_dep_args.push(format!("-l{}", dep.split_once("provided/").unwrap().1));
-            } else {
-                let (id, ver) = dependency::parse_dependency_notation(dep.into());
-                let resolved = dependency::resolve_dependency(id, ver);
-                if resolved.is_some() {
-                    let resolved = resolved.unwrap();
-                    subprocess::build_project(&resolved);
-
-                    link_dep_args.push(format!("-L{}", format!("{}/build", resolved.display())));
-                    link_dep_args.push(format!("-I{}", format!("{}/export", resolved.display())));
-                    cc_dep_args.push(format!("-I{}", format!("{}/export", resolved.display())));
-
-                    let mut dep_manifest: ProjectManifest = ProjectManifest::new();
-                    dep_manifest.read_and_append(
-                        &PathBuf::from_str(format!("{}/Project.ghm", resolved.display()).as_str())
-                            .unwrap(),
-                    );
-                    link_dep_args.push(format!(
-                        "-l{}",
-                        dep_manifest.get_string_property("Executable-Name", "LIBRESOLVEERROR")
-                    ));
-                } else {
-                    error!("Failed to resolve a dependency. Abort.");
-                    std::process::exit(1);
-                }
             }
         }
 
-        let cc_dep_args = Arc::new(cc_dep_args);
+        // store dependencies and everything they depend on
+        let graph = DependencyGraph::resolve(manifest, &mut lock)?;
+        // writing a compilation database leaves the lockfile alone
+        if !compdb_only {
+            lock.save()?;
+        }
+        let (graph_cc_args, graph_link_args) = graph.c_flags(manifest, &toolchain)?;
+        cc_dep_args.extend(graph_cc_args);
+        link_dep_args.extend(graph_link_args);
+
+        let flags = Arc::new(CompileFlags {
+            opt,
+            cflags,
+            dep_args: cc_dep_args,
+            debug_info,
+        });
+
+        // compilation database
+        if write_compdb {
+            let directory = match std::fs::canonicalize(&root) {
+                Ok(d) => d.display().to_string(),
+                Err(_) => ".".into(),
+            };
+            let mut commands: Vec<CompileCommand> = Vec::new();
+            let mut sources: Vec<&String> = hashes.keys().collect();
+            sources.sort();
+            for k in sources {
+                if k.ends_with(".h") {
+                    continue;
+                }
+                let output = format!(
+                    "{build_dir}/{}-{}.o",
+                    k.replace("/", "_"),
+                    hashes.get(k).unwrap()
+                );
+                let mut arguments = vec![cc.clone()];
+                arguments.extend(flags.arguments(k, &output, None));
+                commands.push(CompileCommand {
+                    directory: directory.clone(),
+                    file: k.clone(),
+                    arguments,
+                    output,
+                });
+            }
+            compdb::write_compile_commands(&root, &commands)?;
+            if compdb_only {
+                return Ok(());
+            }
+        }
 
         // setup parallel build
-        let cpus = manifest.get_usize_property(
-            "build-cpus",
-            match std::thread::available_parallelism() {
-                Ok(v) => v.get(),
-                Err(_) => 4, // 4 feels like a safe-ish default
-            },
-        );
+        let cpus = parallel::get_build_cpus(manifest);
 
         info!("Building in parallel with \x1bc{cpus}\x1br CPUs...");
-        let mut build = ParallelBuild::new(cpus, rebuild.len());
+        let mut build = ParallelBuild::new(cpus, keep_going);
+
+        // plugins may hook into compiling each file
+        let hook_manifest = Arc::new(manifest.clone());
 
         // actually build all the things
         for f in rebuild.keys() {
             let file = rebuild.get(f).unwrap().to_owned();
             let f = f.to_owned();
             let cc = cc.clone();
-            let cflags = cflags.clone();
-            let opt = opt.clone();
             outs.push(format!(
-                "build/{}.o",
+                "{build_dir}/{}.o",
                 f.clone().file_name().unwrap().to_string_lossy()
             ));
-            let a = Arc::clone(&cc_dep_args);
-            build.submit(move || {
-                if script::has_script("compiler") {
-                    script::run_script(
+            let build_dir = build_dir.clone();
+            let flags = Arc::clone(&flags);
+            let hook_manifest = Arc::clone(&hook_manifest);
+            let root = root.clone();
+            let name = f.display().to_string();
+            build.submit(&name, move || {
+                let object = PathBuf::from(format!(
+                    "{build_dir}/{}-{}.o",
+                    str::replace(f.as_path().display().to_string().as_str(), "/", "_"),
+                    file
+                ));
+                let run_hooks = |point| {
+                    hooks::run_hooks(point, &hook_manifest, Some(f.clone()), Some(object.clone()))
+                };
+                run_hooks(HookPoint::BeforeCompile)?;
+
+                if script::has_script(&root, "compiler") {
+                    if !script::run_script(
+                        &root,
                         "compiler",
                         vec![
                             format!("{}", f.display()),
                             format!(
-                                "build/{}-{}.o",
+                                "{build_dir}/{}-{}.o",
                                 str::replace(f.as_path().display().to_string().as_str(), "/", "_"),
                                 file
                             ),
                         ],
-                    );
+                    ) {
+                        return Err(GreathelmError::BuildError(format!(
+                            "Script \x1bccompiler\x1br failed for \x1bc{}\x1br",
+                            f.display()
+                        )));
+                    }
+                    return run_hooks(HookPoint::AfterCompile);
                 } else {
                     let mut cc_incantation = Command::new(cc.clone());
-                    cc_incantation
-                        .arg("-c") // dont link
-                        .arg("-o") // output
-                        .arg(format!(
-                            "build/{}-{}.o",
-                            str::replace(f.as_path().display().to_string().as_str(), "/", "_"),
-                            file
-                        ))
-                        .arg(format!("-O{opt}")) // -Oopt from earlier
-                        .arg("-Wall") // -Wall
-                        .args(cflags.clone()) // the funny cflags
-                        .args(a.iter())
-                        .arg(format!("{}", f.display())); // actual file
-
-                    // debug information
-                    if debug_info {
-                        cc_incantation.arg("-g");
-                    }
+                    cc_incantation.current_dir(&root);
+                    jobserver::export(&mut cc_incantation);
+                    let source = f.display().to_string();
+                    let output = object.display().to_string();
+                    let depfile = format!(
+                        "{build_dir}/{}-{}.d",
+                        str::replace(source.as_str(), "/", "_"),
+                        file
+                    );
+                    cc_incantation.args(flags.arguments(&source, &output, Some(&depfile)));
 
                     if inspect {
                         let a: Vec<_> = cc_incantation
@@ -268,28 +327,40 @@ impl ProjectBuilder for CBuilder {
                         info!("{cc} {}", a.join(" "));
                     }
 
-                    let cc_incantation = cc_incantation
+                    let cc_incantation = match cc_incantation
                         .stdout(std::process::Stdio::piped())
                         .stderr(std::process::Stdio::piped())
                         .output()
-                        .unwrap();
-                    print!("{}", String::from_utf8(cc_incantation.stdout).unwrap());
-                    eprint!("{}", String::from_utf8(cc_incantation.stderr).unwrap());
-                    std::io::stdout().flush().ok();
-                    std::io::stderr().flush().ok();
-                    if cc_incantation.status.success() {
-                        // result message
-                        ok!("CC \x1bc{}\x1br", f.display());
-                    } else {
-                        error!("CC \x1bc{}\x1br", f.display());
-                        std::process::exit(1);
+                    {
+                        Ok(o) => o,
+                        Err(e) => {
+                            return Err(GreathelmError::io(
+                                format!("Failed to run \x1bc{cc}\x1br."),
+                                e,
+                            ));
+                        }
+                    };
+                    term::print_raw(&String::from_utf8_lossy(&cc_incantation.stdout), false);
+                    term::print_raw(&String::from_utf8_lossy(&cc_incantation.stderr), true);
+                    if !cc_incantation.status.success() {
+                        return Err(GreathelmError::BuildError(format!(
+                            "CC \x1bc{}\x1br",
+                            f.display()
+                        )));
                     }
+                    // result message
+                    ok!("CC \x1bc{}\x1br", f.display());
+                    return run_hooks(HookPoint::AfterCompile);
                 }
             });
         }
 
         // wait for compiling to finish
-        build.wait();
+        build.finish("Failed to compile")?;
+
+        // collect the header dependencies of everything we just compiled
+        ibht::collect_depfiles(&root, &build_dir, &rebuild, &mut ibdt);
+        ibdt.retain(|k, _| hashes.contains_key(k));
 
         // link
         let mut prefix = "";
@@ -305,32 +376,45 @@ impl ProjectBuilder for CBuilder {
 
         // we dont need the linker on static libraries
         if emit == "staticlib" {
-            let mut args = vec!["rcs".into(), format!("build/{prefix}{artifact}{suffix}")];
+            let mut args = vec![
+                "rcs".into(),
+                format!("{build_dir}/{prefix}{artifact}{suffix}"),
+            ];
             args.append(&mut link);
-            let ar = match duct::cmd("ar", args).stderr_to_stdout().run() {
+            info!("Using AR \x1bc{}\x1br", toolchain.archiver);
+            let ar = match subprocess::run(duct::cmd(&toolchain.archiver, args).dir(&root)) {
                 Ok(v) => v,
-                Err(_) => {
-                    error!("Failed to bundle static library.");
-                    std::process::exit(1);
+                Err(e) => {
+                    return Err(GreathelmError::io(
+                        format!("Failed to run \x1bc{}\x1br.", toolchain.archiver),
+                        e,
+                    ));
                 }
             };
             if ar.status.success() {
                 ok!("Successfully bundled static library.");
             } else {
-                error!("Failed to bundle static library.");
-                std::process::exit(1);
+                return Err(GreathelmError::BuildError(
+                    "Failed to bundle static library.".into(),
+                ));
             }
         } else {
-            if script::has_script("linker") {
-                let mut args: Vec<String> = vec![format!("build/{prefix}{artifact}{suffix}")];
+            if script::has_script(&root, "linker") {
+                let mut args: Vec<String> = vec![format!("{build_dir}/{prefix}{artifact}{suffix}")];
                 args.append(&mut link);
-                script::run_script("linker", args);
+                if !script::run_script(&root, "linker", args) {
+                    return Err(GreathelmError::BuildError(format!(
+                        "Script \x1bclinker\x1br failed for \x1bc{artifact}\x1br"
+                    )));
+                }
             } else {
                 let mut ld_incantation = Command::new(ld.clone());
+                ld_incantation.current_dir(&root);
+                jobserver::export(&mut ld_incantation);
 
                 let ld_incantation = ld_incantation
                     .arg("-o")
-                    .arg(format!("build/{prefix}{artifact}{suffix}"))
+                    .arg(format!("{build_dir}/{prefix}{artifact}{suffix}"))
                     .args(ldflags.clone())
                     .args(link)
                     .arg("-I./lib/include") // local lib headers
@@ -348,16 +432,14 @@ impl ProjectBuilder for CBuilder {
                 if manifest
                     .directives
                     .get("Directive")
-                    .unwrap()
-                    .contains(&"no-link-libc".into())
+                    .is_some_and(|d| d.contains(&"no-link-libc".into()))
                 {
                     ld_incantation.arg("-nostdlib");
                 }
                 if manifest
                     .directives
                     .get("Directive")
-                    .unwrap()
-                    .contains(&"ffreestanding".into())
+                    .is_some_and(|d| d.contains(&"ffreestanding".into()))
                 {
                     ld_incantation.arg("-ffreestanding");
                 }
@@ -380,67 +462,156 @@ impl ProjectBuilder for CBuilder {
                 }
 
                 // finally, actually link
-                let ld_incantation = ld_incantation.output().unwrap();
-
-                print!("{}", String::from_utf8(ld_incantation.stdout).unwrap());
-                eprint!("{}", String::from_utf8(ld_incantation.stderr).unwrap());
+                let ld_incantation = match ld_incantation.output() {
+                    Ok(o) => o,
+                    Err(e) => {
+                        return Err(GreathelmError::io(
+                            format!("Failed to run \x1bc{ld}\x1br."),
+                            e,
+                        ));
+                    }
+                };
 
-                std::io::stdout().flush().ok();
-                std::io::stderr().flush().ok();
+                term::print_raw(&String::from_utf8_lossy(&ld_incantation.stdout), false);
+                term::print_raw(&String::from_utf8_lossy(&ld_incantation.stderr), true);
 
                 if ld_incantation.status.success() {
                     ok!("LD \x1bc{artifact}");
                 } else {
-                    error!("LD \x1bc{artifact}");
+                    return Err(GreathelmError::BuildError(format!(
+                        "LD \x1bc{artifact}\x1br"
+                    )));
                 }
             }
         }
 
+        hooks::run_hooks(
+            HookPoint::AfterLink,
+            manifest,
+            None,
+            Some(PathBuf::from(format!(
+                "{build_dir}/{prefix}{artifact}{suffix}"
+            ))),
+        )?;
+
         info!("Regenerating IBHT for future runs...");
-        ibht::write_ibht();
+        ibht::write_ibht(&root, &manifest.project_path(profile::ibht_path(manifest)))?;
+        ibht::write_ibdt(&manifest.project_path(profile::ibdt_path(manifest)), &ibdt)?;
+        return Ok(());
     }
 
     fn validate(&self, _manifest: &ProjectManifest) -> bool {
         return true;
     }
 
-    fn cleanup(&self, manifest: &ProjectManifest) {
-        let ibht = ibht::read_ibht();
+    fn get_property_schema(&self) -> Vec<PropertySchema> {
+        let one_of =
+            |values: &[&str]| PropertyKind::OneOf(values.iter().map(|v| v.to_string()).collect());
+        let mut schema = vec![
+            PropertySchema::create(
+                "Override-C-Compiler",
+                PropertyKind::Text,
+                "C compiler to use instead of `cc`.",
+            ),
+            PropertySchema::create(
+                "Override-C-Linker",
+                PropertyKind::Text,
+                "Linker to use instead of `cc`.",
+            ),
+            PropertySchema::create(
+                "Compiler-Opt-Level",
+                one_of(&["0", "1", "2", "3", "s", "z", "g", "fast"]),
+                "Optimization level passed as `-O<level>`.",
+            ),
+            PropertySchema::create(
+                "Executable-Name",
+                PropertyKind::Text,
+                "Name of the built artifact.",
+            ),
+            PropertySchema::create(
+                "Emit",
+                one_of(&["binary", "executable", "shared", "dylib", "staticlib"]),
+                "Kind of artifact to build.",
+            ),
+            PropertySchema::create(
+                "Additional-CC-Flags",
+                PropertyKind::List,
+                "Extra compiler flags, comma separated.",
+            ),
+            PropertySchema::create(
+                "Additional-LD-Flags",
+                PropertyKind::List,
+                "Extra linker flags, comma separated.",
+            ),
+            PropertySchema::create(
+                "C-Linker-Script",
+                PropertyKind::Text,
+                "Linker script passed with `-T`.",
+            ),
+        ];
+        schema.extend(schema::toolchain_schema());
+        return schema;
+    }
+    fn get_required_tools(&self, manifest: &ProjectManifest) -> Vec<ToolRequirement> {
+        return Toolchain::from_manifest(manifest, "C").requirements(manifest, "C");
+    }
+    fn write_compile_commands(&self, manifest: &ProjectManifest) -> Result<bool, GreathelmError> {
+        let mut manifest = manifest.clone();
+        manifest
+            .properties
+            .insert("compile-commands-only".into(), "true".into());
+        self.build(&manifest)?;
+        return Ok(true);
+    }
+
+    fn cleanup(&self, manifest: &ProjectManifest) -> Result<(), GreathelmError> {
+        let root = manifest.project_dir();
+        let build_dir = profile::build_dir(manifest).display().to_string();
+        let ibht = ibht::read_ibht(&manifest.project_path(profile::ibht_path(manifest)))?;
         let mut keep = Vec::new();
         for k in ibht.keys() {
             keep.push(format!(
-                "build/{}-{}.o",
+                "{build_dir}/{}-{}.o",
                 k.replace("/", "_"),
                 ibht.get(k).unwrap()
             ));
         }
         keep.push(format!(
-            "build/{}",
+            "{build_dir}/{}",
             manifest.get_string_property("Executable-Name", "unnamedexe")
         ));
         keep.push(format!(
-            "build/lib{}.so",
+            "{build_dir}/lib{}.so",
             manifest.get_string_property("Executable-Name", "unnamedexe")
         ));
         keep.push(format!(
-            "build/lib{}.a",
+            "{build_dir}/lib{}.a",
             manifest.get_string_property("Executable-Name", "unnamedexe")
         ));
 
-        let path = Path::new("build/");
+        // profiles other than the default keep their tables in the build directory
+        keep.push(profile::ibht_path(manifest).display().to_string());
+        keep.push(profile::ibdt_path(manifest).display().to_string());
+
+        let path = manifest.project_path(&build_dir);
 
         for f in match std::fs::read_dir(path) {
             Ok(rd) => rd,
-            Err(_) => {
-                error!("Failed to read build directory.");
-                return;
+            Err(e) => {
+                return Err(GreathelmError::io("Failed to read build directory.", e));
             }
         } {
             if !f.is_ok() {
                 continue;
             }
             let f = f.unwrap();
-            if !keep.contains(&format!("{}", f.path().display())) {
+            // other profiles build into directories in here
+            if f.path().is_dir() {
+                continue;
+            }
+            let file = f.path();
+            let file = file.strip_prefix(&root).unwrap_or(&file);
+            if !keep.contains(&format!("{}", file.display())) {
                 match std::fs::remove_file(f.path()) {
                     Ok(_) => {}
                     Err(_) => {
@@ -452,5 +623,7 @@ impl ProjectBuilder for CBuilder {
                 };
             }
         }
+
+        return Ok(());
     }
 }
@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use crate::{error::GreathelmError, json::quote, term::*};
+
+/**
+ * A single entry in a compilation database (compile_commands.json).
+ */
+pub struct CompileCommand {
+    /**
+     * The working directory the command is run from.
+     */
+    pub directory: String,
+    /**
+     * The source file being compiled.
+     */
+    pub file: String,
+    /**
+     * The full compiler invocation, starting with the compiler itself.
+     */
+    pub arguments: Vec<String>,
+    /**
+     * The object file produced by this command.
+     */
+    pub output: String,
+}
+
+/**
+ * The flags every source of a project is compiled with. Builders use the same arguments for
+ * compiling and for the compilation database so the two never drift apart.
+ */
+pub struct CompileFlags {
+    /**
+     * The value of `-O`.
+     */
+    pub opt: String,
+    /**
+     * Flags from the toolchain and the manifest.
+     */
+    pub cflags: Vec<String>,
+    /**
+     * Include paths and flags of the project's dependencies.
+     */
+    pub dep_args: Vec<String>,
+    pub debug_info: bool,
+}
+
+impl CompileFlags {
+    /**
+     * The arguments (without the compiler) compiling `source` to `output`. With a `depfile` the
+     * compiler also writes the headers `source` included to it.
+     */
+    pub fn arguments(&self, source: &str, output: &str, depfile: Option<&str>) -> Vec<String> {
+        let mut arguments: Vec<String> = vec![
+            "-c".into(), // dont link
+            "-o".into(),
+            output.into(),
+            format!("-O{}", self.opt),
+            "-Wall".into(),
+        ];
+        if let Some(depfile) = depfile {
+            // emit header dependencies
+            arguments.push("-MMD".into());
+            arguments.push("-MF".into());
+            arguments.push(depfile.into());
+        }
+        arguments.extend(self.cflags.iter().cloned());
+        arguments.extend(self.dep_args.iter().cloned());
+        if self.debug_info {
+            arguments.push("-g".into());
+        }
+        arguments.push(source.into());
+        return arguments;
+    }
+}
+
+/**
+ * Writes `commands` as a compilation database to compile_commands.json in the project in `root`.
+ */
+pub fn write_compile_commands(
+    root: &Path,
+    commands: &[CompileCommand],
+) -> Result<(), GreathelmError> {
+    let mut entries: Vec<String> = Vec::new();
+    for c in commands {
+        let arguments: Vec<String> = c.arguments.iter().map(|a| quote(a)).collect();
+        entries.push(format!(
+            "  {{\n    \"directory\": {},\n    \"file\": {},\n    \"arguments\": [{}],\n    \"output\": {}\n  }}",
+            quote(&c.directory),
+            quote(&c.file),
+            arguments.join(", "),
+            quote(&c.output)
+        ));
+    }
+
+    let compdb = format!("[\n{}\n]\n", entries.join(",\n"));
+    match std::fs::write(root.join("compile_commands.json"), compdb) {
+        Ok(_) => {
+            ok!(
+                "Wrote \x1bccompile_commands.json\x1br with \x1bc{}\x1br entries.",
+                commands.len()
+            );
+            Ok(())
+        }
+        Err(e) => Err(GreathelmError::io(
+            "Failed to write \x1bccompile_commands.json\x1br.",
+            e,
+        )),
+    }
+}
@@ -1,6 +1,4 @@
-use std::path::Path;
-
-use crate::{manifest::ProjectManifest, script, term::*};
+use crate::{error::GreathelmError, manifest::ProjectManifest, profile, script};
 
 use super::ProjectBuilder;
 
@@ -32,26 +30,29 @@ impl ProjectBuilder for CustomBuilder {
     fn validate(&self, _manifest: &ProjectManifest) -> bool {
         return true;
     }
-    fn cleanup(&self, _manifest: &ProjectManifest) {
-        script::run_script("cleanup", vec![]);
+    fn cleanup(&self, manifest: &ProjectManifest) -> Result<(), GreathelmError> {
+        script::run_script(&manifest.project_dir(), "cleanup", vec![]);
+        return Ok(());
     }
-    fn build(&self, manifest: &ProjectManifest) {
-        let build_dir = Path::new("build");
-        if !build_dir.exists() {
-            match std::fs::create_dir(build_dir) {
+    fn build(&self, manifest: &ProjectManifest) -> Result<(), GreathelmError> {
+        let root = manifest.project_dir();
+        let build_dir = profile::build_dir(manifest);
+        if !manifest.project_path(&build_dir).exists() {
+            match std::fs::create_dir_all(manifest.project_path(&build_dir)) {
                 Ok(_) => {}
-                Err(_) => {
-                    error!("Failed to create build directory. Abort.");
-                    return;
+                Err(e) => {
+                    return Err(GreathelmError::io("Failed to create build directory.", e));
                 }
             }
         }
 
-        script::run_script("prebuild", vec![]);
+        script::run_script(&root, "prebuild", vec![]);
 
-        let outfile = manifest.get_string_property("Output-Name", "build/compiled".into());
-        script::run_script("build", vec![outfile]);
+        let outfile = manifest
+            .get_string_property("Output-Name", &format!("{}/compiled", build_dir.display()));
+        script::run_script(&root, "build", vec![outfile]);
 
-        script::run_script("postbuild", vec![]);
+        script::run_script(&root, "postbuild", vec![]);
+        return Ok(());
     }
 }
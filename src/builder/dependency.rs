@@ -1,56 +1,77 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
 
 use crate::{
+    error::GreathelmError,
     identify::NamespacedIdentifier,
-    store,
+    lockfile::{self, Lockfile},
+    manifest::ProjectManifest,
+    profile, store,
+    target::{self, Toolchain},
     term::*,
-    version::{self, Version},
+    version::{self, Version, VersionRange},
 };
 
 /**
- * Parse a dependency-notation identifier (something like `<identifier>@<version>`, `<identifier>`)
- * into a tuple of the identifier and (if present) version.
+ * Parse a dependency-notation identifier (something like `<identifier>@<version>`,
+ * `<identifier>@<range>`, `<identifier>`) into a tuple of the identifier and (if present) version
+ * range. An exact version is parsed as a range only matching that version.
  */
-pub fn parse_dependency_notation(notation: String) -> (NamespacedIdentifier, Option<Version>) {
-    if notation.contains("@") {
-        let spl = notation.split_once("@").unwrap();
-        return (
-            match NamespacedIdentifier::parse_text(&spl.0.into()) {
-                Some(v) => v,
-                None => {
-                    error!("Failed to parse identifier.");
-                    std::process::exit(1);
-                }
-            },
-            Some(Version::parse(spl.1.into())),
-        );
-    } else {
-        return (
-            match NamespacedIdentifier::parse_text(&notation) {
-                Some(v) => v,
-                None => {
-                    error!("Failed to parse identifier.");
-                    std::process::exit(1);
-                }
-            },
-            None,
-        );
-    }
+pub fn parse_dependency_notation(
+    notation: String,
+) -> Result<(NamespacedIdentifier, Option<VersionRange>), GreathelmError> {
+    let (identifier, range) = match notation.split_once("@") {
+        Some((i, r)) => (i.to_string(), Some(r.to_string())),
+        None => (notation.clone(), None),
+    };
+    let identifier = match NamespacedIdentifier::parse_text(&identifier) {
+        Some(v) => v,
+        None => {
+            return Err(GreathelmError::DependencyError(format!(
+                "Failed to parse identifier \x1bc{identifier}\x1br."
+            )));
+        }
+    };
+    let range = match range {
+        Some(r) => match VersionRange::parse(r.clone()) {
+            Some(v) => Some(v),
+            None => {
+                return Err(GreathelmError::DependencyError(format!(
+                    "Failed to parse version range \x1bc{r}\x1br."
+                )));
+            }
+        },
+        None => None,
+    };
+    return Ok((identifier, range));
 }
 
 /**
- * Returns a Vec of all versions of the specified package found in the
- * local store.
+ * Returns a Vec of all versions of the specified package found in the store at `store` (usually
+ * `store::get_store_path()`).
  */
-pub fn get_all_versions(identifier: &NamespacedIdentifier) -> Vec<Version> {
-    let path = store::get_path(&identifier);
+pub fn get_all_versions(
+    store: &Path,
+    identifier: &NamespacedIdentifier,
+) -> Result<Vec<Version>, GreathelmError> {
+    let path = store::path_in(store, identifier);
     if !path.exists() {
-        return vec![];
+        return Ok(vec![]);
     }
 
+    let read_error = |e| {
+        GreathelmError::io(
+            format!("Failed to read the store entry of \x1bc{identifier}\x1br."),
+            e,
+        )
+    };
     let mut versions = Vec::new();
-    for ent in path.read_dir().unwrap() {
-        let ent = ent.unwrap();
+    for ent in path.read_dir().map_err(read_error)? {
+        let ent = ent.map_err(read_error)?;
         let vtext = format!("{}", ent.file_name().to_string_lossy());
         if !vtext.contains("@") {
             continue;
@@ -60,62 +81,733 @@ pub fn get_all_versions(identifier: &NamespacedIdentifier) -> Vec<Version> {
         versions.push(version);
     }
 
-    return versions;
+    return Ok(versions);
 }
 
 /**
  * Attempts to resolve the Path (as a PathBuf) to the directory containing the
- * specified package (and version). If no version is passed, the latest version in the
- * store is used.
- * If there are no versions or the package is not found, this returns None.
+ * specified package (and version) in the store at `store`. If a range is passed, the newest
+ * version in the store satisfying it is used. If no range is passed, the latest version in the store is used.
+ * If there are no matching versions or the package is not found, this returns an error.
  */
 pub fn resolve_dependency(
+    store: &Path,
     identifier: NamespacedIdentifier,
-    version: Option<Version>,
-) -> Option<PathBuf> {
-    let path = store::get_path(&identifier);
-    let path = PathBuf::from_str(
-        format!(
-            "{}{}",
-            path.display(),
-            match &version {
-                Some(v) => {
-                    format!("/@{v}")
+    range: Option<VersionRange>,
+) -> Result<PathBuf, GreathelmError> {
+    let path = store::path_in(store, &identifier);
+    if !path.exists() {
+        return Err(GreathelmError::DependencyError(format!(
+            "Item \x1bc{identifier}\x1br could not be resolved."
+        )));
+    }
+
+    // exact versions don't need to look at the rest of the store
+    if let Some(v) = range.as_ref().and_then(|r| r.as_exact()) {
+        let path = PathBuf::from_str(format!("{}/@{v}", path.display()).as_str()).unwrap();
+        if path.exists() {
+            return Ok(path);
+        }
+    }
+
+    let mut versions = get_all_versions(store, &identifier)?;
+    if versions.is_empty() {
+        return Err(GreathelmError::DependencyError(format!(
+            "Item \x1bc{identifier}\x1br was resolved, but there are no present versions!"
+        )));
+    }
+    versions.sort();
+    versions.reverse();
+
+    let range = range.unwrap_or(VersionRange {
+        requirements: vec![],
+    });
+    let v = match range.select(&versions) {
+        Some(v) => v,
+        None => {
+            let available: Vec<String> = versions.iter().map(|v| format!("{v}")).collect();
+            return Err(GreathelmError::DependencyError(format!(
+                "No version of \x1bc{identifier}\x1br in the store satisfies \x1bc{range}\x1br.\nAvailable versions: \x1bc{}\x1br",
+                available.join(", ")
+            )));
+        }
+    };
+
+    let path = PathBuf::from_str(format!("{}/@{v}", path.display()).as_str()).unwrap();
+    if path.exists() {
+        return Ok(path);
+    } else {
+        return Err(GreathelmError::DependencyError(format!(
+            "Item \x1bc{identifier}\x1br was resolved, but the version folder is not present?"
+        )));
+    }
+}
+
+/**
+ * Resolves a dependency in the store at `store` like `resolve_dependency` but honors `lock`. If
+ * `lock` contains an entry for `identifier` satisfying `range`, exactly that version is used and
+ * its content hash is verified. Otherwise the dependency is resolved normally and recorded in
+ * `lock`.
+ * This does not look at the dependencies of the package. Use DependencyGraph for that.
+ */
+pub fn resolve_locked(
+    store: &Path,
+    identifier: NamespacedIdentifier,
+    range: Option<VersionRange>,
+    lock: &mut Lockfile,
+) -> Result<PathBuf, GreathelmError> {
+    let key = format!("{identifier}");
+    match lock.entries.get(&key).cloned() {
+        Some(entry) if range.as_ref().is_none_or(|r| r.matches(&entry.version)) => {
+            let path = store::path_in(store, &identifier);
+            let path = PathBuf::from_str(format!("{}/@{}", path.display(), entry.version).as_str())
+                .unwrap();
+            if !path.exists() {
+                return Err(GreathelmError::DependencyError(format!(
+                    "Locked version \x1bc{identifier}@{}\x1br is not present in the store.\nImport it or run \x1bcgreathelm update\x1br to pick another version.",
+                    entry.version
+                )));
+            }
+            if lockfile::hash_package(&path) != entry.hash {
+                return Err(GreathelmError::DependencyError(format!(
+                    "Contents of \x1bc{identifier}@{}\x1br do not match the lockfile.\nIf this change is intended, run \x1bcgreathelm update {identifier}\x1br.",
+                    entry.version
+                )));
+            }
+            return Ok(path);
+        }
+        entry => {
+            if let Some(entry) = entry {
+                warning!(
+                    "Locked version \x1bc{identifier}@{}\x1br does not satisfy \x1bc{}\x1br. Re-resolving.",
+                    entry.version,
+                    range.as_ref().unwrap()
+                );
+            }
+            let path = resolve_dependency(store, identifier, range)?;
+            if let Some(version) = lockfile::version_of_path(&path) {
+                lock.lock(key, version, lockfile::hash_package(&path));
+            }
+            return Ok(path);
+        }
+    }
+}
+
+/**
+ * Checks if a @Dependency line refers to a package in the local store. Dependencies with a
+ * `<kind>/` prefix (`raw/`, `sys/`, `provided/`, ...) are handled by builders themselves.
+ */
+pub fn is_store_dependency(dep: &str) -> bool {
+    !dep.contains("/")
+}
+
+/**
+ * Gets the flags `pkgconf` reports for the system package `name`, looking the package up for the
+ * target of `toolchain`.
+ */
+pub fn system_dependency_flags(
+    name: &str,
+    toolchain: &Toolchain,
+) -> Result<Vec<String>, GreathelmError> {
+    let pkgconf = match Command::new("pkgconf")
+        .envs(toolchain.pkg_config_env())
+        .arg("--libs")
+        .arg("--cflags")
+        .arg(name)
+        .output()
+    {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(GreathelmError::io(
+                format!("Failed to run \x1bcpkgconf\x1br for \x1bc{name}\x1br."),
+                e,
+            ));
+        }
+    };
+    if !pkgconf.status.success() {
+        warning!(
+            "\x1bcpkgconf\x1br could not find the system package \x1bc{name}\x1br, \
+             building without its flags: {}",
+            String::from_utf8_lossy(&pkgconf.stderr).trim()
+        );
+        return Ok(vec![]);
+    }
+    return Ok(String::from_utf8_lossy(&pkgconf.stdout)
+        .split_whitespace()
+        .map(|f| f.to_string())
+        .collect());
+}
+
+/**
+ * A single store package within a DependencyGraph.
+ */
+pub struct DependencyNode {
+    pub identifier: NamespacedIdentifier,
+    pub version: Version,
+    /**
+     * Path of the package in the store.
+     */
+    pub path: PathBuf,
+    /**
+     * The package's own Project.ghm.
+     */
+    pub manifest: ProjectManifest,
+    /**
+     * Keys (text form of the NamespacedIdentifier) of the store packages this one depends on.
+     */
+    pub dependencies: Vec<String>,
+}
+
+/**
+ * The full closure of store dependencies of a project. Every package appears exactly once, at a
+ * single version satisfying everything that depends on it.
+ */
+pub struct DependencyGraph {
+    /**
+     * Keys of the store dependencies the project itself declares.
+     */
+    pub roots: Vec<String>,
+    /**
+     * All packages in the closure keyed by the text form of their NamespacedIdentifier.
+     */
+    pub nodes: HashMap<String, DependencyNode>,
+    /**
+     * Keys of all packages ordered so that every package comes after its dependencies.
+     */
+    pub order: Vec<String>,
+}
+
+impl DependencyGraph {
+    /**
+     * Resolves every store dependency of `manifest` and everything they depend on (honoring and
+     * updating `lock`). Each package is used at the newest version satisfying every range it is
+     * required with. Fails if a package cannot be resolved, no version of a package satisfies all
+     * of its requirements, or the dependencies form a cycle.
+     */
+    pub fn resolve(
+        manifest: &ProjectManifest,
+        lock: &mut Lockfile,
+    ) -> Result<DependencyGraph, GreathelmError> {
+        return Self::resolve_in(&store::get_store_path(), manifest, lock);
+    }
+
+    /**
+     * Same as resolve but looks up packages in the store at `store`.
+     */
+    pub fn resolve_in(
+        store: &Path,
+        manifest: &ProjectManifest,
+        lock: &mut Lockfile,
+    ) -> Result<DependencyGraph, GreathelmError> {
+        // extra requirements for packages picked too eagerly by an earlier attempt
+        let mut constraints: HashMap<String, VersionRange> = HashMap::new();
+        let mut graph = loop {
+            let mut attempt = lock.clone();
+            match Self::resolve_attempt(store, manifest, &mut attempt, &constraints)? {
+                Ok(graph) => {
+                    *lock = attempt;
+                    break graph;
                 }
-                None => {
-                    "".into()
+                Err((key, combined)) => {
+                    constraints.insert(key, combined);
                 }
             }
-        )
-        .as_str(),
-    )
-    .unwrap();
-
-    if path.exists() && version.is_some() {
-        return Some(path);
-    } else if path.exists() {
-        let mut versions = get_all_versions(&identifier);
-
-        if versions.is_empty() {
-            error!("Item \x1bc{identifier}\x1br was resolved, but there are no present versions!");
-            return None;
-        } else {
-            versions.sort();
-            versions.reverse();
-
-            let v = versions.get(0).unwrap();
-            let path = PathBuf::from_str(format!("{}/@{v}", path.display()).as_str()).unwrap();
-            if path.exists() {
-                return Some(path);
-            } else {
-                error!(
-                    "Item \x1bc{identifier}\x1br was resolved, but the version folder is not present?"
-                );
-                return None;
+        };
+
+        // order the graph, failing on cycles
+        let mut keys: Vec<String> = graph.nodes.keys().cloned().collect();
+        keys.sort();
+        let mut done: HashSet<String> = HashSet::new();
+        let mut stack: Vec<String> = Vec::new();
+        for k in keys {
+            graph.visit(&k, &mut done, &mut stack)?;
+        }
+
+        return Ok(graph);
+    }
+
+    /**
+     * A single pass of resolve_in. Every package is resolved the first time it is required,
+     * narrowed by its entry in `constraints`. If a later requirement rejects the version picked
+     * for a package but a version satisfying all of its requirements exists, this returns the
+     * key of that package and the combined range so the resolution can be retried with it.
+     */
+    fn resolve_attempt(
+        store: &Path,
+        manifest: &ProjectManifest,
+        lock: &mut Lockfile,
+        constraints: &HashMap<String, VersionRange>,
+    ) -> Result<Result<DependencyGraph, (String, VersionRange)>, GreathelmError> {
+        let mut graph = DependencyGraph {
+            roots: vec![],
+            nodes: HashMap::new(),
+            order: vec![],
+        };
+        // (who requires it, the required notation)
+        let mut queue: VecDeque<(String, String)> = VecDeque::new();
+        // every requirement seen for each package, for conflict reporting
+        let mut required_by: HashMap<String, Vec<(String, Option<VersionRange>)>> = HashMap::new();
+
+        let project = manifest.get_string_property("Project-Name", "this project");
+        for dep in manifest.directives.get("Dependency").unwrap() {
+            if is_store_dependency(dep) {
+                queue.push_back((project.clone(), dep.clone()));
             }
         }
-    } else {
-        error!("Item \x1bc{identifier}\x1br could not be resolved.");
-        return None;
+
+        while let Some((requirer, notation)) = queue.pop_front() {
+            let (id, range) = parse_dependency_notation(notation)?;
+            let key = format!("{id}");
+            if requirer == project && !graph.roots.contains(&key) {
+                graph.roots.push(key.clone());
+            }
+            required_by
+                .entry(key.clone())
+                .or_default()
+                .push((requirer.clone(), range.clone()));
+
+            if let Some(node) = graph.nodes.get(&key) {
+                if let Some(ref r) = range {
+                    if !r.matches(&node.version) {
+                        let requirements = required_by.get(&key).unwrap();
+                        let mut combined = VersionRange {
+                            requirements: vec![],
+                        };
+                        for r in requirements.iter().filter_map(|(_, r)| r.as_ref()) {
+                            combined.requirements.extend(r.requirements.iter().copied());
+                        }
+                        if let Some(c) = constraints.get(&key) {
+                            combined.requirements.extend(c.requirements.iter().copied());
+                        }
+                        if resolve_dependency(store, id.clone(), Some(combined.clone())).is_ok() {
+                            return Ok(Err((key, combined)));
+                        }
+
+                        let mut message = format!("Version conflict for \x1bc{key}\x1br:\n");
+                        for (who, r) in requirements {
+                            match r {
+                                Some(r) => message.push_str(&format!(
+                                    "  \x1bc{who}\x1br requires \x1bc{r}\x1br\n"
+                                )),
+                                None => message
+                                    .push_str(&format!("  \x1bc{who}\x1br requires any version\n")),
+                            }
+                        }
+                        message.push_str("No version in the store satisfies all of them.");
+                        return Err(GreathelmError::DependencyError(message));
+                    }
+                }
+                if let Some(n) = graph.nodes.get_mut(&requirer) {
+                    if !n.dependencies.contains(&key) {
+                        n.dependencies.push(key);
+                    }
+                }
+                continue;
+            }
+
+            let range = match (range, constraints.get(&key)) {
+                (Some(mut r), Some(c)) => {
+                    r.requirements.extend(c.requirements.iter().copied());
+                    Some(r)
+                }
+                (r, c) => r.or(c.cloned()),
+            };
+            let path = match resolve_locked(store, id.clone(), range, lock) {
+                Ok(p) => p,
+                Err(e) => {
+                    return Err(GreathelmError::DependencyError(format!(
+                        "{}\nFailed to resolve \x1bc{key}\x1br (required by \x1bc{requirer}\x1br).",
+                        e.message()
+                    )));
+                }
+            };
+            let version = match lockfile::version_of_path(&path) {
+                Some(v) => v,
+                None => Version::new(0, 0, 0),
+            };
+            let mut dep_manifest = ProjectManifest::new();
+            let manifest_path =
+                PathBuf::from_str(format!("{}/Project.ghm", path.display()).as_str()).unwrap();
+            if manifest_path.exists() {
+                dep_manifest.read_and_append(&manifest_path)?;
+            }
+            for dep in dep_manifest.directives.get("Dependency").unwrap() {
+                if is_store_dependency(dep) {
+                    queue.push_back((key.clone(), dep.clone()));
+                }
+            }
+            if let Some(n) = graph.nodes.get_mut(&requirer) {
+                n.dependencies.push(key.clone());
+            }
+            graph.nodes.insert(
+                key,
+                DependencyNode {
+                    identifier: id,
+                    version,
+                    path,
+                    manifest: dep_manifest,
+                    dependencies: vec![],
+                },
+            );
+        }
+
+        return Ok(Ok(graph));
+    }
+
+    /**
+     * Keys of the packages in `keys` and of everything they depend on, directly or not.
+     */
+    pub fn closure(&self, keys: &[String]) -> HashSet<String> {
+        let mut found: HashSet<String> = HashSet::new();
+        let mut queue: Vec<String> = keys.to_vec();
+        while let Some(key) = queue.pop() {
+            if !found.insert(key.clone()) {
+                continue;
+            }
+            if let Some(node) = self.nodes.get(&key) {
+                queue.extend(node.dependencies.iter().cloned());
+            }
+        }
+        return found;
+    }
+
+    /**
+     * Drops the entries of `lock` for packages that are no longer part of this graph, i.e.
+     * dependencies removed from the manifest. Only valid for the project's own lockfile.
+     */
+    pub fn prune_lock(&self, lock: &mut Lockfile) {
+        lock.retain(|id| self.nodes.contains_key(id));
+    }
+
+    /**
+     * Depth-first visit used to order the graph. `stack` holds the path currently being visited
+     * so a cycle can be reported.
+     */
+    fn visit(
+        &mut self,
+        key: &String,
+        done: &mut HashSet<String>,
+        stack: &mut Vec<String>,
+    ) -> Result<(), GreathelmError> {
+        if done.contains(key) {
+            return Ok(());
+        }
+        if let Some(pos) = stack.iter().position(|k| k == key) {
+            let mut cycle: Vec<String> = stack[pos..].to_vec();
+            cycle.push(key.clone());
+            return Err(GreathelmError::DependencyError(format!(
+                "Dependency cycle detected: \x1bc{}\x1br",
+                cycle.join(" -> ")
+            )));
+        }
+
+        stack.push(key.clone());
+        let mut deps = self.nodes.get(key).unwrap().dependencies.clone();
+        deps.sort();
+        for d in deps {
+            self.visit(&d, done, stack)?;
+        }
+        stack.pop();
+
+        done.insert(key.clone());
+        self.order.push(key.clone());
+        return Ok(());
+    }
+
+    /**
+     * Gets the flags needed to compile (`-I`) and link (`-L`, `-l`) against every package in the
+     * graph for C-family builders. Packages are linked before the packages they depend on.
+     * The `sys/` and `provided/` dependencies of packages are included as well, `sys/` ones are
+     * looked up for the target of `toolchain`. Packages are linked from the build directory of
+     * the profile and target of `manifest`, which they are built with.
+     */
+    pub fn c_flags(
+        &self,
+        manifest: &ProjectManifest,
+        toolchain: &Toolchain,
+    ) -> Result<(Vec<String>, Vec<String>), GreathelmError> {
+        let mut cc_args: Vec<String> = Vec::new();
+        let mut link_args: Vec<String> = Vec::new();
+        let build_dir = profile::build_dir_for(
+            &profile::active_profile(manifest),
+            &target::active_target(manifest),
+        );
+
+        for key in self.order.iter().rev() {
+            let node = self.nodes.get(key).unwrap();
+            let path = node.path.display();
+            cc_args.push(format!("-I{path}/export"));
+            link_args.push(format!("-L{path}/{build_dir}"));
+            link_args.push(format!("-I{path}/export"));
+            link_args.push(format!(
+                "-l{}",
+                node.manifest
+                    .get_string_property("Executable-Name", "LIBRESOLVEERROR")
+            ));
+
+            for dep in node.manifest.directives.get("Dependency").unwrap() {
+                if let Some(sys) = dep.strip_prefix("sys/") {
+                    for flag in system_dependency_flags(sys, toolchain)? {
+                        if flag.starts_with("-I") {
+                            cc_args.push(flag.clone());
+                        }
+                        link_args.push(flag);
+                    }
+                } else if let Some(provided) = dep.strip_prefix("provided/") {
+                    link_args.push(format!("-L{path}/lib/shared"));
+                    link_args.push(format!("-l{provided}"));
+                }
+            }
+        }
+
+        return Ok((cc_args, link_args));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+     * A graph of the packages `nodes` (key and the keys it depends on), ordered like `resolve`
+     * orders it.
+     */
+    fn graph(nodes: &[(&str, &[&str])]) -> Result<DependencyGraph, GreathelmError> {
+        let mut graph = DependencyGraph {
+            roots: vec![],
+            nodes: HashMap::new(),
+            order: vec![],
+        };
+        for (key, dependencies) in nodes {
+            graph.nodes.insert(
+                key.to_string(),
+                DependencyNode {
+                    identifier: NamespacedIdentifier::parse_text(&key.to_string()).unwrap(),
+                    version: Version::new(1, 0, 0),
+                    path: PathBuf::new(),
+                    manifest: ProjectManifest::new(),
+                    dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+                },
+            );
+        }
+        let mut keys: Vec<String> = graph.nodes.keys().cloned().collect();
+        keys.sort();
+        let (mut done, mut stack) = (HashSet::new(), Vec::new());
+        for k in keys {
+            graph.visit(&k, &mut done, &mut stack)?;
+        }
+        return Ok(graph);
+    }
+
+    /**
+     * An empty store for the test `name`.
+     */
+    fn scratch_store(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("greathelm-store-{}-{name}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        return dir;
+    }
+
+    /**
+     * Adds version `version` of the package `key` to the store at `store`, depending on `deps`.
+     */
+    fn package(store: &Path, key: &str, version: &str, deps: &[&str]) -> PathBuf {
+        let id = NamespacedIdentifier::parse_text(&key.to_string()).unwrap();
+        let path = store::path_in(store, &id).join(format!("@{version}"));
+        std::fs::create_dir_all(&path).unwrap();
+        let mut manifest = format!(
+            "Project-Name={}\nProject-Version={version}\n",
+            id.identifier
+        );
+        for d in deps {
+            manifest.push_str(&format!("@Dependency {d}\n"));
+        }
+        std::fs::write(path.join("Project.ghm"), manifest).unwrap();
+        return path;
+    }
+
+    fn id(key: &str) -> NamespacedIdentifier {
+        return NamespacedIdentifier::parse_text(&key.to_string()).unwrap();
+    }
+
+    fn range(text: &str) -> Option<VersionRange> {
+        return VersionRange::parse(text.into());
+    }
+
+    fn project(deps: &[&str]) -> ProjectManifest {
+        let mut manifest = ProjectManifest::new();
+        manifest.append_from_str("Project-Name=app").unwrap();
+        for d in deps {
+            manifest
+                .append_from_str(&format!("@Dependency {d}"))
+                .unwrap();
+        }
+        return manifest;
+    }
+
+    #[test]
+    fn honors_the_lockfile() {
+        let store = scratch_store("locked");
+        package(&store, "t:B", "1.0.0", &[]);
+        let locked = package(&store, "t:B", "1.2.0", &[]);
+        let mut lock = Lockfile::new(store.join("Project.lock.ghm"));
+
+        let path = resolve_locked(&store, id("t:B"), range("^1"), &mut lock).unwrap();
+        assert_eq!(path, locked);
+        assert_eq!(lock.entries["t:B"].version, Version::new(1, 2, 0));
+        assert_eq!(lock.entries["t:B"].hash, lockfile::hash_package(&locked));
+
+        // a newer version doesn't replace the locked one
+        package(&store, "t:B", "1.3.0", &[]);
+        let path = resolve_locked(&store, id("t:B"), range("^1"), &mut lock).unwrap();
+        assert_eq!(path, locked);
+        // unless the range no longer allows it
+        let path = resolve_locked(&store, id("t:B"), range("<1.2"), &mut lock).unwrap();
+        assert_eq!(
+            lockfile::version_of_path(&path),
+            Some(Version::new(1, 0, 0))
+        );
+        assert_eq!(lock.entries["t:B"].version, Version::new(1, 0, 0));
+
+        // locked packages have to stay the same
+        let mut lock = Lockfile::new(store.join("Project.lock.ghm"));
+        resolve_locked(&store, id("t:B"), range("1.2.0"), &mut lock).unwrap();
+        std::fs::write(locked.join("extra.c"), "int x;").unwrap();
+        let e = resolve_locked(&store, id("t:B"), None, &mut lock).unwrap_err();
+        assert!(e.message().contains("do not match the lockfile"));
+        std::fs::remove_dir_all(locked).unwrap();
+        let e = resolve_locked(&store, id("t:B"), None, &mut lock).unwrap_err();
+        assert!(e.message().contains("not present in the store"));
+
+        std::fs::remove_dir_all(&store).ok();
+    }
+
+    #[test]
+    fn parses_dependency_notation() {
+        let (id, range) = parse_dependency_notation("com.example:Lib@^1.2".into()).unwrap();
+        assert_eq!(id.to_string(), "com.example:Lib");
+        assert_eq!(range.unwrap().to_string(), ">=1.2.0,<2.0.0");
+        let (id, range) = parse_dependency_notation("com.example:Lib@1.0.3".into()).unwrap();
+        assert_eq!(id.identifier, "Lib");
+        assert_eq!(range.unwrap().as_exact(), Some(Version::new(1, 0, 3)));
+        let (_, range) = parse_dependency_notation("com.example:Lib".into()).unwrap();
+        assert!(range.is_none());
+        assert!(parse_dependency_notation("com.example:Lib@".into()).is_err());
+        assert!(parse_dependency_notation("com.example:Lib@>=1,,<2".into()).is_err());
+    }
+    #[test]
+    fn orders_dependencies_first() {
+        let g = graph(&[
+            ("t:App", &["t:Net", "t:Log"]),
+            ("t:Net", &["t:Log", "t:Tls"]),
+            ("t:Tls", &[]),
+            ("t:Log", &[]),
+        ])
+        .unwrap();
+        assert_eq!(g.order, ["t:Log", "t:Tls", "t:Net", "t:App"]);
+    }
+
+    #[test]
+    fn reports_cycles() {
+        let e = graph(&[("t:A", &["t:B"]), ("t:B", &["t:C"]), ("t:C", &["t:A"])])
+            .err()
+            .unwrap();
+        assert!(e.message().contains("t:A -> t:B -> t:C -> t:A"));
+    }
+
+    #[test]
+    fn finds_the_closure() {
+        let g = graph(&[
+            ("t:A", &["t:B"]),
+            ("t:B", &["t:C"]),
+            ("t:C", &[]),
+            ("t:D", &[]),
+        ])
+        .unwrap();
+        let mut closure: Vec<String> = g.closure(&["t:B".into()]).into_iter().collect();
+        closure.sort();
+        assert_eq!(closure, ["t:B", "t:C"]);
+    }
+
+    #[test]
+    fn resolves_against_the_store() {
+        let store = scratch_store("graph");
+        package(&store, "t:A", "1.0.0", &["t:B@^1.1", "t:C", "sys/m"]);
+        package(&store, "t:B", "1.0.0", &[]);
+        package(&store, "t:B", "1.2.0", &[]);
+        package(&store, "t:B", "2.0.0", &[]);
+        package(&store, "t:C", "1.0.0", &[]);
+        package(&store, "t:X", "1.0.0", &["t:Y"]);
+        package(&store, "t:Y", "1.0.0", &["t:X"]);
+        let mut lock = Lockfile::new(store.join("Project.lock.ghm"));
+
+        // every package once at the newest version satisfying everything
+        let g =
+            DependencyGraph::resolve_in(&store, &project(&["t:A", "t:B@^1", "raw/x"]), &mut lock)
+                .unwrap();
+        assert_eq!(g.roots, ["t:A", "t:B"]);
+        assert_eq!(g.nodes["t:B"].version, Version::new(1, 2, 0));
+        assert_eq!(g.nodes["t:A"].dependencies, ["t:B", "t:C"]);
+        assert_eq!(g.order.last().unwrap(), "t:A");
+        assert_eq!(g.order.len(), 3);
+        assert_eq!(lock.entries.len(), 3);
+
+        // the lockfile keeps the version even once a newer one is imported
+        package(&store, "t:B", "1.3.0", &[]);
+        let g = DependencyGraph::resolve_in(&store, &project(&["t:A"]), &mut lock).unwrap();
+        assert_eq!(g.nodes["t:B"].version, Version::new(1, 2, 0));
+        let mut fresh = Lockfile::new(store.join("Project.lock.ghm"));
+        let g = DependencyGraph::resolve_in(&store, &project(&["t:A"]), &mut fresh).unwrap();
+        assert_eq!(g.nodes["t:B"].version, Version::new(1, 3, 0));
+
+        // packages no longer in the graph leave the lockfile
+        let g = DependencyGraph::resolve_in(&store, &project(&["t:C"]), &mut lock).unwrap();
+        g.prune_lock(&mut lock);
+        assert_eq!(lock.entries.keys().collect::<Vec<_>>(), ["t:C"]);
+
+        let e = DependencyGraph::resolve_in(&store, &project(&["t:B@^2", "t:A"]), &mut fresh)
+            .err()
+            .unwrap();
+        assert!(e.message().contains("Version conflict for \x1bct:B\x1br"));
+        let e = DependencyGraph::resolve_in(&store, &project(&["t:X"]), &mut fresh)
+            .err()
+            .unwrap();
+        assert!(e.message().contains("cycle"));
+        let e = DependencyGraph::resolve_in(&store, &project(&["t:Missing"]), &mut fresh)
+            .err()
+            .unwrap();
+        assert!(e.message().contains("t:Missing"));
+
+        std::fs::remove_dir_all(&store).ok();
+    }
+
+    #[test]
+    fn narrows_eagerly_picked_versions() {
+        let store = scratch_store("narrow");
+        package(&store, "t:A", "1.0.0", &["t:C@<1.2"]);
+        package(&store, "t:C", "1.0.0", &[]);
+        package(&store, "t:C", "1.1.0", &[]);
+        package(&store, "t:C", "1.3.0", &[]);
+        let mut lock = Lockfile::new(store.join("Project.lock.ghm"));
+
+        // t:C@^1.1 alone would pick 1.3.0, which t:A rejects
+        let g =
+            DependencyGraph::resolve_in(&store, &project(&["t:C@^1.1", "t:A"]), &mut lock).unwrap();
+        assert_eq!(g.nodes["t:C"].version, Version::new(1, 1, 0));
+        assert_eq!(lock.entries["t:C"].version, Version::new(1, 1, 0));
+
+        let e = DependencyGraph::resolve_in(&store, &project(&["t:C@^1.2", "t:A"]), &mut lock)
+            .err()
+            .unwrap();
+        assert!(e
+            .message()
+            .contains("No version in the store satisfies all of them."));
+        assert!(e
+            .message()
+            .contains("\x1bct:A\x1br requires \x1bc<1.2.0\x1br"));
+
+        std::fs::remove_dir_all(&store).ok();
     }
 }
@@ -0,0 +1,449 @@
+use std::{
+    cell::Cell,
+    fs::{File, OpenOptions},
+    io::{ErrorKind, Read, Write},
+    path::PathBuf,
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+};
+
+use crate::{error::GreathelmError, term::*};
+
+/**
+ * A GNU make jobserver limiting how many jobs run at once across every process taking part in a
+ * build, so nested Greathelm builds, make and compilers that understand `MAKEFLAGS` share one
+ * pool of CPUs. Subprocesses find the jobserver this process serves through `makeflags`.
+ *
+ * Every process may always run one job (its implicit token), each job beyond that needs a token
+ * read from the jobserver, which is written back once the job is done.
+ */
+pub struct Jobserver {
+    read: File,
+    write: File,
+    /**
+     * Whether the implicit token of this process is free.
+     */
+    implicit: AtomicBool,
+    /**
+     * The fifo this process created and serves, None for jobservers inherited from `MAKEFLAGS`.
+     */
+    fifo: Option<PathBuf>,
+    /**
+     * `MAKEFLAGS` for subprocesses pointing at the fifo, None for inherited jobservers which are
+     * already in the environment.
+     */
+    makeflags: Option<String>,
+}
+
+/**
+ * A token taken from the jobserver, given back when dropped. Tokens are dropped on the thread
+ * that took them.
+ */
+pub struct Token<'a> {
+    jobserver: &'a Jobserver,
+    /**
+     * The byte read from the jobserver, None for the implicit token.
+     */
+    byte: Option<u8>,
+}
+
+static JOBSERVER: OnceLock<Option<Jobserver>> = OnceLock::new();
+
+thread_local! {
+    /**
+     * How many tokens the current thread holds, subprocesses it spawns use one of those.
+     */
+    static HOLDS_TOKEN: Cell<usize> = const { Cell::new(0) };
+}
+
+/**
+ * Sets up the jobserver of this process if it isn't already. The jobserver from `MAKEFLAGS` is
+ * used if Greathelm runs under make or another Greathelm build, otherwise this process serves one
+ * with `jobs` tokens. The environment of this process is left alone as other threads may be
+ * reading it, subprocesses get the jobserver from `makeflags`.
+ */
+pub fn init(jobs: usize) -> Option<&'static Jobserver> {
+    return JOBSERVER
+        .get_or_init(|| {
+            let makeflags = std::env::var("MAKEFLAGS").unwrap_or_default();
+            if let Some(auth) = find_auth(&makeflags) {
+                match Jobserver::connect(&auth) {
+                    Some(j) => return Some(j),
+                    None => warning!(
+                        "Could not use the jobserver from \x1bcMAKEFLAGS\x1br, if make runs \
+                         Greathelm mark the rule with \x1bc+\x1br. Serving a new one."
+                    ),
+                }
+            }
+            return Jobserver::serve(jobs.max(1), &makeflags);
+        })
+        .as_ref();
+}
+
+/**
+ * The jobserver of this process, None if `init` wasn't called or no jobserver could be used.
+ */
+pub fn get() -> Option<&'static Jobserver> {
+    return JOBSERVER.get().and_then(|j| j.as_ref());
+}
+
+/**
+ * Takes a token for a subprocess about to be spawned by the current thread. Subprocesses run on
+ * the token of their parent, so this is None if the thread already holds one (it's running a
+ * ParallelBuild job) or there is no jobserver.
+ */
+pub fn token_for_child() -> Result<Option<Token<'static>>, GreathelmError> {
+    if HOLDS_TOKEN.with(|h| h.get()) > 0 {
+        return Ok(None);
+    }
+    return match get() {
+        Some(j) => Ok(Some(j.acquire()?)),
+        None => Ok(None),
+    };
+}
+
+/**
+ * `MAKEFLAGS` for a subprocess so it shares the jobserver this process serves. None if there is
+ * no such jobserver, subprocesses then inherit `MAKEFLAGS` from us unchanged.
+ */
+pub fn makeflags() -> Option<&'static str> {
+    return get().and_then(|j| j.makeflags.as_deref());
+}
+
+/**
+ * Lets `command` share the jobserver this process serves, see `makeflags`.
+ */
+pub fn export(command: &mut Command) {
+    if let Some(flags) = makeflags() {
+        command.env("MAKEFLAGS", flags);
+    }
+}
+
+/**
+ * Removes the fifo of the jobserver this process serves. Registered with `atexit` when the fifo
+ * is created so it also runs when Greathelm (or a plugin) exits in the middle of a build.
+ */
+#[cfg(unix)]
+extern "C" fn cleanup() {
+    if let Some(fifo) = get().and_then(|j| j.fifo.as_ref()) {
+        std::fs::remove_file(fifo).ok();
+    }
+}
+
+impl Jobserver {
+    /**
+     * Takes a token, waiting until one is free. The implicit token is used first.
+     */
+    pub fn acquire(&self) -> Result<Token<'_>, GreathelmError> {
+        if self.implicit.swap(false, Ordering::SeqCst) {
+            return Ok(self.token(None));
+        }
+        let mut byte = [0u8; 1];
+        loop {
+            match (&self.read).read(&mut byte) {
+                Ok(1) => return Ok(self.token(Some(byte[0]))),
+                Ok(_) => {
+                    return Err(GreathelmError::BuildError(
+                        "The jobserver was closed.".into(),
+                    ));
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                // the pipe make gave us may be non-blocking
+                Err(e) if e.kind() == ErrorKind::WouldBlock => self.wait_readable(),
+                Err(e) => {
+                    return Err(GreathelmError::io("Failed to read from the jobserver.", e));
+                }
+            }
+        }
+    }
+
+    /**
+     * Blocks until a token can be read, another thread may take it first though.
+     */
+    #[cfg(unix)]
+    fn wait_readable(&self) {
+        use std::os::unix::io::AsRawFd;
+
+        let mut fd = PollFd {
+            fd: self.read.as_raw_fd(),
+            events: POLLIN,
+            revents: 0,
+        };
+        // errors (like being interrupted) just make us try reading again
+        unsafe { poll(&mut fd, 1, -1) };
+    }
+
+    #[cfg(not(unix))]
+    fn wait_readable(&self) {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    fn token(&self, byte: Option<u8>) -> Token<'_> {
+        HOLDS_TOKEN.with(|h| h.set(h.get() + 1));
+        return Token {
+            jobserver: self,
+            byte,
+        };
+    }
+
+    /**
+     * Uses the jobserver described by `auth`, either `fifo:PATH` or the file descriptors `R,W`.
+     */
+    fn connect(auth: &str) -> Option<Self> {
+        let (read, write) = match auth.strip_prefix("fifo:") {
+            Some(path) => {
+                let fifo = OpenOptions::new().read(true).write(true).open(path).ok()?;
+                (fifo.try_clone().ok()?, fifo)
+            }
+            None => {
+                let (r, w) = auth.split_once(",")?;
+                let (r, w): (i32, i32) = (r.parse().ok()?, w.parse().ok()?);
+                if r < 0 || w < 0 {
+                    return None;
+                }
+                // reopening gives us our own handles, whatever make did with its file descriptors
+                (
+                    File::open(format!("/dev/fd/{r}")).ok()?,
+                    OpenOptions::new()
+                        .write(true)
+                        .open(format!("/dev/fd/{w}"))
+                        .ok()?,
+                )
+            }
+        };
+        return Some(Self {
+            read,
+            write,
+            implicit: AtomicBool::new(true),
+            fifo: None,
+            makeflags: None,
+        });
+    }
+
+    /**
+     * Creates a fifo holding `jobs - 1` tokens, removed again when the process exits.
+     */
+    #[cfg(unix)]
+    fn serve(jobs: usize, makeflags: &str) -> Option<Self> {
+        use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+        let path = std::env::temp_dir().join(format!("greathelm-jobserver-{}", std::process::id()));
+        std::fs::remove_file(&path).ok();
+        let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        if unsafe { mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+            warning!(
+                "Could not create a jobserver at \x1bc{}\x1br, nested builds will not share CPUs.",
+                path.display()
+            );
+            return None;
+        }
+
+        // JOBSERVER isn't set yet, remove the fifo ourselves if we can't use it
+        let opened = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .and_then(|f| Ok((f.try_clone()?, f)));
+        let (read, fifo) = match opened {
+            Ok(f) if (&f.1).write_all(&vec![b'+'; jobs - 1]).is_ok() => f,
+            _ => {
+                std::fs::remove_file(&path).ok();
+                return None;
+            }
+        };
+        unsafe { atexit(cleanup) };
+
+        // keep everything else make was told, but only our jobserver
+        let mut flags: Vec<&str> = makeflags
+            .split_whitespace()
+            .filter(|f| {
+                !f.starts_with("--jobserver-auth=")
+                    && !f.starts_with("--jobserver-fds=")
+                    && !f.starts_with("-j")
+            })
+            .collect();
+        let jobs_flag = format!("-j{jobs}");
+        let auth_flag = format!("--jobserver-auth=fifo:{}", path.display());
+        flags.push(&jobs_flag);
+        flags.push(&auth_flag);
+
+        return Some(Self {
+            read,
+            write: fifo,
+            implicit: AtomicBool::new(true),
+            fifo: Some(path),
+            makeflags: Some(flags.join(" ")),
+        });
+    }
+
+    #[cfg(not(unix))]
+    fn serve(_jobs: usize, _makeflags: &str) -> Option<Self> {
+        return None;
+    }
+}
+
+impl Drop for Token<'_> {
+    fn drop(&mut self) {
+        HOLDS_TOKEN.with(|h| h.set(h.get().saturating_sub(1)));
+        match self.byte {
+            None => self.jobserver.implicit.store(true, Ordering::SeqCst),
+            Some(b) => {
+                // losing a token only slows the build down
+                (&self.jobserver.write).write_all(&[b]).ok();
+            }
+        }
+    }
+}
+
+#[cfg(all(
+    unix,
+    any(target_os = "macos", target_os = "ios", target_os = "freebsd")
+))]
+type Mode = u16;
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))
+))]
+type Mode = u32;
+
+#[cfg(all(unix, target_os = "linux"))]
+type NFds = std::ffi::c_ulong;
+#[cfg(all(unix, not(target_os = "linux")))]
+type NFds = std::ffi::c_uint;
+
+#[cfg(unix)]
+const POLLIN: i16 = 0x1;
+
+#[cfg(unix)]
+#[repr(C)]
+struct PollFd {
+    fd: i32,
+    events: i16,
+    revents: i16,
+}
+
+#[cfg(unix)]
+extern "C" {
+    fn mkfifo(path: *const std::ffi::c_char, mode: Mode) -> i32;
+    fn poll(fds: *mut PollFd, nfds: NFds, timeout: i32) -> i32;
+    fn atexit(callback: extern "C" fn()) -> i32;
+}
+
+/**
+ * The number of jobs make was started with (`-jN` in `MAKEFLAGS`), None if it wasn't or there is
+ * no jobserver to share them with. Once this process serves a jobserver its own number of jobs is
+ * used, so projects built in-process share the jobs of the project building them.
+ */
+pub fn make_jobs() -> Option<usize> {
+    let makeflags = match makeflags() {
+        Some(flags) => flags.to_string(),
+        None => std::env::var("MAKEFLAGS").ok()?,
+    };
+    return jobs_in(&makeflags);
+}
+
+/**
+ * The last `-jN` in `makeflags`, None if there is none or no jobserver is given with it.
+ */
+fn jobs_in(makeflags: &str) -> Option<usize> {
+    find_auth(makeflags)?;
+    return makeflags
+        .split_whitespace()
+        .filter_map(|f| f.strip_prefix("-j").and_then(|j| j.parse().ok()))
+        .next_back();
+}
+
+/**
+ * The jobserver given in `makeflags` with `--jobserver-auth=` (or `--jobserver-fds=` by older
+ * versions of make). The last one wins like it does for make.
+ */
+fn find_auth(makeflags: &str) -> Option<String> {
+    return makeflags
+        .split_whitespace()
+        .filter_map(|f| {
+            f.strip_prefix("--jobserver-auth=")
+                .or_else(|| f.strip_prefix("--jobserver-fds="))
+        })
+        .next_back()
+        .map(|a| a.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_jobserver_in_makeflags() {
+        assert_eq!(find_auth(""), None);
+        assert_eq!(find_auth("-j4 -k"), None);
+        assert_eq!(
+            find_auth("-j4 --jobserver-auth=3,4"),
+            Some("3,4".to_string())
+        );
+        assert_eq!(
+            find_auth("--jobserver-fds=3,4 -j --jobserver-auth=fifo:/tmp/js"),
+            Some("fifo:/tmp/js".to_string())
+        );
+
+        assert_eq!(jobs_in("-j4 --jobserver-auth=3,4"), Some(4));
+        assert_eq!(jobs_in("-j2 -k -j8 --jobserver-fds=3,4"), Some(8));
+        // without a jobserver there is nothing to share the jobs with
+        assert_eq!(jobs_in("-j4"), None);
+        assert_eq!(jobs_in("-j --jobserver-auth=3,4"), None);
+    }
+
+    #[test]
+    fn rejects_unusable_jobservers() {
+        assert!(Jobserver::connect("fifo:/nonexistent/greathelm-jobserver").is_none());
+        assert!(Jobserver::connect("-1,4").is_none());
+        assert!(Jobserver::connect("3").is_none());
+        assert!(Jobserver::connect("a,b").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hands_out_tokens() {
+        use std::{ffi::CString, os::unix::ffi::OsStrExt, sync::mpsc, time::Duration};
+
+        let path =
+            std::env::temp_dir().join(format!("greathelm-jobserver-test-{}", std::process::id()));
+        std::fs::remove_file(&path).ok();
+        let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        let jobserver = Jobserver::connect(&format!("fifo:{}", path.display())).unwrap();
+        // two tokens in the fifo and the implicit one make three jobs
+        (&jobserver.write).write_all(b"++").unwrap();
+
+        let implicit = jobserver.acquire().unwrap();
+        assert_eq!(implicit.byte, None);
+        let first = jobserver.acquire().unwrap();
+        let second = jobserver.acquire().unwrap();
+        assert_eq!((first.byte, second.byte), (Some(b'+'), Some(b'+')));
+        assert_eq!(HOLDS_TOKEN.with(|h| h.get()), 3);
+
+        let (sender, receiver) = mpsc::channel();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                let token = jobserver.acquire().unwrap();
+                sender.send(token.byte).unwrap();
+            });
+            // every token is taken until one is given back
+            assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+            drop(first);
+            assert_eq!(
+                receiver.recv_timeout(Duration::from_secs(10)).unwrap(),
+                Some(b'+')
+            );
+        });
+
+        drop(second);
+        drop(implicit);
+        assert_eq!(HOLDS_TOKEN.with(|h| h.get()), 0);
+        // the implicit token is used first again
+        assert_eq!(jobserver.acquire().unwrap().byte, None);
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -1,16 +1,24 @@
-use crate::{identify::NamespacedIdentifier, manifest::ProjectManifest};
+use crate::{
+    error::GreathelmError, identify::NamespacedIdentifier, manifest::ProjectManifest,
+    probe::ToolRequirement,
+};
+
+use self::schema::PropertySchema;
 
 pub mod c;
+pub mod compdb;
 pub mod cpp;
 pub mod custom;
 pub mod dependency;
+pub mod jobserver;
 pub mod parallel;
+pub mod schema;
 
 /**
  * Trait for project builders. These builders handle building the project, validating if it
  * can be built and cleaning up.
  */
-pub trait ProjectBuilder {
+pub trait ProjectBuilder: Send + Sync {
     /**
      * Pretty name of the builder. This is not used to call the builder. It is currently completely
      * unused but will be used in a plugin-tree view in the future.
@@ -31,7 +39,7 @@ pub trait ProjectBuilder {
     /**
      * Actually builds the project.
      */
-    fn build(&self, manifest: &ProjectManifest);
+    fn build(&self, manifest: &ProjectManifest) -> Result<(), GreathelmError>;
     /**
      * Validate is called before build in an effort to ensure the project
      * is in working order to be built. This is where you would put things
@@ -44,5 +52,28 @@ pub trait ProjectBuilder {
      * In the C builder this would remove all stale objects in the build
      * directory.
      */
-    fn cleanup(&self, manifest: &ProjectManifest);
+    fn cleanup(&self, manifest: &ProjectManifest) -> Result<(), GreathelmError>;
+    /**
+     * This is called by the `greathelm compdb` command.
+     * Writes a compilation database (compile_commands.json) for the project without actually
+     * building it. Returns false if this builder does not support compilation databases.
+     */
+    fn write_compile_commands(&self, _manifest: &ProjectManifest) -> Result<bool, GreathelmError> {
+        Ok(false)
+    }
+    /**
+     * The manifest properties this builder reads. They are checked before building, and unknown
+     * properties are reported as likely typos. Builders that don't have a schema (the default)
+     * can read any property so unknown ones aren't reported for their projects.
+     */
+    fn get_property_schema(&self) -> Vec<PropertySchema> {
+        vec![]
+    }
+    /**
+     * The programs building `manifest` needs, like its compiler. `greathelm doctor` reports the
+     * ones that are missing. The default is none.
+     */
+    fn get_required_tools(&self, _manifest: &ProjectManifest) -> Vec<ToolRequirement> {
+        vec![]
+    }
 }
@@ -1,95 +1,335 @@
-use std::sync::{mpsc, Arc, Mutex};
+use std::{
+    collections::VecDeque,
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+};
 
-use crate::term::*;
+use crate::{
+    error::GreathelmError,
+    manifest::ProjectManifest,
+    term::{self, OutputContext},
+};
 
-type Job = Box<dyn FnOnce() + Send + 'static>;
+use super::jobserver;
+
+type Job = Box<dyn FnOnce() -> Result<(), GreathelmError> + Send + 'static>;
+
+/**
+ * A job of `run_scoped`, which may borrow from its caller.
+ */
+pub type ScopedJob<'a> = Box<dyn FnOnce() -> Result<(), GreathelmError> + Send + 'a>;
+
+/**
+ * A job of a ParallelBuild that failed.
+ */
+pub struct JobFailure {
+    /**
+     * The name the job was submitted with, usually the file it builds.
+     */
+    pub name: String,
+    /**
+     * What went wrong. It was already reported when the job failed.
+     */
+    #[allow(dead_code)] // the built-in builders only need the names
+    pub error: GreathelmError,
+}
 
 /**
  * Struct defining a specific parallel building run.
+ *
+ * Jobs return a Result. Failed jobs are reported as soon as they fail and collected, `wait`
+ * returns all of them. Unless the build keeps going, jobs that haven't started yet when one fails
+ * are skipped.
+ *
+ * Every job takes a token from the jobserver (see `jobserver`) while it runs, so parallel builds
+ * in nested Greathelm processes or under `make -j` share the CPUs instead of each using all of
+ * them.
  */
 pub struct ParallelBuild {
     threads: Vec<std::thread::JoinHandle<()>>,
-    sender: mpsc::Sender<Job>,
-    size: usize,
+    sender: Option<mpsc::Sender<(String, OutputContext, Job)>>,
+    failures: Arc<Mutex<Vec<JobFailure>>>,
+    skipped: Arc<AtomicUsize>,
+    keep_going: bool,
+}
+
+/**
+ * The number of jobs to run at once, the `build-cpus` property. Defaults to the jobs of the make
+ * (or parent Greathelm build) we run under, or the number of CPUs.
+ */
+pub fn get_build_cpus(manifest: &ProjectManifest) -> usize {
+    let default = match jobserver::make_jobs() {
+        Some(jobs) => jobs,
+        None => match std::thread::available_parallelism() {
+            Ok(v) => v.get(),
+            Err(_) => 4, // 4 feels like a safe-ish default
+        },
+    };
+    return manifest.get_usize_property("build-cpus", default);
 }
 
 impl ParallelBuild {
     /**
-     * Create a new ParallelBuild using `size` CPUs (parallel jobs) which will have a total of
-     * `total_jobs` jobs run on it.
+     * Create a new ParallelBuild using `size` CPUs (parallel jobs). With `keep_going` every job
+     * runs even after one failed (the `--keep-going` flag).
      */
-    pub fn new(size: usize, total_jobs: usize) -> Self {
-        assert!(size > 0);
-        let (sender, receiver): (mpsc::Sender<Job>, mpsc::Receiver<Job>) =
-            std::sync::mpsc::channel();
-        let receiver: Arc<Mutex<mpsc::Receiver<Job>>> = Arc::new(Mutex::new(receiver));
-        let completed: Arc<std::sync::atomic::AtomicUsize> =
-            Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    pub fn new(size: usize, keep_going: bool) -> Self {
+        let size = size.max(1);
+        jobserver::init(size);
+        let (sender, receiver) = mpsc::channel::<(String, OutputContext, Job)>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let failures: Arc<Mutex<Vec<JobFailure>>> = Arc::new(Mutex::new(Vec::new()));
+        let skipped = Arc::new(AtomicUsize::new(0));
+        let failed = Arc::new(AtomicBool::new(false));
+
         let mut threads = Vec::with_capacity(size);
         for _ in 0..size {
-            let recv = Arc::clone(&receiver);
-            let completed = Arc::clone(&completed);
-            let total_jobs = total_jobs.clone();
-            let handle = std::thread::spawn(move || loop {
-                let comp = completed.fetch_add(0, std::sync::atomic::Ordering::SeqCst);
-                if comp >= total_jobs {
-                    break;
+            let receiver = Arc::clone(&receiver);
+            let failures = Arc::clone(&failures);
+            let skipped = Arc::clone(&skipped);
+            let failed = Arc::clone(&failed);
+            threads.push(std::thread::spawn(move || loop {
+                // the lock is only held while waiting for the next job. Once the sender is dropped
+                // and every job is taken, recv fails and the worker is done
+                let next = match receiver.lock() {
+                    Ok(r) => r.recv(),
+                    Err(_) => break,
+                };
+                let (name, context, job) = match next {
+                    Ok(j) => j,
+                    Err(_) => break,
+                };
+                if !keep_going && failed.load(Ordering::SeqCst) {
+                    skipped.fetch_add(1, Ordering::SeqCst);
+                    continue;
                 }
-                let task = match recv.lock() {
-                    Ok(r) => match r.recv() {
-                        Ok(v) => v,
-                        Err(_) => {
-                            error!("A parallel build worker failed to receive jobs. The build WILL fail past this point. Abort.");
-                            panic!("Failed to receive jobs.");
+
+                // the job and its failure log like the thread that submitted it
+                let result = term::with_output_context(context, || {
+                    let result = match jobserver::get().map(|j| j.acquire()).transpose() {
+                        Ok(token) => {
+                            let result = run_job(&name, job);
+                            drop(token);
+                            result
                         }
-                    },
-                    Err(_) => {
-                        error!("A parallel build worker failed to receive jobs. The build WILL fail past this point. Abort.");
-                        panic!("Failed to receive jobs.");
+                        Err(e) => Err(e),
+                    };
+                    if let Err(error) = &result {
+                        error.report();
                     }
-                };
-                task();
-                completed.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
-            });
-
-            threads.push(handle);
+                    return result;
+                });
+                if let Err(error) = result {
+                    failed.store(true, Ordering::SeqCst);
+                    if let Ok(mut f) = failures.lock() {
+                        f.push(JobFailure { name, error });
+                    }
+                }
+            }));
         }
+
         Self {
             threads,
-            sender,
-            size,
+            sender: Some(sender),
+            failures,
+            skipped,
+            keep_going,
         }
     }
 
     /**
-     * Submit a job to this ParallelBuild.
+     * Submit a job to this ParallelBuild. `name` identifies the job in failures. The job logs
+     * like the thread submitting it (see `term::OutputContext`).
      */
-    pub fn submit<F>(&mut self, f: F)
+    pub fn submit<F>(&mut self, name: &str, f: F)
     where
-        F: FnOnce() + Send + 'static,
+        F: FnOnce() -> Result<(), GreathelmError> + Send + 'static,
     {
-        self.sender.send(Box::new(f)).unwrap();
+        if let Some(sender) = &self.sender {
+            // the workers only stop once the sender is dropped, so this can't fail
+            sender
+                .send((name.into(), term::output_context(), Box::new(f)))
+                .ok();
+        }
     }
 
     /**
-     * Waits for this ParallelBuild to finish running.
+     * Waits for every submitted job to finish (or be skipped) and returns the jobs that failed.
      * You MUST call this or the ParallelBuild's threads will not be joined.
      */
-    pub fn wait(&mut self) {
-        for _ in 0..self.size {
-            self.submit(|| {});
+    pub fn wait(&mut self) -> Vec<JobFailure> {
+        // no more jobs, the workers exit once the queue is empty
+        self.sender = None;
+        for handle in self.threads.drain(..) {
+            handle.join().ok();
+        }
+        return match self.failures.lock() {
+            Ok(mut f) => std::mem::take(&mut *f),
+            Err(_) => vec![],
+        };
+    }
+
+    /**
+     * Waits like `wait`, failing with `message` followed by the name of every failed job if any
+     * did. Mentions the jobs that were skipped because of a failure.
+     */
+    pub fn finish(&mut self, message: &str) -> Result<(), GreathelmError> {
+        let failures = self.wait();
+        if failures.is_empty() {
+            return Ok(());
         }
+        let mut names: Vec<String> = failures.into_iter().map(|f| f.name).collect();
+        names.sort();
+        let mut text = format!("{message} \x1bc{}\x1br.", names.join(", "));
+        let skipped = self.skipped.load(Ordering::SeqCst);
+        if skipped > 0 && !self.keep_going {
+            text.push_str(&format!(
+                "\nSkipped \x1bc{skipped}\x1br more, use \x1bc--keep-going\x1br to build everything \
+                 that can be built."
+            ));
+        }
+        return Err(GreathelmError::BuildError(text));
+    }
+}
+
+/**
+ * Runs `jobs` on up to `size` threads like a ParallelBuild and returns the jobs that failed
+ * without reporting them. Jobs skipped because another one failed aren't included. Unlike the
+ * jobs of a ParallelBuild these may borrow from the caller and don't take a jobserver token: they
+ * run builds that take tokens for their own jobs, like the modules and dependencies of a project.
+ */
+pub fn run_scoped(
+    size: usize,
+    keep_going: bool,
+    jobs: Vec<(String, ScopedJob)>,
+) -> Vec<JobFailure> {
+    let context = term::output_context();
+    let queue = Mutex::new(VecDeque::from(jobs));
+    let failures: Mutex<Vec<JobFailure>> = Mutex::new(Vec::new());
+    let failed = AtomicBool::new(false);
 
-        'outer: loop {
-            let mut cont: bool = false;
-            for handle in &mut self.threads {
-                if !handle.is_finished() {
-                    cont = true;
+    std::thread::scope(|scope| {
+        for _ in 0..size.max(1) {
+            let context = context.clone();
+            let (queue, failures, failed) = (&queue, &failures, &failed);
+            scope.spawn(move || loop {
+                let (name, job) = match queue.lock().unwrap().pop_front() {
+                    Some(j) => j,
+                    None => break,
+                };
+                if !keep_going && failed.load(Ordering::SeqCst) {
+                    continue;
                 }
-            }
-            if !cont {
-                break 'outer;
-            }
+                let result = term::with_output_context(context.clone(), || run_job(&name, job));
+                if let Err(error) = result {
+                    failed.store(true, Ordering::SeqCst);
+                    failures.lock().unwrap().push(JobFailure { name, error });
+                }
+            });
         }
+    });
+    return failures.into_inner().unwrap();
+}
+
+/**
+ * Runs `job`, turning a panic into an error.
+ */
+fn run_job<'a>(
+    name: &str,
+    job: impl FnOnce() -> Result<(), GreathelmError> + 'a,
+) -> Result<(), GreathelmError> {
+    return match std::panic::catch_unwind(AssertUnwindSafe(job)) {
+        Ok(r) => r,
+        Err(_) => Err(GreathelmError::BuildError(format!(
+            "Building \x1bc{name}\x1br panicked."
+        ))),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fail(name: &str) -> Result<(), GreathelmError> {
+        return Err(GreathelmError::BuildError(format!("{name} failed")));
+    }
+
+    #[test]
+    fn collects_every_failure_when_keeping_going() {
+        let ran = Arc::new(AtomicUsize::new(0));
+        let mut build = ParallelBuild::new(2, true);
+        for name in ["a.c", "b.c", "c.c", "d.c"] {
+            let ran = Arc::clone(&ran);
+            build.submit(name, move || {
+                ran.fetch_add(1, Ordering::SeqCst);
+                if name == "b.c" || name == "d.c" {
+                    return fail(name);
+                }
+                return Ok(());
+            });
+        }
+        let mut failures: Vec<(String, String)> = build
+            .wait()
+            .into_iter()
+            .map(|f| (f.name, f.error.message()))
+            .collect();
+        failures.sort();
+        assert_eq!(ran.load(Ordering::SeqCst), 4);
+        assert_eq!(
+            failures,
+            [
+                ("b.c".to_string(), "b.c failed".to_string()),
+                ("d.c".to_string(), "d.c failed".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_the_rest_after_a_failure() {
+        let mut build = ParallelBuild::new(1, false);
+        build.submit("a.c", || fail("a.c"));
+        build.submit("b.c", || panic!("b.c should have been skipped"));
+        build.submit("c.c", || Ok(()));
+        let message = build.finish("Failed to compile").unwrap_err().message();
+        assert!(message.starts_with("Failed to compile \x1bca.c\x1br."));
+        assert!(message.contains("Skipped \x1bc2\x1br more"));
+    }
+
+    #[test]
+    fn turns_panics_into_failures() {
+        let mut build = ParallelBuild::new(2, true);
+        build.submit("a.c", || panic!("compiler exploded"));
+        build.submit("b.c", || Ok(()));
+        let failures = build.wait();
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].error.message().contains("panicked"));
+    }
+
+    #[test]
+    fn runs_scoped_jobs() {
+        let built = Mutex::new(Vec::new());
+        let jobs: Vec<(String, ScopedJob)> = ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                let built = &built;
+                let job: ScopedJob = Box::new(move || {
+                    built.lock().unwrap().push(name.to_string());
+                    if *name == "b" {
+                        return fail(name);
+                    }
+                    return Ok(());
+                });
+                (name.to_string(), job)
+            })
+            .collect();
+        let failures = run_scoped(2, true, jobs);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].name, "b");
+        let mut built = built.into_inner().unwrap();
+        built.sort();
+        assert_eq!(built, ["a", "b", "c"]);
     }
 }
@@ -0,0 +1,326 @@
+#![allow(dead_code)] // not every kind is used by the built-in builders, they are for plugins.
+
+use crate::{error::GreathelmError, manifest::ProjectManifest, term::*};
+
+use super::ProjectBuilder;
+
+/**
+ * What values a property accepts.
+ */
+#[derive(Clone, Debug)]
+pub enum PropertyKind {
+    /**
+     * Any text.
+     */
+    Text,
+    /**
+     * A whole number.
+     */
+    Integer,
+    /**
+     * `true` or `false`.
+     */
+    Bool,
+    /**
+     * One of the listed values.
+     */
+    OneOf(Vec<String>),
+    /**
+     * A comma separated list, like `Additional-CC-Flags`.
+     */
+    List,
+}
+
+/**
+ * A property a builder understands. Builders return these from `get_property_schema` so typos
+ * and bad values in manifests are reported before building.
+ */
+#[derive(Clone, Debug)]
+pub struct PropertySchema {
+    pub key: String,
+    pub kind: PropertyKind,
+    pub description: String,
+}
+
+impl PropertySchema {
+    pub fn create(key: &str, kind: PropertyKind, description: &str) -> Self {
+        return Self {
+            key: key.into(),
+            kind,
+            description: description.into(),
+        };
+    }
+
+    /**
+     * Checks `value` against this property's kind. Returns what is wrong with it.
+     */
+    pub fn check(&self, value: &str) -> Option<String> {
+        let value = value.trim();
+        let ok = match &self.kind {
+            PropertyKind::Text | PropertyKind::List => true,
+            PropertyKind::Integer => value.parse::<i64>().is_ok(),
+            PropertyKind::Bool => value == "true" || value == "false",
+            PropertyKind::OneOf(values) => values.iter().any(|v| v == value),
+        };
+        if ok {
+            return None;
+        }
+        let expected = match &self.kind {
+            PropertyKind::Integer => "a whole number".to_string(),
+            PropertyKind::Bool => "\x1bctrue\x1br or \x1bcfalse\x1br".to_string(),
+            PropertyKind::OneOf(values) => format!(
+                "one of {}",
+                values
+                    .iter()
+                    .map(|v| format!("\x1bc{v}\x1br"))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            _ => unreachable!(),
+        };
+        return Some(format!(
+            "\x1bc{}\x1br must be {expected}, not \x1bc{value}\x1br.",
+            self.key
+        ));
+    }
+}
+
+/**
+ * Properties every project may set regardless of its builder.
+ */
+pub fn global_schema() -> Vec<PropertySchema> {
+    return vec![
+        PropertySchema::create("Project-Name", PropertyKind::Text, "Name of the project."),
+        PropertySchema::create(
+            "Project-Namespace",
+            PropertyKind::Text,
+            "Namespace of the project, used when importing it into the store.",
+        ),
+        PropertySchema::create(
+            "Project-Author",
+            PropertyKind::Text,
+            "Author of the project.",
+        ),
+        PropertySchema::create(
+            "Project-Version",
+            PropertyKind::Text,
+            "Version of the project.",
+        ),
+        PropertySchema::create(
+            "Project-Type",
+            PropertyKind::Text,
+            "Builder used to build the project.",
+        ),
+        PropertySchema::create(
+            "Greathelm-Version",
+            PropertyKind::Text,
+            "Version of Greathelm the project was created with.",
+        ),
+        PropertySchema::create(
+            "PackageShellPS1",
+            PropertyKind::Text,
+            "Prompt of `greathelm pkgshell`.",
+        ),
+    ];
+}
+
+/**
+ * Properties describing the target toolchain (see `target::Toolchain`), for builders that use one.
+ */
+pub fn toolchain_schema() -> Vec<PropertySchema> {
+    return vec![
+        PropertySchema::create(
+            "Target-Triple",
+            PropertyKind::Text,
+            "Triple of the target, like `aarch64-linux-gnu`. Selects `<triple>-gcc` and \
+             `<triple>-ar` unless they are overridden.",
+        ),
+        PropertySchema::create(
+            "Target-Sysroot",
+            PropertyKind::Text,
+            "Sysroot of the target, passed as `--sysroot` and to pkgconf.",
+        ),
+        PropertySchema::create(
+            "Target-PkgConfig-Path",
+            PropertyKind::Text,
+            "Where pkgconf looks for the target's `.pc` files.",
+        ),
+        PropertySchema::create(
+            "Target-Flags",
+            PropertyKind::List,
+            "Flags passed to both the compiler and the linker, comma separated.",
+        ),
+        PropertySchema::create(
+            "Override-Archiver",
+            PropertyKind::Text,
+            "Archiver used to bundle static libraries instead of `ar`.",
+        ),
+    ];
+}
+
+/**
+ * Checks `manifest` before it is built with `builder`.
+ *
+ * Warns about properties no builder knows (with a suggestion if they look like a typo), properties
+ * overridden within the same file and anything else noticed while reading the manifest. Values of
+ * properties `builder` knows and @Module directives are checked as well, every problem found there
+ * is returned as one error. Keys that start lowercase are command line flags and are not checked.
+ * Unknown keys aren't reported if `builder` has no schema, as it may read any property.
+ */
+pub fn check_manifest(
+    manifest: &ProjectManifest,
+    builder: Option<&dyn ProjectBuilder>,
+    builders: &[Box<dyn ProjectBuilder>],
+) -> Result<(), GreathelmError> {
+    for w in &manifest.warnings {
+        warning!("{w}");
+    }
+
+    let mut schema = global_schema();
+    let builder_schema = match builder {
+        Some(b) => b.get_property_schema(),
+        None => vec![],
+    };
+    let mut known: Vec<String> = schema.iter().map(|s| s.key.clone()).collect();
+    for b in builders {
+        known.extend(b.get_property_schema().into_iter().map(|s| s.key));
+    }
+    let check_unknown = !builder_schema.is_empty();
+    schema.extend(builder_schema);
+
+    let mut keys: Vec<&String> = manifest.property_locations.keys().collect();
+    keys.sort();
+    let mut problems = Vec::new();
+    for key in keys {
+        if !key.starts_with(|c: char| c.is_ascii_uppercase()) {
+            continue;
+        }
+        let location = manifest.property_location(key).unwrap();
+        if let Some(s) = schema.iter().find(|s| &s.key == key) {
+            if let Some(value) = manifest.properties.get(key) {
+                if let Some(problem) = s.check(value) {
+                    problems.push(format!("\x1bc{location}\x1br: {problem}"));
+                }
+            }
+            continue;
+        }
+        if !check_unknown || known.contains(key) || manifest.referenced.contains(key) {
+            continue;
+        }
+        match suggestion(key, &known) {
+            Some(s) => warning!(
+                "\x1bc{location}\x1br: Unknown property \x1bc{key}\x1br. Did you mean \x1bc{s}\x1br?"
+            ),
+            None => warning!("\x1bc{location}\x1br: Unknown property \x1bc{key}\x1br."),
+        }
+    }
+
+    // @Module <name> <path in project>:<path in module>...
+    for (i, m) in manifest
+        .directives
+        .get("Module")
+        .unwrap_or(&vec![])
+        .iter()
+        .enumerate()
+    {
+        let at = match manifest.directive_location("Module", i) {
+            Some(l) => format!("\x1bc{l}\x1br: "),
+            None => String::new(),
+        };
+        let mut parts = m.split_whitespace();
+        if parts.next().is_none() {
+            problems.push(format!("{at}@Module needs a module name."));
+            continue;
+        }
+        for component in parts {
+            match component.split_once(":") {
+                Some((home, module)) if !home.is_empty() && !module.is_empty() => {}
+                _ => problems.push(format!(
+                    "{at}Module component \x1bc{component}\x1br is not in the form \
+                     \x1bc<path in project>:<path in module>\x1br."
+                )),
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+    return Err(GreathelmError::ManifestError(problems.join("\n")));
+}
+
+/**
+ * The known key closest to `key`, if it is close enough to be a typo.
+ */
+fn suggestion(key: &str, known: &[String]) -> Option<String> {
+    let key = key.to_lowercase();
+    return known
+        .iter()
+        .map(|k| (edit_distance(&key, &k.to_lowercase()), k))
+        .filter(|(d, k)| *d <= 2.max(k.len() / 5))
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, k)| k.clone());
+}
+
+/**
+ * Levenshtein distance between `a` and `b` that counts swapping two neighbouring characters as
+ * one edit.
+ */
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    return d[a.len()][b.len()];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("Emit", "Emit"), 0);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        // a swap of neighbouring characters is a single edit
+        assert_eq!(edit_distance("Emti", "Emit"), 1);
+        assert_eq!(edit_distance("Project-Nmae", "Project-Name"), 1);
+    }
+
+    #[test]
+    fn suggests_close_keys() {
+        let known: Vec<String> = ["Project-Name", "Project-Type", "Emit", "Compiler-Opt-Level"]
+            .iter()
+            .map(|k| k.to_string())
+            .collect();
+        assert_eq!(
+            suggestion("Project-Nmae", &known),
+            Some("Project-Name".into())
+        );
+        // case doesn't count
+        assert_eq!(suggestion("emit", &known), Some("Emit".into()));
+        assert_eq!(
+            suggestion("Compiler-Opt-Lvl", &known),
+            Some("Compiler-Opt-Level".into())
+        );
+        assert_eq!(suggestion("Linker", &known), None);
+    }
+}
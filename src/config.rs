@@ -1,6 +1,6 @@
 use std::{path::PathBuf, str::FromStr};
 
-use crate::{store, template, term::*};
+use crate::{error::GreathelmError, store, template};
 
 /**
  * Gets the base config directory.
@@ -53,7 +53,7 @@ pub fn get_data_base_dir() -> PathBuf {
 /**
  * Called once on startup to ensure all the config directories exist.
  */
-pub fn ensure_config_dirs() {
+pub fn ensure_config_dirs() -> Result<(), GreathelmError> {
     let ghconfig_base = get_config_base_dir();
     let ghconfig_plugins =
         PathBuf::from_str(format!("{}/plugins", ghconfig_base.to_str().unwrap()).as_str()).unwrap();
@@ -62,37 +62,35 @@ pub fn ensure_config_dirs() {
     let ghdata_base = get_data_base_dir();
     let ghdata_store = store::get_store_path();
     let ghdata_templates = template::get_templates_path();
-    ensure_dir(ghconfig_base);
-    ensure_dir(ghconfig_plugins);
-    ensure_dir(ghconfig_scripts);
-    ensure_dir(ghdata_base);
-    ensure_dir(ghdata_store);
-    ensure_dir(ghdata_templates);
+    ensure_dir(ghconfig_base)?;
+    ensure_dir(ghconfig_plugins)?;
+    ensure_dir(ghconfig_scripts)?;
+    ensure_dir(ghdata_base)?;
+    ensure_dir(ghdata_store)?;
+    ensure_dir(ghdata_templates)?;
+    return Ok(());
 }
 
 /**
  * Checks if a directory exists. If it does not, it will create it.
  */
-pub fn ensure_dir(path: PathBuf) {
-    if !match path.try_exists() {
+pub fn ensure_dir(path: PathBuf) -> Result<(), GreathelmError> {
+    let exists = match path.try_exists() {
         Ok(ex) => ex,
         Err(e) => {
-            print_error_obj(
-                Some("Could not ensure config directories. Abort.".into()),
-                Box::new(e),
-            );
-            std::process::exit(1);
+            return Err(GreathelmError::io(
+                "Could not ensure config directories.",
+                e,
+            ));
         }
-    } {
-        match std::fs::create_dir_all(path) {
-            Ok(_) => {}
-            Err(e) => {
-                print_error_obj(
-                    Some("Could not ensure config directories. Abort.".into()),
-                    Box::new(e),
-                );
-                std::process::exit(1);
-            }
+    };
+    if !exists {
+        if let Err(e) = std::fs::create_dir_all(path) {
+            return Err(GreathelmError::io(
+                "Could not ensure config directories.",
+                e,
+            ));
         }
     }
+    return Ok(());
 }
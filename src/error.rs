@@ -0,0 +1,114 @@
+use std::fmt::Display;
+
+use crate::term::*;
+
+/**
+ * Errors returned by Greathelm's library functions. Nothing in the library exits the process on
+ * failure. The CLI reports these with `report` and decides to exit.
+ *
+ * Messages may contain Greathelm's shortened color codes (`\x1bc`/`\x1br`) to highlight names.
+ * `report` turns them into colors while the Display implementation strips them.
+ */
+#[derive(Debug)]
+pub enum GreathelmError {
+    /**
+     * An I/O operation failed. `context` describes what was being done.
+     */
+    IOError {
+        context: String,
+        error: std::io::Error,
+    },
+    /**
+     * A manifest could not be read or contains something invalid.
+     */
+    ManifestError(String),
+    /**
+     * A dependency could not be parsed, resolved, verified or built.
+     */
+    DependencyError(String),
+    /**
+     * A template could not be resolved or a project could not be generated from it.
+     */
+    TemplateError(String),
+    /**
+     * A Greathelm subprocess could not be spawned or failed.
+     */
+    SubprocessError(String),
+    /**
+     * A project or module failed to build.
+     */
+    BuildError(String),
+    /**
+     * An action could not be carried out (bad arguments, missing items, ...).
+     */
+    ActionError(String),
+    /**
+     * A plugin could not be reached or failed to handle a message.
+     */
+    PluginError(String),
+}
+
+impl GreathelmError {
+    /**
+     * Creates an IOError from `error` with a description of what was being done.
+     */
+    pub fn io<S: Into<String>>(context: S, error: std::io::Error) -> Self {
+        Self::IOError {
+            context: context.into(),
+            error,
+        }
+    }
+
+    /**
+     * Gets the message including the shortened color codes.
+     */
+    pub fn message(&self) -> String {
+        match self {
+            Self::IOError { context, error } => format!("{context}\n{error}"),
+            Self::ManifestError(m)
+            | Self::DependencyError(m)
+            | Self::TemplateError(m)
+            | Self::SubprocessError(m)
+            | Self::BuildError(m)
+            | Self::ActionError(m)
+            | Self::PluginError(m) => m.clone(),
+        }
+    }
+
+    /**
+     * Prints this error to the terminal as [ERROR]s, one per line of the message.
+     */
+    pub fn report(&self) {
+        for line in self.message().lines() {
+            error!("{line}");
+        }
+    }
+
+    /**
+     * Same as report but as [WARN]s, for errors that don't stop Greathelm.
+     */
+    pub fn report_as_warning(&self) {
+        for line in self.message().lines() {
+            warning!("{line}");
+        }
+    }
+}
+
+impl Display for GreathelmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.message().replace("\x1bc", "").replace("\x1br", "")
+        )
+    }
+}
+
+impl std::error::Error for GreathelmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IOError { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 
-use crate::term::*;
+use crate::{error::GreathelmError, term::*};
 
 use super::ProjectGenerator;
 
@@ -27,20 +27,20 @@ impl ProjectGenerator for CGenerator {
     fn get_aliases(&self) -> Vec<String> {
         vec!["c".into()]
     }
-    fn generate(&self, _cwd: PathBuf) {
-        super::helper::create_directory("src");
-        super::helper::create_directory("lib");
-        super::helper::create_directory("export");
-        super::helper::create_directory("lib/include");
-        super::helper::create_directory("lib/shared");
-        super::helper::create_directory("lib/obj");
+    fn generate(&self, _cwd: PathBuf) -> Result<(), GreathelmError> {
+        super::helper::create_directory("src")?;
+        super::helper::create_directory("lib")?;
+        super::helper::create_directory("export")?;
+        super::helper::create_directory("lib/include")?;
+        super::helper::create_directory("lib/shared")?;
+        super::helper::create_directory("lib/obj")?;
 
         let main_c_contents = "#include <stdio.h>\n\
                                \n\
                                int main(int argc, char **argv) {\n\
                                    \tprintf(\"Hello World!\\n\");\n\
                                }\n";
-        super::helper::create_file("src/main.c", main_c_contents);
+        super::helper::create_file("src/main.c", main_c_contents)?;
 
         let mut project_name = question("Project name?".into());
         let mut project_namespace = question("Project namespace?".into());
@@ -79,8 +79,9 @@ impl ProjectGenerator for CGenerator {
                 env!("CARGO_PKG_VERSION")
             )
             .as_str(),
-        );
+        )?;
 
         ok!("Succeeded in generating project from template.");
+        return Ok(());
     }
 }
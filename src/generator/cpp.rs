@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 
-use crate::term::*;
+use crate::{error::GreathelmError, term::*};
 
 use super::ProjectGenerator;
 
@@ -27,20 +27,20 @@ impl ProjectGenerator for CPPGenerator {
             identifier: "C++".into(),
         }
     }
-    fn generate(&self, _cwd: PathBuf) {
-        super::helper::create_directory("src");
-        super::helper::create_directory("lib");
-        super::helper::create_directory("export");
-        super::helper::create_directory("lib/include");
-        super::helper::create_directory("lib/shared");
-        super::helper::create_directory("lib/obj");
+    fn generate(&self, _cwd: PathBuf) -> Result<(), GreathelmError> {
+        super::helper::create_directory("src")?;
+        super::helper::create_directory("lib")?;
+        super::helper::create_directory("export")?;
+        super::helper::create_directory("lib/include")?;
+        super::helper::create_directory("lib/shared")?;
+        super::helper::create_directory("lib/obj")?;
 
         let main_cpp_contents = "#include <iostream>\n\
                                  \n\
                                  int main(int argc, char **argv) {\n\
                                      \tstd::cout << \"Hello World!\" << std::endl;\n\
                                  }\n";
-        super::helper::create_file("src/main.cpp", main_cpp_contents);
+        super::helper::create_file("src/main.cpp", main_cpp_contents)?;
 
         let mut project_name = question("Project name?".into());
         let mut project_namespace = question("Project namespace?".into());
@@ -79,7 +79,8 @@ impl ProjectGenerator for CPPGenerator {
                 env!("CARGO_PKG_VERSION")
             )
             .as_str(),
-        );
+        )?;
         ok!("Succeeded in generating project from template.");
+        return Ok(());
     }
 }
@@ -4,7 +4,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::term::*;
+use crate::{error::GreathelmError, term::*};
 
 use super::ProjectGenerator;
 
@@ -31,21 +31,21 @@ impl ProjectGenerator for CustomGenerator {
             identifier: "Custom".into(),
         }
     }
-    fn generate(&self, cwd: PathBuf) {
-        super::helper::create_directory("src");
-        super::helper::create_directory("scripts");
+    fn generate(&self, cwd: PathBuf) -> Result<(), GreathelmError> {
+        super::helper::create_directory("src")?;
+        super::helper::create_directory("scripts")?;
         super::helper::create_file(
             "scripts/prebuild.sh",
             "#!/usr/bin/bash\necho !! prebuild.sh has not been written yet !!\n",
-        );
+        )?;
         super::helper::create_file(
             "scripts/build.sh",
             "#!/usr/bin/bash\necho !! build.sh has not been written yet !!\n",
-        );
+        )?;
         super::helper::create_file(
             "scripts/postbuild.sh",
             "#!/usr/bin/bash\necho !! postbuild.sh has not been written yet !!\n",
-        );
+        )?;
 
         // set permissions on UNIX systems.
         #[cfg(target_family = "unix")]
@@ -83,8 +83,9 @@ impl ProjectGenerator for CustomGenerator {
                 env!("CARGO_PKG_VERSION")
             )
             .as_str(),
-        );
+        )?;
 
         ok!("Succeeded in generating project from template.");
+        return Ok(());
     }
 }
@@ -1,27 +1,30 @@
 use std::path::Path;
 
-use crate::term::print_error_obj;
+use crate::error::GreathelmError;
 
-pub fn create_directory(name: &str) {
+pub fn create_directory(name: &str) -> Result<(), GreathelmError> {
     let path = Path::new(name);
     if !path.exists() {
         match std::fs::create_dir_all(path) {
             Ok(_) => {}
             Err(e) => {
-                print_error_obj(Some("Failed to create directory.".into()), Box::new(e));
-                std::process::exit(1);
+                return Err(GreathelmError::io(
+                    format!("Failed to create directory \x1bc{name}\x1br."),
+                    e,
+                ));
             }
         }
     }
+    return Ok(());
 }
 
-pub fn create_file(name: &str, contents: &str) {
+pub fn create_file(name: &str, contents: &str) -> Result<(), GreathelmError> {
     let path = Path::new(name);
     match std::fs::write(path, contents) {
-        Ok(_) => {}
-        Err(e) => {
-            print_error_obj(Some("Failed to write file.".into()), Box::new(e));
-            std::process::exit(1);
-        }
-    };
+        Ok(_) => Ok(()),
+        Err(e) => Err(GreathelmError::io(
+            format!("Failed to write file \x1bc{name}\x1br."),
+            e,
+        )),
+    }
 }
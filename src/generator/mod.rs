@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 
-use crate::identify::NamespacedIdentifier;
+use crate::{error::GreathelmError, identify::NamespacedIdentifier};
 
 pub mod c;
 pub mod cpp;
@@ -11,7 +11,7 @@ pub mod helper;
  * Trait for all project generators.
  *
  */
-pub trait ProjectGenerator {
+pub trait ProjectGenerator: Send + Sync {
     /**
      * Generator name. This is not necessarily used for calling the generator. It is currently
      * unused but will be used in a future plugin-tree display.
@@ -31,5 +31,5 @@ pub trait ProjectGenerator {
     /**
      * Actually generates the project. This is given the path to create the project in.
      */
-    fn generate(&self, cwd: PathBuf);
+    fn generate(&self, cwd: PathBuf) -> Result<(), GreathelmError>;
 }
@@ -1,71 +1,92 @@
-use std::{collections::HashMap, fs::ReadDir, path::Path};
+use std::{
+    collections::HashMap,
+    fs::ReadDir,
+    path::{Path, PathBuf},
+};
 
-use crate::term::*;
+use crate::error::GreathelmError;
 
 /**
- * Hashes all files in the src/ directory and then writes the result to the IBHT.ghd file.
+ * Hashes all files in the src/ directory of the project in `root` and then writes the result to
+ * the IBHT at `path` (IBHT.ghd, or the one of the active profile, see `profile::ibht_path`).
  */
-pub fn write_ibht() {
-    let hashes = gen_hashtable();
+pub fn write_ibht(root: &Path, path: &Path) -> Result<(), GreathelmError> {
+    write_hashtable(path, &gen_hashtable(root)?)
+}
 
+/**
+ * Writes a table of names to hashes to `path` in the IBHT format.
+ */
+pub fn write_hashtable(
+    path: &Path,
+    hashes: &HashMap<String, String>,
+) -> Result<(), GreathelmError> {
     let mut hashtable_file: String = "".into();
 
     for pair in hashes {
         hashtable_file.push_str(format!("{}={}\n", pair.0, pair.1).as_str());
     }
 
-    match std::fs::write("IBHT.ghd", hashtable_file) {
-        Ok(_) => {}
-        Err(e) => {
-            print_error_obj(Some("Failed to write IBHT.".into()), Box::new(e));
-        }
+    match std::fs::write(path, hashtable_file) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(GreathelmError::io(
+            format!("Failed to write \x1bc{}\x1br.", path.display()),
+            e,
+        )),
     }
 }
 
 /**
- * Generates a table of files in src/ to their MD5 hashes.
+ * Generates a table of files in src/ of the project in `root` to their MD5 hashes. Files are
+ * named relative to the project, like `src/main.c`.
  */
-pub fn gen_hashtable() -> HashMap<String, String> {
+pub fn gen_hashtable(root: &Path) -> Result<HashMap<String, String>, GreathelmError> {
     let mut hashes: HashMap<String, String> = HashMap::new();
 
-    let srcdir = Path::new("src");
+    let srcdir = root.join("src");
+    let srcdir = srcdir.as_path();
     if !srcdir.exists() {
-        error!("There is no source directory. Abort.");
-        return hashes;
+        return Err(GreathelmError::BuildError(
+            "There is no source directory.".into(),
+        ));
     }
     if !srcdir.is_dir() {
-        error!("src/ is not a directory. Abort.");
-        return hashes;
+        return Err(GreathelmError::BuildError(
+            "src/ is not a directory.".into(),
+        ));
     }
 
     match srcdir.read_dir() {
         Ok(iter) => {
-            recurse_dir(iter, &mut hashes);
+            recurse_dir(root, iter, &mut hashes)?;
         }
         Err(e) => {
-            print_error_obj(Some("Failed to read src".into()), Box::new(e));
-            return hashes;
+            return Err(GreathelmError::io("Failed to read src", e));
         }
     }
 
-    return hashes;
+    return Ok(hashes);
 }
 
-fn recurse_dir(dir: ReadDir, hashes: &mut HashMap<String, String>) {
+fn recurse_dir(
+    root: &Path,
+    dir: ReadDir,
+    hashes: &mut HashMap<String, String>,
+) -> Result<(), GreathelmError> {
     for f in dir {
         match f {
             Ok(f) => {
-                if f.metadata().unwrap().is_dir() {
+                if f.path().is_dir() {
                     recurse_dir(
+                        root,
                         match std::fs::read_dir(f.path()) {
                             Ok(dir) => dir,
                             Err(e) => {
-                                print_error_obj(Some("Failed to read src".into()), Box::new(e));
-                                std::process::exit(1);
+                                return Err(GreathelmError::io("Failed to read src", e));
                             }
                         },
                         hashes,
-                    );
+                    )?;
                 }
                 let contents = match std::fs::read_to_string(f.path()) {
                     Ok(contents) => contents,
@@ -74,29 +95,41 @@ fn recurse_dir(dir: ReadDir, hashes: &mut HashMap<String, String>) {
                     }
                 };
                 let hash = md5::compute(contents);
-                hashes.insert(f.path().display().to_string(), format!("{:x}", hash));
+                let path = f.path();
+                let name = path.strip_prefix(root).unwrap_or(&path);
+                hashes.insert(name.display().to_string(), format!("{:x}", hash));
             }
             Err(_) => {
                 continue;
             }
         }
     }
+
+    return Ok(());
 }
 
 /**
- * Reads IBHT.ghd from disk into a HashMap of files within src/ to their MD5 hashes.
+ * Reads the IBHT at `path` from disk into a HashMap of files within src/ to their MD5 hashes.
  */
-pub fn read_ibht() -> HashMap<String, String> {
-    let ibht_path = Path::new("IBHT.ghd");
-    if !ibht_path.exists() {
-        return HashMap::new();
+pub fn read_ibht(path: &Path) -> Result<HashMap<String, String>, GreathelmError> {
+    read_hashtable(path)
+}
+
+/**
+ * Reads a table in the IBHT format from `path`. Returns an empty table if it does not exist.
+ */
+pub fn read_hashtable(path: &Path) -> Result<HashMap<String, String>, GreathelmError> {
+    if !path.exists() {
+        return Ok(HashMap::new());
     }
 
-    let ibht_file = match std::fs::read_to_string(ibht_path) {
+    let ibht_file = match std::fs::read_to_string(path) {
         Ok(ibht) => ibht,
         Err(e) => {
-            print_error_obj(Some("Failed to read IBHT.".into()), Box::new(e));
-            std::process::exit(1);
+            return Err(GreathelmError::io(
+                format!("Failed to read \x1bc{}\x1br.", path.display()),
+                e,
+            ));
         }
     };
     let mut table: HashMap<String, String> = HashMap::new();
@@ -108,5 +141,258 @@ pub fn read_ibht() -> HashMap<String, String> {
         table.insert(f.into(), h.into());
     }
 
-    return table;
+    return Ok(table);
+}
+
+/**
+ * Reads the Module IBHT at `path` (MIBHT.ghd, or the one of the active profile, see
+ * `profile::mibht_path`) from disk. It maps each module to the hash of its project and each
+ * `<module>:<path>` file provided by a module to the hash it had when it was fetched.
+ */
+pub fn read_mibht(path: &Path) -> Result<HashMap<String, String>, GreathelmError> {
+    read_hashtable(path)
+}
+
+/**
+ * Writes the Module IBHT to `path`, creating the build directory it is in if needed.
+ */
+pub fn write_mibht(path: &Path, table: &HashMap<String, String>) -> Result<(), GreathelmError> {
+    if let Some(dir) = path.parent() {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            return Err(GreathelmError::io(
+                format!("Failed to create \x1bc{}\x1br.", dir.display()),
+                e,
+            ));
+        }
+    }
+    write_hashtable(path, table)
+}
+
+/**
+ * Hashes a single file. Returns None if the file could not be read.
+ */
+pub fn hash_file(path: &str) -> Option<String> {
+    match std::fs::read(path) {
+        Ok(contents) => Some(format!("{:x}", md5::compute(contents))),
+        Err(_) => None,
+    }
+}
+
+/**
+ * Hashes a file or a whole directory (the relative paths and contents of every file within it).
+ * Returns None if `path` does not exist.
+ */
+pub fn hash_path(path: &Path) -> Option<String> {
+    if !path.is_dir() {
+        return hash_file(&path.display().to_string());
+    }
+
+    let mut files: Vec<PathBuf> = Vec::new();
+    collect_files(path, &mut files);
+    files.sort();
+
+    let mut ctx = md5::Context::new();
+    for f in files {
+        ctx.consume(f.strip_prefix(path).unwrap_or(&f).display().to_string());
+        if let Ok(contents) = std::fs::read(&f) {
+            ctx.consume(contents);
+        }
+    }
+    Some(format!("{:x}", ctx.compute()))
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    if let Ok(rd) = std::fs::read_dir(dir) {
+        for ent in rd.flatten() {
+            if ent.path().is_dir() {
+                collect_files(&ent.path(), files);
+            } else {
+                files.push(ent.path());
+            }
+        }
+    }
+}
+
+/**
+ * Writes the IBDT (Incremental Build Dependency Table) to `path` (IBDT.ghd, or the one of the
+ * active profile).
+ * The IBDT maps each source file to the headers it included at the time it was last compiled
+ * along with the hash each header had at that point.
+ */
+pub fn write_ibdt(
+    path: &Path,
+    table: &HashMap<String, Vec<(String, String)>>,
+) -> Result<(), GreathelmError> {
+    let mut ibdt_file: String = "".into();
+
+    for (source, headers) in table {
+        let headers: Vec<String> = headers
+            .iter()
+            .map(|(h, hash)| format!("{h}|{hash}"))
+            .collect();
+        ibdt_file.push_str(format!("{}={}\n", source, headers.join(",")).as_str());
+    }
+
+    match std::fs::write(path, ibdt_file) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(GreathelmError::io("Failed to write IBDT.", e)),
+    }
+}
+
+/**
+ * Reads the IBDT at `path` from disk into a HashMap of source files to the headers they depend on (and the
+ * hashes those headers had when the source was compiled).
+ */
+pub fn read_ibdt(path: &Path) -> Result<HashMap<String, Vec<(String, String)>>, GreathelmError> {
+    let mut table: HashMap<String, Vec<(String, String)>> = HashMap::new();
+    let ibdt_path = path;
+    if !ibdt_path.exists() {
+        return Ok(table);
+    }
+
+    let ibdt_file = match std::fs::read_to_string(ibdt_path) {
+        Ok(ibdt) => ibdt,
+        Err(e) => {
+            return Err(GreathelmError::io("Failed to read IBDT.", e));
+        }
+    };
+    for ent in ibdt_file.split("\n") {
+        if !ent.contains("=") {
+            continue;
+        }
+        let (source, headers) = ent.split_once("=").unwrap();
+        let mut deps = Vec::new();
+        for h in headers.split(",") {
+            if let Some((header, hash)) = h.split_once("|") {
+                deps.push((header.into(), hash.into()));
+            }
+        }
+        table.insert(source.into(), deps);
+    }
+
+    return Ok(table);
+}
+
+/**
+ * Checks the headers recorded for a source file of the project in `root` against their current
+ * contents. Returns the first header that changed (or disappeared) since it was recorded.
+ */
+pub fn find_changed_header(root: &Path, deps: &[(String, String)]) -> Option<String> {
+    for (header, hash) in deps {
+        match hash_path(&root.join(header)) {
+            Some(current) => {
+                if &current != hash {
+                    return Some(header.clone());
+                }
+            }
+            None => {
+                return Some(header.clone());
+            }
+        }
+    }
+    None
+}
+
+/**
+ * Parses a compiler-emitted (-MMD) Makefile-style depfile and returns every prerequisite listed
+ * in it except `source` itself.
+ */
+pub fn parse_depfile(path: &Path, source: &str) -> Vec<String> {
+    return match std::fs::read_to_string(path) {
+        Ok(c) => parse_depfile_text(&c, source),
+        Err(_) => vec![],
+    };
+}
+
+/**
+ * Same as parse_depfile but for the contents of a depfile.
+ */
+pub fn parse_depfile_text(contents: &str, source: &str) -> Vec<String> {
+    let contents = contents.replace("\\\r\n", " ").replace("\\\n", " ");
+
+    let mut deps = Vec::new();
+    for rule in contents.split("\n") {
+        // the target may itself contain a colon (on some platforms) so split at ": "
+        let prereqs = match rule.split_once(": ") {
+            Some((_, p)) => p,
+            None => continue,
+        };
+        // escaped spaces are part of the file name
+        let prereqs = prereqs.replace("\\ ", "\u{0}");
+        for p in prereqs.split_whitespace() {
+            let p = p.replace("\u{0}", " ");
+            if p == source || deps.contains(&p) {
+                continue;
+            }
+            deps.push(p);
+        }
+    }
+
+    return deps;
+}
+
+/**
+ * Records the headers of every source in `rebuilt` (source to hash, as used to name its object)
+ * in `ibdt` from the depfile the compiler left next to its object in `build_dir` of the project
+ * in `root`. The depfiles are removed afterwards. Sources without a depfile (a custom compiler
+ * script might not emit them) lose their entry so they are rebuilt next time.
+ */
+pub fn collect_depfiles(
+    root: &Path,
+    build_dir: &str,
+    rebuilt: &HashMap<PathBuf, String>,
+    ibdt: &mut HashMap<String, Vec<(String, String)>>,
+) {
+    for (f, hash) in rebuilt {
+        let source = f.display().to_string();
+        let depfile = root.join(format!(
+            "{build_dir}/{}-{}.d",
+            str::replace(source.as_str(), "/", "_"),
+            hash
+        ));
+        if !depfile.exists() {
+            ibdt.remove(&source);
+            continue;
+        }
+        let mut deps = Vec::new();
+        for header in parse_depfile(&depfile, &source) {
+            if let Some(h) = hash_path(&root.join(&header)) {
+                deps.push((header, h));
+            }
+        }
+        ibdt.insert(source, deps);
+        std::fs::remove_file(&depfile).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_continued_depfiles() {
+        let depfile = "build/main.o: src/main.c src/a.h \\\n  include/b.h \\\r\n  src/a.h\n";
+        assert_eq!(
+            parse_depfile_text(depfile, "src/main.c"),
+            ["src/a.h", "include/b.h"]
+        );
+    }
+
+    #[test]
+    fn keeps_escaped_spaces() {
+        let depfile = "main.o: main.c my\\ headers/a.h b.h\n";
+        assert_eq!(
+            parse_depfile_text(depfile, "main.c"),
+            ["my headers/a.h", "b.h"]
+        );
+    }
+
+    #[test]
+    fn reads_every_rule() {
+        // -MP adds an empty rule for every header
+        let depfile = "C:/build/main.o: main.c a.h\na.h:\nb.o: b.h\n";
+        assert_eq!(parse_depfile_text(depfile, "main.c"), ["a.h", "b.h"]);
+        assert!(parse_depfile_text("", "main.c").is_empty());
+        assert!(parse_depfile_text("main.o: main.c\n", "main.c").is_empty());
+    }
 }
@@ -0,0 +1,425 @@
+use std::fmt::Display;
+
+/**
+ * A minimal JSON value. Used for compile_commands.json and the out-of-process plugin protocol.
+ * Objects keep their keys in insertion order.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /**
+     * Parses a complete JSON document. Trailing data other than whitespace is an error.
+     */
+    pub fn parse(text: &str) -> Result<JsonValue, String> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            pos: 0,
+        };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.pos != parser.chars.len() {
+            return Err(format!("Unexpected data at offset {}.", parser.pos));
+        }
+        return Ok(value);
+    }
+
+    /**
+     * Builds an object from key/value pairs.
+     */
+    pub fn object(pairs: Vec<(&str, JsonValue)>) -> JsonValue {
+        JsonValue::Object(pairs.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+
+    /**
+     * Builds a string value.
+     */
+    pub fn string<S: Into<String>>(s: S) -> JsonValue {
+        JsonValue::String(s.into())
+    }
+
+    /**
+     * Builds an array of strings.
+     */
+    pub fn string_array(items: &[String]) -> JsonValue {
+        JsonValue::Array(items.iter().map(|s| JsonValue::String(s.clone())).collect())
+    }
+
+    /**
+     * Gets the value under `key` if this is an object containing it.
+     */
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(pairs) => pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<JsonValue>> {
+        match self {
+            JsonValue::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    /**
+     * Gets the strings of an array value, skipping anything that isn't a string.
+     */
+    pub fn as_string_vec(&self) -> Vec<String> {
+        match self {
+            JsonValue::Array(a) => a
+                .iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/**
+ * Writes the value as compact single-line JSON.
+ */
+impl Display for JsonValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Bool(b) => write!(f, "{b}"),
+            JsonValue::Number(n) => {
+                if n.is_finite() {
+                    write!(f, "{n}")
+                } else {
+                    write!(f, "null")
+                }
+            }
+            JsonValue::String(s) => write!(f, "{}", quote(s)),
+            JsonValue::Array(a) => {
+                write!(f, "[")?;
+                for (i, v) in a.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{v}")?;
+                }
+                write!(f, "]")
+            }
+            JsonValue::Object(pairs) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in pairs.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{v}", quote(k))?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/**
+ * Quotes and escapes `text` as a JSON string.
+ */
+pub fn quote(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    return out;
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            return Ok(());
+        }
+        return Err(format!("Expected '{c}' at offset {}.", self.pos));
+    }
+
+    fn expect_word(&mut self, word: &str, value: JsonValue) -> Result<JsonValue, String> {
+        for c in word.chars() {
+            self.expect(c)?;
+        }
+        return Ok(value);
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('n') => self.expect_word("null", JsonValue::Null),
+            Some('t') => self.expect_word("true", JsonValue::Bool(true)),
+            Some('f') => self.expect_word("false", JsonValue::Bool(false)),
+            Some('"') => Ok(JsonValue::String(self.parse_string()?)),
+            Some('[') => self.parse_array(),
+            Some('{') => self.parse_object(),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some(c) => Err(format!("Unexpected '{c}' at offset {}.", self.pos)),
+            None => Err("Unexpected end of input.".into()),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() || "+-.eE".contains(c) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        return match text.parse::<f64>() {
+            Ok(n) => Ok(JsonValue::Number(n)),
+            Err(_) => Err(format!("Invalid number '{text}' at offset {start}.")),
+        };
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        if self.pos + 4 > self.chars.len() {
+            return Err("Unexpected end of input.".into());
+        }
+        let text: String = self.chars[self.pos..self.pos + 4].iter().collect();
+        self.pos += 4;
+        return u32::from_str_radix(&text, 16)
+            .map_err(|_| format!("Invalid unicode escape '{text}'."));
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err("Unterminated string.".into()),
+            };
+            self.pos += 1;
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let e = match self.peek() {
+                        Some(e) => e,
+                        None => return Err("Unterminated string.".into()),
+                    };
+                    self.pos += 1;
+                    match e {
+                        '"' => out.push('"'),
+                        '\\' => out.push('\\'),
+                        '/' => out.push('/'),
+                        'b' => out.push('\u{8}'),
+                        'f' => out.push('\u{c}'),
+                        'n' => out.push('\n'),
+                        'r' => out.push('\r'),
+                        't' => out.push('\t'),
+                        'u' => {
+                            let mut code = self.parse_hex4()?;
+                            // surrogate pair, a high surrogate not followed by a low one is
+                            // replaced on its own
+                            if (0xD800..0xDC00).contains(&code)
+                                && self.peek() == Some('\\')
+                                && self.chars.get(self.pos + 1) == Some(&'u')
+                            {
+                                let start = self.pos;
+                                self.pos += 2;
+                                let low = self.parse_hex4()?;
+                                if (0xDC00..0xE000).contains(&low) {
+                                    code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                                } else {
+                                    self.pos = start;
+                                }
+                            }
+                            out.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                        }
+                        e => return Err(format!("Invalid escape '\\{e}'.")),
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(JsonValue::Array(items));
+        }
+        loop {
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                _ => return Err(format!("Expected ',' or ']' at offset {}.", self.pos)),
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, String> {
+        self.expect('{')?;
+        let mut pairs = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(pairs));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.parse_value()?;
+            pairs.push((key, value));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(pairs));
+                }
+                _ => return Err(format!("Expected ',' or '}}' at offset {}.", self.pos)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_values() {
+        let value = JsonValue::parse(
+            r#" {"a": [1, -2.5, 3e2, true, false, null], "b": {"c": "d"}, "e": [], "f": {}} "#,
+        )
+        .unwrap();
+        assert_eq!(
+            value.get("a").unwrap().as_array().unwrap(),
+            &vec![
+                JsonValue::Number(1.0),
+                JsonValue::Number(-2.5),
+                JsonValue::Number(300.0),
+                JsonValue::Bool(true),
+                JsonValue::Bool(false),
+                JsonValue::Null,
+            ]
+        );
+        assert_eq!(
+            value.get("b").and_then(|b| b.get("c")),
+            Some(&JsonValue::string("d"))
+        );
+        assert_eq!(value.get("e"), Some(&JsonValue::Array(vec![])));
+        assert_eq!(value.get("f"), Some(&JsonValue::Object(vec![])));
+        assert_eq!(value.get("missing"), None);
+    }
+
+    #[test]
+    fn parses_escapes() {
+        let value = JsonValue::parse(r#""q\"b\\s\/n\nr\rt\tb\bf\fu\u00e9""#).unwrap();
+        assert_eq!(value.as_str(), Some("q\"b\\s/n\nr\rt\tb\u{8}f\u{c}u\u{e9}"));
+        // surrogate pairs and lone surrogates
+        let value = JsonValue::parse(r#""\ud83d\ude00""#).unwrap();
+        assert_eq!(value.as_str(), Some("\u{1F600}"));
+        let value = JsonValue::parse(r#""\ud83dx\ude00\ud83d\u0041""#).unwrap();
+        assert_eq!(value.as_str(), Some("\u{FFFD}x\u{FFFD}\u{FFFD}A"));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for text in [
+            "",
+            "nul",
+            "[1,",
+            "[1 2]",
+            "{\"a\" 1}",
+            "{\"a\":1,}",
+            "{a:1}",
+            "\"open",
+            "\"\\x\"",
+            "\"\\u12\"",
+            "\"\\uzzzz\"",
+            "1.2.3",
+            "-",
+            "{} {}",
+            "'a'",
+        ] {
+            assert!(JsonValue::parse(text).is_err(), "{text} parsed");
+        }
+    }
+
+    #[test]
+    fn writes_what_it_parses() {
+        let value = JsonValue::object(vec![
+            ("text", JsonValue::string("a \"quote\"\n\t\\ \u{1} \u{e9}")),
+            ("list", JsonValue::string_array(&["x".into(), "y".into()])),
+            ("n", JsonValue::Number(1.5)),
+            ("nan", JsonValue::Number(f64::NAN)),
+            ("none", JsonValue::Null),
+        ]);
+        let text = value.to_string();
+        assert_eq!(
+            text,
+            r#"{"text":"a \"quote\"\n\t\\ \u0001 é","list":["x","y"],"n":1.5,"nan":null,"none":null}"#
+        );
+        let parsed = JsonValue::parse(&text).unwrap();
+        assert_eq!(parsed.get("text"), value.get("text"));
+        assert_eq!(parsed.get("list").unwrap().as_string_vec(), ["x", "y"]);
+        assert_eq!(parsed.get("nan"), Some(&JsonValue::Null));
+    }
+}
@@ -1,16 +1,23 @@
 pub mod action;
 pub mod builder;
 pub mod config;
+pub mod error;
 pub mod generator;
 pub mod ibht;
 pub mod identify;
+pub mod json;
+pub mod lockfile;
 pub mod manifest;
 pub mod module;
 pub mod plugin;
+pub mod probe;
+pub mod profile;
+pub mod project;
 pub mod script;
 pub mod state;
 pub mod store;
 pub mod subprocess;
+pub mod target;
 pub mod template;
 pub mod term;
 mod util;
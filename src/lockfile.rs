@@ -0,0 +1,345 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{error::GreathelmError, manifest::ProjectManifest, term::*, version::Version};
+
+/**
+ * A single locked store dependency.
+ */
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LockEntry {
+    /**
+     * The exact version that was resolved.
+     */
+    pub version: Version,
+    /**
+     * MD5 hash of the package's contents in the store at the time it was locked.
+     */
+    pub hash: String,
+}
+
+/**
+ * Struct for Project.lock.ghm. The lockfile records the exact version and content hash of every
+ * store dependency (including transitive ones) resolved for a project so later builds resolve
+ * the same packages.
+ */
+#[derive(Clone)]
+pub struct Lockfile {
+    /**
+     * Map of the text form of a NamespacedIdentifier to its locked entry.
+     */
+    pub entries: BTreeMap<String, LockEntry>,
+    /**
+     * Whether the lockfile was modified since it was read.
+     */
+    pub changed: bool,
+    /**
+     * Where the lockfile is saved.
+     */
+    pub path: PathBuf,
+}
+
+impl Lockfile {
+    /**
+     * Creates an empty lockfile saved to `path`.
+     */
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            changed: false,
+            path,
+        }
+    }
+
+    /**
+     * Reads the lockfile of the project `project` is the manifest of (see `get_lockfile_path`).
+     * Returns an empty lockfile if it does not exist.
+     */
+    pub fn read(project: &ProjectManifest) -> Result<Self, GreathelmError> {
+        let path = get_lockfile_path(project);
+        if !path.exists() {
+            return Ok(Self::new(path));
+        }
+        let mut lockfile = Self::new(path.clone());
+
+        let mut manifest = ProjectManifest::new();
+        manifest.read_and_append(&path)?;
+        for l in manifest.directives.get("Locked").unwrap_or(&vec![]) {
+            // @Locked <identifier>@<version> <hash>
+            let (notation, hash) = match l.split_once(" ") {
+                Some(v) => v,
+                None => {
+                    warning!("Ignoring malformed lockfile entry \x1bc{l}\x1br");
+                    continue;
+                }
+            };
+            let (id, version) = match notation.rsplit_once("@") {
+                Some(v) => v,
+                None => {
+                    warning!("Ignoring malformed lockfile entry \x1bc{l}\x1br");
+                    continue;
+                }
+            };
+            lockfile.entries.insert(
+                id.into(),
+                LockEntry {
+                    version: Version::parse(version.into()),
+                    hash: hash.trim().into(),
+                },
+            );
+        }
+
+        return Ok(lockfile);
+    }
+
+    /**
+     * Writes this lockfile to its path if it was changed.
+     */
+    pub fn save(&mut self) -> Result<(), GreathelmError> {
+        if !self.changed {
+            return Ok(());
+        }
+
+        let mut contents: String = "# Greathelm Lockfile\n".into();
+        contents.push_str("# This file is generated. Use `greathelm update` to change it.\n");
+        for (id, entry) in &self.entries {
+            contents.push_str(format!("@Locked {id}@{} {}\n", entry.version, entry.hash).as_str());
+        }
+
+        match std::fs::write(&self.path, contents) {
+            Ok(_) => {
+                self.changed = false;
+                Ok(())
+            }
+            Err(e) => Err(GreathelmError::io("Failed to write lockfile.", e)),
+        }
+    }
+
+    /**
+     * Drops every entry `keep` returns false for.
+     */
+    pub fn retain<F: FnMut(&String) -> bool>(&mut self, mut keep: F) {
+        let count = self.entries.len();
+        self.entries.retain(|id, _| keep(id));
+        if self.entries.len() != count {
+            self.changed = true;
+        }
+    }
+
+    /**
+     * Records `version` and `hash` as the locked entry for `id`.
+     */
+    pub fn lock(&mut self, id: String, version: Version, hash: String) {
+        let entry = LockEntry { version, hash };
+        if self.entries.get(&id) != Some(&entry) {
+            self.entries.insert(id, entry);
+            self.changed = true;
+        }
+    }
+}
+
+/**
+ * Files and directories within a package that are produced by building it and are not part of
+ * its content hash. The tables of other profiles and targets are in their directory in `build`.
+ */
+const HASH_IGNORE: [&str; 6] = [
+    "build",
+    "export",
+    "IBHT.ghd",
+    "IBDT.ghd",
+    "MIBHT.ghd",
+    "compile_commands.json",
+];
+
+/**
+ * Hashes the contents of the package at `path` (ignoring build artifacts).
+ */
+pub fn hash_package(path: &Path) -> String {
+    let mut files: Vec<PathBuf> = Vec::new();
+    collect_files(path, &mut files);
+    files.sort();
+
+    let mut ctx = md5::Context::new();
+    for f in files {
+        let relative = f.strip_prefix(path).unwrap_or(&f);
+        ctx.consume(relative.display().to_string().as_bytes());
+        match std::fs::read(&f) {
+            Ok(contents) => ctx.consume(contents),
+            Err(_) => {
+                warning!("Failed to read \x1bc{}\x1br while hashing.", f.display());
+            }
+        }
+    }
+
+    return format!("{:x}", ctx.compute());
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let rd = match std::fs::read_dir(dir) {
+        Ok(rd) => rd,
+        Err(_) => {
+            return;
+        }
+    };
+    for ent in rd {
+        let ent = match ent {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        let name = ent.file_name().to_string_lossy().to_string();
+        if HASH_IGNORE.contains(&name.as_str()) {
+            continue;
+        }
+        if ent.path().is_dir() {
+            collect_files(&ent.path(), files);
+        } else {
+            files.push(ent.path());
+        }
+    }
+}
+
+/**
+ * Gets the version a resolved package path (`.../@<version>`) points to.
+ */
+pub fn version_of_path(path: &Path) -> Option<Version> {
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let version = name.strip_prefix("@")?;
+    Some(Version::parse(version.into()))
+}
+
+/**
+ * Gets the path of the lockfile of the project of `manifest`. This is its Project.lock.ghm unless
+ * it is a dependency being built for another project, in which case the parent's lockfile is used
+ * so the whole graph is built against the same versions. The parent passes it as the `lockfile`
+ * property (see `project::build_dependencies`).
+ */
+pub fn get_lockfile_path(manifest: &ProjectManifest) -> PathBuf {
+    match manifest.properties.get("lockfile") {
+        Some(p) => PathBuf::from(p),
+        None => manifest.project_path("Project.lock.ghm"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+     * An empty directory for the test `name`.
+     */
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("greathelm-lockfile-{}-{name}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        return dir;
+    }
+
+    fn project_with_lockfile(path: &Path) -> ProjectManifest {
+        let mut manifest = ProjectManifest::new();
+        manifest
+            .properties
+            .insert("lockfile".into(), path.display().to_string());
+        return manifest;
+    }
+
+    #[test]
+    fn saves_and_reads_entries() {
+        let dir = scratch("roundtrip");
+        let path = dir.join("Project.lock.ghm");
+        let mut lock = Lockfile::new(path.clone());
+        lock.lock("t:A".into(), Version::new(1, 2, 0), "aaaa".into());
+        lock.lock(
+            "t:B".into(),
+            Version::parse("0.1.0-beta".into()),
+            "bbbb".into(),
+        );
+        lock.save().unwrap();
+        assert!(!lock.changed);
+
+        let read = Lockfile::read(&project_with_lockfile(&path)).unwrap();
+        assert_eq!(read.entries, lock.entries);
+        assert_eq!(read.path, path);
+        assert!(!read.changed);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn only_saves_changes() {
+        let dir = scratch("changes");
+        let path = dir.join("Project.lock.ghm");
+        let mut lock = Lockfile::new(path.clone());
+        lock.save().unwrap();
+        assert!(!path.exists());
+
+        lock.lock("t:A".into(), Version::new(1, 0, 0), "aaaa".into());
+        assert!(lock.changed);
+        lock.save().unwrap();
+        // locking the same entry again or keeping everything is no change
+        lock.lock("t:A".into(), Version::new(1, 0, 0), "aaaa".into());
+        lock.retain(|_| true);
+        assert!(!lock.changed);
+        lock.retain(|id| id != "t:A");
+        assert!(lock.changed);
+        assert!(lock.entries.is_empty());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn skips_malformed_entries() {
+        let dir = scratch("malformed");
+        let path = dir.join("Project.lock.ghm");
+        std::fs::write(
+            &path,
+            "@Locked t:A@1.0.0 aaaa\n@Locked t:B@1.0.0\n@Locked t:C bbbb\n",
+        )
+        .unwrap();
+        let read = Lockfile::read(&project_with_lockfile(&path)).unwrap();
+        assert_eq!(read.entries.keys().collect::<Vec<_>>(), ["t:A"]);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn hashes_package_contents_only() {
+        let dir = scratch("hash");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("Project.ghm"), "Project-Name=A\n").unwrap();
+        std::fs::write(dir.join("src/a.c"), "int a;\n").unwrap();
+        let hash = hash_package(&dir);
+
+        // build artifacts don't count
+        std::fs::create_dir_all(dir.join("build")).unwrap();
+        std::fs::write(dir.join("build/a.o"), "object").unwrap();
+        std::fs::write(dir.join("IBHT.ghd"), "table").unwrap();
+        assert_eq!(hash_package(&dir), hash);
+
+        std::fs::write(dir.join("src/a.c"), "int b;\n").unwrap();
+        assert_ne!(hash_package(&dir), hash);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn finds_versions_and_paths() {
+        assert_eq!(
+            version_of_path(Path::new("/store/t/A/@1.2.3")),
+            Some(Version::new(1, 2, 3))
+        );
+        assert_eq!(version_of_path(Path::new("/store/t/A")), None);
+
+        let mut manifest = project_with_lockfile(Path::new("/parent/Project.lock.ghm"));
+        assert_eq!(
+            get_lockfile_path(&manifest),
+            PathBuf::from("/parent/Project.lock.ghm")
+        );
+        manifest.properties.remove("lockfile");
+        manifest
+            .properties
+            .insert("project-dir".into(), "/project".into());
+        assert_eq!(
+            get_lockfile_path(&manifest),
+            PathBuf::from("/project/Project.lock.ghm")
+        );
+    }
+}
@@ -1,5 +1,3 @@
-use std::{path::PathBuf, str::FromStr};
-
 use crate::term::*;
 use action::Action;
 use builder::ProjectBuilder;
@@ -11,16 +9,23 @@ use state::GreathelmState;
 mod action;
 mod builder;
 mod config;
+mod error;
 mod generator;
 mod ibht;
 mod identify;
+mod json;
+mod lockfile;
 mod manifest;
 mod module;
 mod plugin;
+mod probe;
+mod profile;
+mod project;
 mod script;
 mod state;
 mod store;
 mod subprocess;
+mod target;
 mod template;
 mod term;
 mod util;
@@ -36,50 +41,25 @@ fn main() {
         }
     };
 
-    config::ensure_config_dirs();
+    if let Err(e) = config::ensure_config_dirs() {
+        e.report();
+        std::process::exit(1);
+    }
 
     let mut builders: Vec<Box<dyn ProjectBuilder>> = Vec::new();
     let mut generators: Vec<Box<dyn ProjectGenerator>> = Vec::new();
-    let mut manifest = manifest::ProjectManifest::new();
     let mut actions: Vec<Box<dyn Action>> = Vec::new();
 
-    // user manifest
-    let path = PathBuf::from_str(
-        format!(
-            "{}/UserManifest.ghm",
-            config::get_config_base_dir().to_str().unwrap()
-        )
-        .as_str(),
-    )
-    .unwrap();
-    if path.exists() {
-        manifest.read_and_append(&path);
-    }
-    let path = PathBuf::from_str("Project.ghm").unwrap();
-    if path.exists() {
-        manifest.read_and_append(&path);
-    }
-    let path = PathBuf::from_str("Project.local.ghm").unwrap();
-    if path.exists() {
-        manifest.read_and_append(&path);
-    }
-
-    manifest.append_from_cli_args(std::env::args().collect());
-
-    let aliases = manifest.get_aliases_map();
-
-    let mut pt = match manifest.properties.get("Project-Type") {
-        Some(t) => t.clone(),
-        None => "%".into(),
-    };
-    if pt != "" {
-        for a in aliases.keys() {
-            if &pt == a {
-                pt = aliases.get(a).unwrap().clone();
-            }
+    // a broken manifest only stops the actions that need it (see Action::requires_manifest),
+    // which isn't known until plugins are loaded and the action is resolved
+    let mut manifest_error: Option<error::GreathelmError> = None;
+    let manifest = match manifest::ProjectManifest::load(args.clone()) {
+        Ok(m) => m,
+        Err(e) => {
+            manifest_error = Some(e);
+            manifest::ProjectManifest::from_cli_args(args.clone())
         }
-    }
-    manifest.properties.insert("Project-Type".into(), pt);
+    };
 
     // builtins
     builders.push(Box::new(builder::c::CBuilder::create()));
@@ -105,16 +85,47 @@ fn main() {
     actions.push(Box::new(
         action::pluginuninstall::PluginUninstallAction::create(),
     ));
+    actions.push(Box::new(action::pluginmsg::PluginMessageAction::create()));
     actions.push(Box::new(action::about::AboutAction::create()));
+    actions.push(Box::new(action::doctor::DoctorAction::create()));
     actions.push(Box::new(action::resolve::ResolveAction::create()));
     actions.push(Box::new(action::ls::LSAction::create()));
     actions.push(Box::new(action::clean::CleanAction::create()));
-
+    actions.push(Box::new(action::compdb::CompileCommandsAction::create()));
+    actions.push(Box::new(action::update::UpdateAction::create()));
+    actions.push(Box::new(action::get::GetAction::create()));
+    actions.push(Box::new(action::set::SetAction::create()));
+    actions.push(Box::new(action::unset::UnsetAction::create()));
+    actions.push(Box::new(action::adddirective::AddDirectiveAction::create()));
+    actions.push(Box::new(
+        action::removedirective::RemoveDirectiveAction::create(),
+    ));
+    actions.push(Box::new(action::adddep::AddDependencyAction::create()));
+    actions.push(Box::new(action::removedep::RemoveDependencyAction::create()));
+
+    let requirements = match plugin::require::required_plugins(&manifest) {
+        Ok(r) => r,
+        Err(e) => {
+            manifest_error.get_or_insert(e);
+            vec![]
+        }
+    };
+    let only_required: Option<Vec<NamespacedIdentifier>> =
+        if manifest.get_bool_property("only-required-plugins", false) {
+            Some(requirements.iter().map(|r| r.identifier.clone()).collect())
+        } else {
+            None
+        };
+
+    plugin::stdio::set_call_timeout(manifest.get_u64_property("plugin-timeout", 600));
     // load plugins here..
-    let plugins = plugin::load_plugins();
+    let (plugins, incompatible_plugins, failed_plugins) =
+        plugin::load_plugins(only_required.as_deref());
     let mut infos: Vec<PluginInfo> = Vec::new();
     for plugin in plugins {
-        infos.push(plugin.as_info());
+        let info = plugin.as_info();
+        plugin::bus::register(&info);
+        infos.push(info);
 
         for b in plugin.builders {
             builders.push(b);
@@ -125,6 +136,9 @@ fn main() {
         for a in plugin.actions {
             actions.push(a);
         }
+        for h in plugin.hooks {
+            plugin::hooks::register(h);
+        }
     }
 
     let state = GreathelmState {
@@ -133,6 +147,8 @@ fn main() {
         manifest,
         actions,
         plugins: infos,
+        incompatible_plugins,
+        failed_plugins,
         cli_args: std::env::args().collect(),
     };
 
@@ -170,9 +186,29 @@ fn main() {
 
     match use_action {
         Some(a) => {
-            a.execute(&state);
+            if let Some(e) = &manifest_error {
+                if a.requires_manifest() {
+                    e.report();
+                    std::process::exit(1);
+                }
+                e.report_as_warning();
+                warning!("Continuing without the project manifest.");
+            }
+            if a.checks_plugin_requirements() {
+                if let Err(e) = plugin::require::check_requirements(&requirements, &state.plugins) {
+                    e.report();
+                    std::process::exit(1);
+                }
+            }
+            if let Err(e) = a.execute(&state) {
+                e.report();
+                std::process::exit(1);
+            }
         }
         None => {
+            if let Some(e) = &manifest_error {
+                e.report();
+            }
             error!("Action \x1bc{action}\x1br could not be resolved.");
             error!("Are you missing a plugin?");
         }
@@ -0,0 +1,437 @@
+use std::path::{Path, PathBuf};
+
+use crate::error::GreathelmError;
+
+/**
+ * An editable .ghm file. Unlike ProjectManifest, which is the merged result of reading manifests,
+ * a ManifestDocument keeps the file as written: comments, blank lines, @If blocks and the order
+ * of everything are preserved and only the lines that are edited change. Writing an unedited
+ * document produces exactly the text it was parsed from.
+ *
+ * Edits only look at what the file itself says, @Imported files are left alone.
+ */
+#[derive(Clone)]
+pub struct ManifestDocument {
+    lines: Vec<String>,
+}
+
+/**
+ * What a line of a manifest is, as far as editing is concerned.
+ */
+enum LineKind {
+    /**
+     * A `Key=value` or `Key+=value` line.
+     */
+    Property {
+        key: String,
+        value: String,
+        append: bool,
+    },
+    /**
+     * A `@Name value` line.
+     */
+    Directive { name: String, value: String },
+    /**
+     * Comments, blank lines, @If/@ElseIf/@Else/@EndIf and anything else.
+     */
+    Other,
+}
+
+/**
+ * A classified line with the @If nesting depth it is at.
+ */
+struct Line {
+    index: usize,
+    depth: usize,
+    kind: LineKind,
+}
+
+impl ManifestDocument {
+    /**
+     * Parses manifest text. Never fails, lines that don't mean anything are kept as they are.
+     */
+    pub fn parse(text: &str) -> Self {
+        return Self {
+            lines: text.split("\n").map(|l| l.to_string()).collect(),
+        };
+    }
+
+    /**
+     * Reads the manifest at `path`.
+     */
+    pub fn read(path: &Path) -> Result<Self, GreathelmError> {
+        return match std::fs::read_to_string(path) {
+            Ok(text) => Ok(Self::parse(&text)),
+            Err(e) => Err(GreathelmError::io(
+                format!("Failed to read \x1bc{}\x1br.", path.display()),
+                e,
+            )),
+        };
+    }
+
+    /**
+     * Reads the manifest of the project in the current directory that edits go to: Project.ghm,
+     * or Project.local.ghm if `local` (which doesn't have to exist yet).
+     */
+    pub fn read_project(local: bool) -> Result<(PathBuf, Self), GreathelmError> {
+        let path = PathBuf::from(if local {
+            "Project.local.ghm"
+        } else {
+            "Project.ghm"
+        });
+        if path.exists() {
+            let document = Self::read(&path)?;
+            return Ok((path, document));
+        }
+        if local {
+            return Ok((path, Self::parse("")));
+        }
+        return Err(GreathelmError::ManifestError(
+            "There is no \x1bcProject.ghm\x1br in the current directory.".into(),
+        ));
+    }
+
+    /**
+     * Writes this document to `path`.
+     */
+    pub fn write(&self, path: &Path) -> Result<(), GreathelmError> {
+        if let Err(e) = std::fs::write(path, self.to_ghm_string()) {
+            return Err(GreathelmError::io(
+                format!("Failed to write \x1bc{}\x1br.", path.display()),
+                e,
+            ));
+        }
+        return Ok(());
+    }
+
+    /**
+     * The text of this document.
+     */
+    pub fn to_ghm_string(&self) -> String {
+        return self.lines.join("\n");
+    }
+
+    /**
+     * Gets the value this file sets `key` to outside of @If blocks. `Key+=` appends in the file
+     * are not applied and `${...}` references are not resolved.
+     */
+    pub fn get_property(&self, key: &str) -> Option<String> {
+        return self
+            .classify()
+            .into_iter()
+            .rev()
+            .find_map(|l| match l.kind {
+                LineKind::Property {
+                    key: k,
+                    value,
+                    append: false,
+                } if l.depth == 0 && k == key => Some(value),
+                _ => None,
+            });
+    }
+
+    /**
+     * Sets `key` to `value`. The last assignment outside of @If blocks is changed in place, if
+     * there is none a new line is added after the last property. `Key+=` lines outside of @If
+     * blocks that would append to the new value are removed.
+     */
+    pub fn set_property(&mut self, key: &str, value: &str) {
+        let lines = self.classify();
+        let line = format!("{key}={value}");
+        let existing = lines.iter().rev().find(|l| {
+            l.depth == 0
+                && matches!(&l.kind, LineKind::Property { key: k, append: false, .. } if k == key)
+        });
+        let replaced = existing.is_some();
+        let at = match existing {
+            Some(l) => {
+                self.lines[l.index] = line;
+                l.index
+            }
+            None => {
+                let after = lines
+                    .iter()
+                    .filter(|l| l.depth == 0 && matches!(l.kind, LineKind::Property { .. }))
+                    .map(|l| l.index)
+                    .next_back();
+                let at = match after {
+                    Some(i) => i + 1,
+                    // below the comments at the top of the file
+                    None => self
+                        .lines
+                        .iter()
+                        .position(|l| !l.starts_with("#"))
+                        .unwrap_or(self.lines.len()),
+                };
+                self.lines.insert(at, line);
+                at
+            }
+        };
+
+        let appends: Vec<usize> = self
+            .classify()
+            .into_iter()
+            .filter(|l| {
+                (l.index > at || !replaced)
+                    && l.depth == 0
+                    && matches!(&l.kind, LineKind::Property { key: k, append: true, .. } if k == key)
+            })
+            .map(|l| l.index)
+            .collect();
+        for i in appends.iter().rev() {
+            self.lines.remove(*i);
+        }
+    }
+
+    /**
+     * Removes every assignment of `key` (including appends and ones inside @If blocks). Returns
+     * whether there was any.
+     */
+    pub fn unset_property(&mut self, key: &str) -> bool {
+        return self
+            .remove_lines(|kind| matches!(kind, LineKind::Property { key: k, .. } if k == key))
+            > 0;
+    }
+
+    /**
+     * Gets the values of the `@name` directives outside of @If blocks, in order.
+     */
+    pub fn get_directives(&self, name: &str) -> Vec<String> {
+        return self
+            .classify()
+            .into_iter()
+            .filter_map(|l| match l.kind {
+                LineKind::Directive { name: n, value } if l.depth == 0 && n == name => Some(value),
+                _ => None,
+            })
+            .collect();
+    }
+
+    /**
+     * Adds a `@name value` directive after the last `@name` outside of @If blocks, or at the end
+     * of the file if there is none.
+     */
+    pub fn add_directive(&mut self, name: &str, value: &str) {
+        let line = format!("@{name} {value}");
+        let after = self
+            .classify()
+            .into_iter()
+            .filter(|l| {
+                l.depth == 0 && matches!(&l.kind, LineKind::Directive { name: n, .. } if n == name)
+            })
+            .map(|l| l.index)
+            .next_back();
+        match after {
+            Some(i) => self.lines.insert(i + 1, line),
+            None => {
+                // keep the trailing newline last
+                let at = match self.lines.last() {
+                    Some(l) if l.is_empty() => self.lines.len() - 1,
+                    _ => self.lines.len(),
+                };
+                self.lines.insert(at, line);
+            }
+        }
+    }
+
+    /**
+     * Removes every `@name` directive (including ones inside @If blocks) whose value `matches`.
+     * Returns how many were removed.
+     */
+    pub fn remove_directives<F: Fn(&str) -> bool>(&mut self, name: &str, matches: F) -> usize {
+        return self.remove_lines(
+            |kind| matches!(kind, LineKind::Directive { name: n, value } if n == name && matches(value)),
+        );
+    }
+
+    /**
+     * Changes every `@name` directive whose value `matches` to `value`, leaving it where it is.
+     * Returns how many were changed.
+     */
+    pub fn replace_directives<F: Fn(&str) -> bool>(
+        &mut self,
+        name: &str,
+        matches: F,
+        value: &str,
+    ) -> usize {
+        let indices: Vec<usize> = self
+            .classify()
+            .into_iter()
+            .filter(|l| {
+                matches!(&l.kind, LineKind::Directive { name: n, value } if n == name && matches(value))
+            })
+            .map(|l| l.index)
+            .collect();
+        for i in &indices {
+            self.lines[*i] = format!("@{name} {value}");
+        }
+        return indices.len();
+    }
+
+    fn remove_lines<F: Fn(&LineKind) -> bool>(&mut self, remove: F) -> usize {
+        let indices: Vec<usize> = self
+            .classify()
+            .into_iter()
+            .filter(|l| remove(&l.kind))
+            .map(|l| l.index)
+            .collect();
+        for i in indices.iter().rev() {
+            self.lines.remove(*i);
+        }
+        return indices.len();
+    }
+
+    /**
+     * Classifies every line the same way ProjectManifest::append_from_str reads them.
+     */
+    fn classify(&self) -> Vec<Line> {
+        let mut depth: usize = 0;
+        let mut lines = Vec::new();
+        for (index, l) in self.lines.iter().enumerate() {
+            let trimmed = l.trim();
+            let mut kind = LineKind::Other;
+            if l.starts_with("#") {
+                // comment
+            } else if trimmed == "@If" || trimmed.starts_with("@If ") {
+                depth += 1;
+            } else if trimmed == "@EndIf" {
+                depth = depth.saturating_sub(1);
+            } else if trimmed == "@Else" || trimmed.starts_with("@ElseIf") {
+                // stays in the same block
+            } else if l.starts_with("@") && l.contains(" ") {
+                let (name, value) = l[1..].split_once(" ").unwrap();
+                kind = LineKind::Directive {
+                    name: name.into(),
+                    value: value.into(),
+                };
+            } else if let Some((key, value)) = l.split_once("=") {
+                kind = match key.strip_suffix("+") {
+                    Some(k) => LineKind::Property {
+                        key: k.into(),
+                        value: value.into(),
+                        append: true,
+                    },
+                    None => LineKind::Property {
+                        key: key.into(),
+                        value: value.into(),
+                        append: false,
+                    },
+                };
+            }
+            // the @If line itself is outside of its block
+            let line_depth = match &kind {
+                LineKind::Other if trimmed == "@If" || trimmed.starts_with("@If ") => depth - 1,
+                _ => depth,
+            };
+            lines.push(Line {
+                index,
+                depth: line_depth,
+                kind,
+            });
+        }
+        return lines;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "# a project\n\
+                        Project-Name=app\n\
+                        Additional-CC-Flags=-Wall\n\
+                        Additional-CC-Flags+=-g\n\
+                        \n\
+                        @If profile release\n\
+                        Additional-CC-Flags+=-O2\n\
+                        @Dependency a:b@1.0\n\
+                        @EndIf\n\
+                        @Dependency c:d@^2\n";
+
+    #[test]
+    fn writes_what_it_parses() {
+        for text in [TEXT, "", "\n\n", "Key=value", "@If x\n@Module m\n@EndIf"] {
+            assert_eq!(ManifestDocument::parse(text).to_ghm_string(), text);
+        }
+    }
+
+    #[test]
+    fn reads_the_file_as_written() {
+        let d = ManifestDocument::parse(TEXT);
+        assert_eq!(d.get_property("Project-Name"), Some("app".into()));
+        // appends are not applied
+        assert_eq!(d.get_property("Additional-CC-Flags"), Some("-Wall".into()));
+        assert_eq!(d.get_property("Missing"), None);
+        // only directives outside of @If blocks
+        assert_eq!(d.get_directives("Dependency"), ["c:d@^2"]);
+    }
+
+    #[test]
+    fn sets_properties() {
+        let mut d = ManifestDocument::parse(TEXT);
+        d.set_property("Project-Name", "tool");
+        d.set_property("Project-Type", "C");
+        let text = d.to_ghm_string();
+        assert!(
+            text.starts_with("# a project\nProject-Name=tool\n"),
+            "{text}"
+        );
+        // added after the last property, not inside the @If block
+        assert!(
+            text.contains("Additional-CC-Flags+=-g\nProject-Type=C\n\n@If"),
+            "{text}"
+        );
+
+        let mut d = ManifestDocument::parse("# comment\n@Module m\n");
+        d.set_property("Key", "value");
+        assert_eq!(d.to_ghm_string(), "# comment\nKey=value\n@Module m\n");
+    }
+
+    #[test]
+    fn drops_appends_to_set_properties() {
+        let mut d = ManifestDocument::parse(TEXT);
+        d.set_property("Additional-CC-Flags", "-O3");
+        let text = d.to_ghm_string();
+        assert!(text.contains("Additional-CC-Flags=-O3\n\n"), "{text}");
+        assert!(!text.contains("-g"), "{text}");
+        // appends inside @If blocks are conditional and stay
+        assert!(text.contains("Additional-CC-Flags+=-O2"), "{text}");
+
+        let mut d = ManifestDocument::parse("Flags+=-g\nOther=1");
+        d.set_property("Flags", "-O2");
+        assert_eq!(d.to_ghm_string(), "Other=1\nFlags=-O2");
+        let mut d = ManifestDocument::parse("Other=1\nFlags+=-g");
+        d.set_property("Flags", "-O2");
+        assert_eq!(d.to_ghm_string(), "Other=1\nFlags=-O2");
+    }
+
+    #[test]
+    fn unsets_properties() {
+        let mut d = ManifestDocument::parse(TEXT);
+        assert!(d.unset_property("Additional-CC-Flags"));
+        assert!(!d.to_ghm_string().contains("Additional-CC-Flags"));
+        assert!(!d.unset_property("Additional-CC-Flags"));
+    }
+
+    #[test]
+    fn edits_directives() {
+        let mut d = ManifestDocument::parse(TEXT);
+        d.add_directive("Dependency", "e:f@1");
+        assert_eq!(d.get_directives("Dependency"), ["c:d@^2", "e:f@1"]);
+        d.add_directive("Module", "lib");
+        // before the trailing newline
+        assert!(d
+            .to_ghm_string()
+            .ends_with("@Dependency e:f@1\n@Module lib\n"));
+
+        assert_eq!(
+            d.replace_directives("Dependency", |v| v.starts_with("a:b"), "a:b@2.0"),
+            1
+        );
+        assert!(d.to_ghm_string().contains("@Dependency a:b@2.0\n@EndIf"));
+        // including the one inside the @If block
+        assert_eq!(d.remove_directives("Dependency", |v| v.contains(":")), 3);
+        assert!(d.get_directives("Dependency").is_empty());
+        assert_eq!(d.get_directives("Module"), ["lib"]);
+    }
+}
@@ -0,0 +1,1131 @@
+#![allow(dead_code)] // don't complain about convenience methods
+                     // (get_<type>_property) that are unused
+                     // they are for plugins/later.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{config, error::GreathelmError, json::JsonValue, module::Module, profile, target};
+
+pub mod document;
+
+/**
+ * Struct for Project Manifests. This is usually a combined manifest of
+ * (CONFIGROOT)/UserManifest.ghm (PROJECTROOT)/Project.ghm and (PROJECTROOT)/Project.local.ghm and
+ * any additional manifests they may @Import.
+ */
+#[derive(Clone)]
+pub struct ProjectManifest {
+    pub properties: HashMap<String, String>,
+    pub directives: HashMap<String, Vec<String>>,
+    /**
+     * Where each property was set, in the order the assignments were read.
+     */
+    pub property_locations: HashMap<String, Vec<SourceLocation>>,
+    /**
+     * Where each directive value was read, in the same order as `directives`.
+     */
+    pub directive_locations: HashMap<String, Vec<SourceLocation>>,
+    /**
+     * Properties referenced by `${Key}` or an @If condition. These are user defined and not
+     * reported as unknown.
+     */
+    pub referenced: HashSet<String>,
+    /**
+     * Warnings found while reading, printed when the manifest is checked before a build.
+     */
+    pub warnings: Vec<String>,
+}
+
+/**
+ * A file and line a property or directive was read from. Values from the command line have line 0.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: usize,
+}
+
+impl SourceLocation {
+    pub fn create(file: &str, line: usize) -> Self {
+        return Self {
+            file: file.into(),
+            line,
+        };
+    }
+
+    /**
+     * The location of values given as command line arguments.
+     */
+    pub fn command_line() -> Self {
+        return Self::create("command line", 0);
+    }
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.line == 0 {
+            return write!(f, "{}", self.file);
+        }
+        return write!(f, "{}:{}", self.file, self.line);
+    }
+}
+
+impl ProjectManifest {
+    /**
+     * Creates an empty ProjectManifest
+     */
+    pub fn new() -> Self {
+        let mut s = Self {
+            properties: HashMap::new(),
+            directives: HashMap::new(),
+            property_locations: HashMap::new(),
+            directive_locations: HashMap::new(),
+            referenced: HashSet::new(),
+            warnings: Vec::new(),
+        };
+
+        s.directives.insert("Dependency".into(), Vec::new());
+        s.directives.insert("Alias".into(), Vec::new());
+        s.directives.insert("Module".into(), Vec::new());
+        s.directives.insert("Directive".into(), Vec::new());
+
+        return s;
+    }
+
+    /**
+     * Loads the combined manifest for the project in the current directory the same way Greathelm
+     * does on startup: (CONFIGROOT)/UserManifest.ghm, Project.ghm and Project.local.ghm followed
+     * by properties and directives from `args`. The active profile and target are applied (see
+     * `profile::apply` and `target::apply`) and an aliased Project-Type is resolved.
+     */
+    pub fn load(args: Vec<String>) -> Result<Self, GreathelmError> {
+        return Self::load_at(Path::new("."), args);
+    }
+
+    /**
+     * Loads the combined manifest for the project in `root` like `load`. `root` is kept in the
+     * `project-dir` property, see `project_dir`.
+     */
+    pub fn load_at(root: &Path, args: Vec<String>) -> Result<Self, GreathelmError> {
+        let mut manifest = Self::new();
+        manifest
+            .properties
+            .insert("project-dir".into(), root.display().to_string());
+        // command line properties are visible to @If conditions in the manifests, they are set
+        // again afterwards so they still override them. Appending ones are only applied then.
+        let plain: Vec<String> = args
+            .iter()
+            .filter(|a| !a.split_once("=").is_some_and(|(k, _)| k.ends_with("+")))
+            .cloned()
+            .collect();
+        manifest.append_cli_properties(&plain);
+
+        // user manifest
+        let path = PathBuf::from_str(
+            format!(
+                "{}/UserManifest.ghm",
+                config::get_config_base_dir().to_str().unwrap()
+            )
+            .as_str(),
+        )
+        .unwrap();
+        if path.exists() {
+            manifest.read_and_append(&path)?;
+        }
+        let path = manifest.project_path("Project.ghm");
+        if path.exists() {
+            manifest.read_and_append(&path)?;
+        }
+        let path = manifest.project_path("Project.local.ghm");
+        if path.exists() {
+            manifest.read_and_append(&path)?;
+        }
+
+        // profile and target settings override the manifests but not the command line
+        profile::declare_from_cli_args(&mut manifest, &args);
+        profile::apply(&mut manifest)?;
+        target::apply(&mut manifest)?;
+        let build_dir = profile::default_build_dir(&manifest);
+        manifest.properties.insert("build-dir".into(), build_dir);
+        // @Profile and @Target arguments were only declarations to fall back on
+        let args: Vec<String> = args
+            .into_iter()
+            .filter(|a| !a.starts_with("@Profile:") && !a.starts_with("@Target:"))
+            .collect();
+        manifest.append_from_cli_args(args);
+        manifest.resolve()?;
+
+        let aliases = manifest.get_aliases_map();
+
+        let mut pt = match manifest.properties.get("Project-Type") {
+            Some(t) => t.clone(),
+            None => "%".into(),
+        };
+        if pt != "" {
+            for a in aliases.keys() {
+                if &pt == a {
+                    pt = aliases.get(a).unwrap().clone();
+                }
+            }
+        }
+        manifest.properties.insert("Project-Type".into(), pt);
+
+        return Ok(manifest);
+    }
+
+    /**
+     * The manifest used when `load` failed: only the properties and directives from `args`.
+     */
+    pub fn from_cli_args(args: Vec<String>) -> Self {
+        let mut manifest = Self::new();
+        manifest.append_from_cli_args(args);
+        return manifest;
+    }
+
+    /**
+     * The directory of the project this manifest belongs to. Greathelm never changes its working
+     * directory, so builds of other projects (modules and dependencies) resolve their files
+     * against this instead. It is `.` for the project Greathelm was started in.
+     */
+    pub fn project_dir(&self) -> PathBuf {
+        return PathBuf::from(self.get_string_property("project-dir", "."));
+    }
+
+    /**
+     * The path of `path`, relative to the project, from the current directory.
+     */
+    pub fn project_path(&self, path: impl AsRef<Path>) -> PathBuf {
+        let dir = self.project_dir();
+        if dir == Path::new(".") {
+            return path.as_ref().to_path_buf();
+        }
+        return dir.join(path);
+    }
+
+    /**
+     * Gets the property `key` as a String. Defaulting to `default` if not present.
+     */
+    pub fn get_string_property(&self, key: &str, default: &str) -> String {
+        match self.properties.get(key) {
+            Some(k) => k.clone(),
+            None => default.into(),
+        }
+    }
+
+    /**
+     * Gets the property `key` as an i32. Defaulting to `default` if not present or not an i32.
+     */
+    pub fn get_i32_property(&self, key: &str, default: i32) -> i32 {
+        match self.get_string_property(key, &format!("{default}")).parse() {
+            Ok(v) => v,
+            Err(_) => default,
+        }
+    }
+
+    /**
+     * Gets the property `key` as a u32. Defaulting to `default` if not present or not a u32.
+     */
+    pub fn get_u32_property(&self, key: &str, default: u32) -> u32 {
+        match self.get_string_property(key, &format!("{default}")).parse() {
+            Ok(v) => v,
+            Err(_) => default,
+        }
+    }
+
+    /**
+     * Gets the property `key` as an i64. Defaulting to `default` if not present or not an i64.
+     */
+    pub fn get_i64_property(&self, key: &str, default: i64) -> i64 {
+        match self.get_string_property(key, &format!("{default}")).parse() {
+            Ok(v) => v,
+            Err(_) => default,
+        }
+    }
+
+    /**
+     * Gets the property `key` as a u64. Defaulting to `default` if not present or not a u64.
+     */
+    pub fn get_u64_property(&self, key: &str, default: u64) -> u64 {
+        match self.get_string_property(key, &format!("{default}")).parse() {
+            Ok(v) => v,
+            Err(_) => default,
+        }
+    }
+
+    /**
+     * Gets the property `key` as a usize. Defaulting to `default` if not present or not a usize.
+     */
+    pub fn get_usize_property(&self, key: &str, default: usize) -> usize {
+        match self.get_string_property(key, &format!("{default}")).parse() {
+            Ok(v) => v,
+            Err(_) => default,
+        }
+    }
+
+    /**
+     * Gets the property `key` as a bool. Defaulting to `default` if not present or not a bool.
+     */
+    pub fn get_bool_property(&self, key: &str, default: bool) -> bool {
+        match self.get_string_property(key, &format!("{default}")).parse() {
+            Ok(v) => v,
+            Err(_) => default,
+        }
+    }
+
+    /**
+     * Reads the manifest at `path` and appends its contents to this manifest.
+     */
+    pub fn read_and_append(&mut self, path: &Path) -> Result<(), GreathelmError> {
+        let raw_file = match std::fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(e) => {
+                return Err(GreathelmError::io(
+                    format!("Failed to read \x1bc{}\x1br.", path.display()),
+                    e,
+                ));
+            }
+        };
+
+        return self.append_from_source(&raw_file, &path.display().to_string());
+    }
+
+    /**
+     * Appends manifest text (the contents of a .ghm file) to this manifest. Same as
+     * `append_from_source` for text that doesn't come from a file.
+     */
+    pub fn append_from_str(&mut self, text: &str) -> Result<(), GreathelmError> {
+        return self.append_from_source(text, "<manifest>");
+    }
+
+    /**
+     * Appends manifest text read from `file` to this manifest, recording the line every property
+     * and directive came from. @Import paths are relative to the project (see `project_dir`). @If
+     * blocks are
+     * evaluated against the manifest as read so far (see `evaluate_condition`).
+     */
+    pub fn append_from_source(&mut self, text: &str, file: &str) -> Result<(), GreathelmError> {
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        // where properties were set outside of @If blocks in this file, to catch overrides
+        let mut assigned: HashMap<String, SourceLocation> = HashMap::new();
+        for (i, l) in text.split("\n").enumerate() {
+            if l.starts_with("#") {
+                continue;
+            }
+            let location = SourceLocation::create(file, i + 1);
+            let located = |e: GreathelmError| located_error(&location, e.message());
+
+            self.referenced.extend(references(l));
+            let trimmed = l.trim();
+            if trimmed == "@If" || trimmed == "@ElseIf" {
+                return Err(located_error(
+                    &location,
+                    format!("\x1bc{trimmed}\x1br needs a condition."),
+                ));
+            }
+            if let Some(condition) = trimmed.strip_prefix("@If ") {
+                self.referenced.extend(condition_key(condition.trim()));
+                let parent_active = conditionals.iter().all(|c| c.active);
+                let active =
+                    parent_active && self.evaluate_condition(condition.trim()).map_err(located)?;
+                conditionals.push(Conditional {
+                    active,
+                    taken: active,
+                    location,
+                });
+                continue;
+            }
+            if let Some(condition) = trimmed.strip_prefix("@ElseIf ") {
+                self.referenced.extend(condition_key(condition.trim()));
+                let parent_active = match conditionals.split_last() {
+                    Some((_, parents)) => parents.iter().all(|c| c.active),
+                    None => {
+                        return Err(located_error(&location, "@ElseIf without a matching @If."));
+                    }
+                };
+                let taken = conditionals.last().unwrap().taken;
+                // conditions of branches that can't be taken aren't evaluated
+                let active = parent_active
+                    && !taken
+                    && self.evaluate_condition(condition.trim()).map_err(located)?;
+                let c = conditionals.last_mut().unwrap();
+                c.active = active;
+                c.taken = taken || active;
+                continue;
+            }
+            if trimmed == "@Else" {
+                let parent_active = match conditionals.split_last() {
+                    Some((_, parents)) => parents.iter().all(|c| c.active),
+                    None => {
+                        return Err(located_error(&location, "@Else without a matching @If."));
+                    }
+                };
+                let c = conditionals.last_mut().unwrap();
+                c.active = parent_active && !c.taken;
+                c.taken = true;
+                continue;
+            }
+            if trimmed == "@EndIf" {
+                if conditionals.pop().is_none() {
+                    return Err(located_error(&location, "@EndIf without a matching @If."));
+                }
+                continue;
+            }
+            if !conditionals.iter().all(|c| c.active) {
+                continue;
+            }
+
+            if l.starts_with("@Import ") {
+                let path = self
+                    .interpolate(l.split_once("@Import ").unwrap().1, "@Import")
+                    .map_err(located)?;
+                let path = self.project_path(path);
+                if path.exists() {
+                    self.read_and_append(&path)?;
+                } else {
+                    self.warnings.push(format!(
+                        "\x1bc{location}\x1br: @Import target \x1bc{}\x1br does not exist.",
+                        path.display()
+                    ));
+                }
+            }
+            if l.starts_with("@") && l.contains(" ") {
+                let directive = l.split_once("@").unwrap().1.split_once(" ").unwrap().0;
+                let mut directivecontent = String::new();
+                for c in l.split(" ").skip(1) {
+                    if !directivecontent.is_empty() {
+                        directivecontent.push_str(" ");
+                    }
+                    directivecontent.push_str(c);
+                }
+                self.push_directive(directive, directivecontent, location.clone());
+            }
+
+            // directive arguments may contain = (`@Profile release Compiler-Opt-Level=3`), they
+            // aren't properties
+            if l.starts_with("@") || !l.contains("=") {
+                continue;
+            }
+            let k = l.split_once("=").unwrap().0;
+            let v = l.split_once("=").unwrap().1;
+
+            self.assign_property(k, v);
+            let key = k.strip_suffix("+").unwrap_or(k);
+            if conditionals.is_empty() && !k.ends_with("+") {
+                if let Some(previous) = assigned.get(key) {
+                    self.warnings.push(format!(
+                        "\x1bc{location}\x1br: \x1bc{key}\x1br overrides the value set on line {}.",
+                        previous.line
+                    ));
+                }
+                assigned.insert(key.into(), location.clone());
+            }
+            self.property_locations
+                .entry(key.into())
+                .or_default()
+                .push(location);
+        }
+
+        if let Some(c) = conditionals.first() {
+            return Err(located_error(&c.location, "@If without a matching @EndIf."));
+        }
+        return Ok(());
+    }
+
+    /**
+     * Adds a directive value read at `location`.
+     */
+    pub fn push_directive(&mut self, directive: &str, value: String, location: SourceLocation) {
+        self.directives
+            .entry(directive.into())
+            .or_default()
+            .push(value);
+        self.directive_locations
+            .entry(directive.into())
+            .or_default()
+            .push(location);
+    }
+
+    /**
+     * Where the current value of property `key` was last set, if it was read from a manifest or
+     * the command line.
+     */
+    pub fn property_location(&self, key: &str) -> Option<&SourceLocation> {
+        return self.property_locations.get(key).and_then(|l| l.last());
+    }
+
+    /**
+     * Where the `index`th value of `directive` was read, if it was read from a manifest or the
+     * command line.
+     */
+    pub fn directive_location(&self, directive: &str, index: usize) -> Option<&SourceLocation> {
+        return self
+            .directive_locations
+            .get(directive)
+            .and_then(|l| l.get(index));
+    }
+
+    /**
+     * Sets a property from a `Key=value` line. `Key+=value` appends to the current value instead,
+     * separated by a comma like the list properties (`Additional-CC-Flags`, ...) expect.
+     */
+    pub fn assign_property(&mut self, key: &str, value: &str) {
+        let key = match key.strip_suffix("+") {
+            Some(k) => k,
+            None => {
+                self.properties.insert(key.into(), value.into());
+                return;
+            }
+        };
+        match self.properties.get_mut(key) {
+            Some(v) if !v.is_empty() => {
+                v.push(',');
+                v.push_str(value);
+            }
+            _ => {
+                self.properties.insert(key.into(), value.into());
+            }
+        }
+    }
+
+    /**
+     * Replaces `${Property}` and `${env:VAR}` references in `text`. Referenced properties are
+     * interpolated themselves and `$${` is a literal `${`. Referencing a property that isn't defined
+     * is an error. `context` names what `text` belongs to for error messages.
+     */
+    pub fn interpolate(&self, text: &str, context: &str) -> Result<String, GreathelmError> {
+        return self.interpolate_with(text, context, &mut vec![]);
+    }
+
+    fn interpolate_with(
+        &self,
+        text: &str,
+        context: &str,
+        stack: &mut Vec<String>,
+    ) -> Result<String, GreathelmError> {
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some(i) = rest.find("${") {
+            if rest[..i].ends_with("$") {
+                out.push_str(&rest[..i - 1]);
+                out.push_str("${");
+                rest = &rest[i + 2..];
+                continue;
+            }
+            out.push_str(&rest[..i]);
+            let after = &rest[i + 2..];
+            let end = match after.find("}") {
+                Some(e) => e,
+                None => {
+                    return Err(GreathelmError::ManifestError(format!(
+                        "Unterminated \x1bc${{\x1br in \x1bc{context}\x1br."
+                    )));
+                }
+            };
+            let name = after[..end].trim();
+            rest = &after[end + 1..];
+
+            if let Some(var) = name.strip_prefix("env:") {
+                match std::env::var(var) {
+                    Ok(v) => out.push_str(&v),
+                    Err(_) => {
+                        return Err(GreathelmError::ManifestError(format!(
+                            "Environment variable \x1bc{var}\x1br referenced in \x1bc{context}\x1br is not set."
+                        )));
+                    }
+                }
+                continue;
+            }
+
+            if stack.iter().any(|s| s == name) {
+                stack.push(name.into());
+                return Err(GreathelmError::ManifestError(format!(
+                    "Cyclic property reference: \x1bc{}\x1br.",
+                    stack.join(" -> ")
+                )));
+            }
+            let raw = match self.properties.get(name) {
+                Some(v) => v,
+                None => {
+                    return Err(GreathelmError::ManifestError(undefined_reference(
+                        name, context,
+                    )));
+                }
+            };
+            stack.push(name.into());
+            let value = self.interpolate_with(raw, name, stack)?;
+            stack.pop();
+            out.push_str(&value);
+        }
+        out.push_str(rest);
+        return Ok(out);
+    }
+
+    /**
+     * Checks that every `${Key}` in the properties `keys` and the directives refers to a property
+     * that is defined. Done before interpolating so the error points at the line with the
+     * reference rather than at a property that uses it.
+     */
+    fn check_references(&self, keys: &[String]) -> Result<(), GreathelmError> {
+        let check = |location: Option<&SourceLocation>, context: &str, text: &str| {
+            for name in references(text) {
+                if self.properties.contains_key(&name) {
+                    continue;
+                }
+                let message = undefined_reference(&name, context);
+                return Err(match location {
+                    Some(l) => located_error(l, &message),
+                    None => GreathelmError::ManifestError(message),
+                });
+            }
+            return Ok(());
+        };
+        for k in keys {
+            check(self.property_location(k), k, &self.properties[k])?;
+        }
+        let mut names: Vec<&String> = self.directives.keys().collect();
+        names.sort();
+        for name in names {
+            for (i, v) in self.directives[name].iter().enumerate() {
+                check(self.directive_location(name, i), &format!("@{name}"), v)?;
+            }
+        }
+        return Ok(());
+    }
+
+    /**
+     * Interpolates every property value and directive argument in place. Called once the whole
+     * manifest has been read, so references can point at properties defined further down.
+     */
+    pub fn resolve(&mut self) -> Result<(), GreathelmError> {
+        let mut keys: Vec<String> = self.properties.keys().cloned().collect();
+        keys.sort();
+        self.check_references(&keys)?;
+        let mut properties = HashMap::new();
+        for k in keys {
+            let mut stack = vec![k.clone()];
+            let value = self
+                .interpolate_with(&self.properties[&k], &k, &mut stack)
+                .map_err(|e| match self.property_location(&k) {
+                    Some(l) => located_error(l, e.message()),
+                    None => e,
+                })?;
+            properties.insert(k, value);
+        }
+
+        let mut directives = HashMap::new();
+        for (name, values) in &self.directives {
+            let context = format!("@{name}");
+            let mut resolved = Vec::new();
+            for (i, v) in values.iter().enumerate() {
+                let value = self.interpolate(v, &context).map_err(|e| {
+                    match self.directive_location(name, i) {
+                        Some(l) => located_error(l, e.message()),
+                        None => e,
+                    }
+                })?;
+                resolved.push(value);
+            }
+            directives.insert(name.clone(), resolved);
+        }
+
+        self.properties = properties;
+        self.directives = directives;
+        return Ok(());
+    }
+
+    /**
+     * Evaluates the condition of an @If or @ElseIf against this manifest. A condition is one of
+     *
+     * - `os <name>` / `arch <name>` The host OS or architecture (`linux`, `macos`, `windows`,
+     *   `x86_64`, `aarch64`, ...).
+     * - `env <VAR>` The environment variable is set and not empty. `env <VAR>=<value>` and
+     *   `env <VAR>!=<value>` compare its value.
+     * - `profile <name>` The active build profile (the `profile` property, `debug` by default).
+     * - `target <name>` The active target (the `target` property, `host` by default).
+     * - `<Key>` The property is set and not `false`. `<Key>=<value>` and `<Key>!=<value>`
+     *   compare its value.
+     *
+     * and can be negated with a leading `!`.
+     */
+    pub fn evaluate_condition(&self, condition: &str) -> Result<bool, GreathelmError> {
+        if let Some(c) = condition.strip_prefix("!") {
+            return Ok(!self.evaluate_condition(c.trim())?);
+        }
+        if condition.is_empty() {
+            return Err(GreathelmError::ManifestError("Empty @If condition.".into()));
+        }
+
+        // compares `actual` according to `rest`, which is `=<value>`, `!=<value>` or nothing
+        let compare = |actual: Option<String>, rest: &str| -> bool {
+            if let Some(v) = rest.strip_prefix("!=") {
+                return actual.as_deref() != Some(v.trim());
+            }
+            if let Some(v) = rest.strip_prefix("=") {
+                return actual.as_deref() == Some(v.trim());
+            }
+            return match actual {
+                Some(a) => !a.is_empty() && a != "false",
+                None => false,
+            };
+        };
+
+        if let Some((kind, arg)) = condition.split_once(" ") {
+            let arg = arg.trim();
+            match kind {
+                "os" => return Ok(std::env::consts::OS == arg),
+                "arch" => return Ok(std::env::consts::ARCH == arg),
+                "profile" => return Ok(profile::active_profile(self) == arg),
+                "target" => return Ok(target::active_target(self) == arg),
+                "env" => {
+                    let (var, rest) = match arg.find(['=', '!']) {
+                        Some(i) => arg.split_at(i),
+                        None => (arg, ""),
+                    };
+                    return Ok(compare(std::env::var(var.trim()).ok(), rest));
+                }
+                _ => {}
+            }
+        }
+
+        let (key, rest) = match condition.find(['=', '!']) {
+            Some(i) => condition.split_at(i),
+            None => (condition, ""),
+        };
+        let key = key.trim();
+        if key.is_empty() || key.contains(" ") {
+            return Err(GreathelmError::ManifestError(format!(
+                "Invalid @If condition \x1bc{condition}\x1br."
+            )));
+        }
+        let value = match self.properties.get(key) {
+            Some(v) => Some(self.interpolate(v, key)?),
+            None => None,
+        };
+        return Ok(compare(value, rest.trim()));
+    }
+
+    /**
+     * Writes this manifest back out as .ghm text that `append_from_str` reads into an equal
+     * manifest. @Import directives are left out as their contents are already merged in.
+     * Properties and directives are sorted so the output is stable.
+     */
+    pub fn to_ghm_string(&self) -> String {
+        let mut text = String::new();
+
+        let mut keys: Vec<&String> = self.properties.keys().collect();
+        keys.sort();
+        for k in keys {
+            text.push_str(&format!("{k}={}\n", self.properties.get(k).unwrap()));
+        }
+
+        let mut directives: Vec<&String> = self.directives.keys().collect();
+        directives.sort();
+        for d in directives {
+            if d == "Import" {
+                continue;
+            }
+            for v in self.directives.get(d).unwrap() {
+                text.push_str(&format!("@{d} {v}\n"));
+            }
+        }
+
+        return text;
+    }
+
+    /**
+     * Serializes this manifest as a JSON object with a `properties` object of strings and a
+     * `directives` object of string arrays.
+     */
+    pub fn to_json(&self) -> JsonValue {
+        let mut keys: Vec<&String> = self.properties.keys().collect();
+        keys.sort();
+        let properties = keys
+            .into_iter()
+            .map(|k| {
+                (
+                    k.clone(),
+                    JsonValue::string(self.properties.get(k).unwrap()),
+                )
+            })
+            .collect();
+
+        let mut keys: Vec<&String> = self.directives.keys().collect();
+        keys.sort();
+        let directives = keys
+            .into_iter()
+            .map(|k| {
+                (
+                    k.clone(),
+                    JsonValue::string_array(self.directives.get(k).unwrap()),
+                )
+            })
+            .collect();
+
+        return JsonValue::object(vec![
+            ("properties", JsonValue::Object(properties)),
+            ("directives", JsonValue::Object(directives)),
+        ]);
+    }
+
+    /**
+     * Reads a manifest back from the JSON form produced by `to_json`.
+     */
+    pub fn from_json(json: &JsonValue) -> Result<Self, GreathelmError> {
+        let mut manifest = Self::new();
+        match json.get("properties") {
+            Some(JsonValue::Object(pairs)) => {
+                for (k, v) in pairs {
+                    match v.as_str() {
+                        Some(v) => {
+                            manifest.properties.insert(k.clone(), v.into());
+                        }
+                        None => {
+                            return Err(GreathelmError::ManifestError(format!(
+                                "Manifest property \x1bc{k}\x1br is not a string."
+                            )));
+                        }
+                    }
+                }
+            }
+            _ => {
+                return Err(GreathelmError::ManifestError(
+                    "Manifest JSON has no properties object.".into(),
+                ));
+            }
+        }
+        if let Some(JsonValue::Object(pairs)) = json.get("directives") {
+            for (k, v) in pairs {
+                manifest.directives.insert(k.clone(), v.as_string_vec());
+            }
+        }
+        return Ok(manifest);
+    }
+
+    /**
+     * Gets the @Module directives as Module structs.
+     */
+    pub fn get_modules(&self) -> Vec<Module> {
+        let mut modules = Vec::new();
+
+        for m in self.directives.get("Module").unwrap() {
+            let module_name = match m.split_whitespace().next() {
+                Some(n) => n,
+                None => continue,
+            };
+            let mut files: HashMap<String, String> = HashMap::new();
+            for path in m.split(" ").skip(1) {
+                if !path.contains(":") {
+                    continue;
+                }
+                let (homepath, modpath) = path.split_once(":").unwrap();
+                files.insert(homepath.into(), modpath.into());
+            }
+            modules.push(Module {
+                module_name: module_name.into(),
+                files,
+                dependencies: vec![],
+                project_dir: self.project_dir(),
+            });
+        }
+
+        // @ModuleDependency <module> <modules it depends on...>
+        if let Some(deps) = self.directives.get("ModuleDependency") {
+            for d in deps {
+                let mut names = d.split_whitespace();
+                let module_name = match names.next() {
+                    Some(n) => n,
+                    None => continue,
+                };
+                for m in modules.iter_mut() {
+                    if m.module_name == module_name {
+                        m.dependencies.extend(names.clone().map(|n| n.to_string()));
+                    }
+                }
+            }
+        }
+
+        return modules;
+    }
+
+    /**
+     * Gets a map of all the aliases defined with @Alias directives.
+     */
+    pub fn get_aliases_map(&self) -> HashMap<String, String> {
+        let mut m = HashMap::new();
+
+        for a in self.directives.get("Alias").unwrap() {
+            if !a.contains(" ") {
+                continue;
+            }
+            let (k, v) = a.split_once(" ").unwrap();
+            m.insert(k.into(), v.into());
+        }
+
+        return m;
+    }
+
+    /**
+     * Sets the `--Key=value` (or `--flag`, meaning `true`) properties from CLI arguments.
+     */
+    pub fn append_cli_properties(&mut self, args: &[String]) {
+        for arg in args {
+            if arg.starts_with("--") {
+                let key = if arg.contains("=") {
+                    let (k, v) = arg[2..].split_once("=").unwrap();
+                    self.assign_property(k, v);
+                    k.strip_suffix("+").unwrap_or(k)
+                } else {
+                    self.properties.insert(arg[2..].into(), "true".into());
+                    &arg[2..]
+                };
+                self.property_locations
+                    .entry(key.into())
+                    .or_default()
+                    .push(SourceLocation::command_line());
+            }
+        }
+    }
+
+    /**
+     * Appends properties to this manifest instance from CLI arguments.
+     */
+    pub fn append_from_cli_args(&mut self, args: Vec<String>) {
+        self.append_cli_properties(&args);
+        for arg in &args {
+            if arg.starts_with("@") && arg.contains(":") {
+                let (directive, value) = arg[1..].split_at(match arg.find(":") {
+                    Some(v) => v,
+                    None => {
+                        continue;
+                    }
+                });
+                let directive = &directive[0..directive.len() - 1];
+
+                self.push_directive(directive, value.into(), SourceLocation::command_line());
+            }
+        }
+    }
+}
+
+/**
+ * A ManifestError for `message` that points at `location`.
+ */
+fn located_error(location: &SourceLocation, message: impl Display) -> GreathelmError {
+    return GreathelmError::ManifestError(format!("\x1bc{location}\x1br: {message}"));
+}
+
+fn undefined_reference(name: &str, context: &str) -> String {
+    return format!(
+        "Property \x1bc{name}\x1br referenced in \x1bc{context}\x1br is not defined. \
+         Write \x1bc$${{{name}}}\x1br for a literal \x1bc${{{name}}}\x1br."
+    );
+}
+
+/**
+ * The properties referenced with `${Key}` in `text`.
+ */
+fn references(text: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(i) = rest.find("${") {
+        let escaped = rest[..i].ends_with("$");
+        rest = &rest[i + 2..];
+        if escaped {
+            continue;
+        }
+        if let Some(end) = rest.find("}") {
+            let name = rest[..end].trim();
+            if !name.starts_with("env:") {
+                names.push(name.to_string());
+            }
+            rest = &rest[end + 1..];
+        }
+    }
+    return names;
+}
+
+/**
+ * The property an @If condition looks at, if it looks at one.
+ */
+fn condition_key(condition: &str) -> Option<String> {
+    let condition = condition.trim_start_matches(['!', ' ']);
+    if let Some((kind, _)) = condition.split_once(" ") {
+        if ["os", "arch", "env", "profile", "target"].contains(&kind) {
+            return None;
+        }
+    }
+    let key = match condition.find(['=', '!']) {
+        Some(i) => &condition[..i],
+        None => condition,
+    };
+    return Some(key.trim().to_string());
+}
+
+/**
+ * State of an @If block while parsing.
+ */
+struct Conditional {
+    /**
+     * Whether the lines of the current branch are used.
+     */
+    active: bool,
+    /**
+     * Whether any branch of this block was taken yet.
+     */
+    taken: bool,
+    /**
+     * Where the @If is.
+     */
+    location: SourceLocation,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(text: &str) -> ProjectManifest {
+        let mut manifest = ProjectManifest::new();
+        manifest.append_from_str(text).unwrap();
+        return manifest;
+    }
+
+    fn resolved(text: &str) -> ProjectManifest {
+        let mut manifest = manifest(text);
+        manifest.resolve().unwrap();
+        return manifest;
+    }
+
+    fn property(manifest: &ProjectManifest, key: &str) -> String {
+        return manifest.get_string_property(key, "<unset>");
+    }
+
+    #[test]
+    fn interpolates_properties() {
+        let m = resolved(
+            "Name=tool\nOut=${Dir}/${Name}\nDir=build/${Profile}\nProfile=release\n@Export ${Out} bin/${Name}",
+        );
+        // references can point further down and are interpolated themselves
+        assert_eq!(property(&m, "Out"), "build/release/tool");
+        assert_eq!(m.directives["Export"], ["build/release/tool bin/tool"]);
+    }
+
+    #[test]
+    fn keeps_escaped_references() {
+        let m = resolved("Flags=-Wl,-rpath,$${ORIGIN}\nLiteral=$${Name}\nName=x");
+        assert_eq!(property(&m, "Flags"), "-Wl,-rpath,${ORIGIN}");
+        assert_eq!(property(&m, "Literal"), "${Name}");
+        assert!(m.warnings.is_empty());
+    }
+
+    #[test]
+    fn rejects_undefined_references() {
+        let mut m = ProjectManifest::new();
+        m.append_from_source("Name=x\nFlags=-Wl,-rpath,${ORIGIN}", "Project.ghm")
+            .unwrap();
+        let e = m.resolve().unwrap_err().message();
+        assert!(e.contains("Project.ghm:2"), "{e}");
+        assert!(e.contains("ORIGIN"), "{e}");
+
+        // the error points at the reference, not at a property using it
+        let mut m = ProjectManifest::new();
+        m.append_from_source("A=${B}\nB=${C}\n@Export ${A} a", "Project.ghm")
+            .unwrap();
+        let e = m.resolve().unwrap_err().message();
+        assert!(e.contains("Project.ghm:2"), "{e}");
+
+        let mut m = ProjectManifest::new();
+        m.append_from_source("\n@Export ${Missing} a", "Project.ghm")
+            .unwrap();
+        let e = m.resolve().unwrap_err().message();
+        assert!(e.contains("Project.ghm:2"), "{e}");
+        assert!(manifest("").interpolate("${Missing}", "test").is_err());
+    }
+
+    #[test]
+    fn interpolates_the_environment() {
+        let m = manifest("");
+        assert_eq!(
+            m.interpolate("${env:PATH}", "test").unwrap(),
+            std::env::var("PATH").unwrap()
+        );
+        assert!(m
+            .interpolate("${env:GREATHELM_TEST_UNSET_VARIABLE}", "test")
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_bad_references() {
+        let mut cyclic = manifest("A=${B}\nB=${A}");
+        let e = cyclic.resolve().unwrap_err();
+        assert!(e.message().contains("Cyclic"));
+        assert!(manifest("").interpolate("${Name", "test").is_err());
+    }
+
+    #[test]
+    fn appends_to_properties() {
+        let m = manifest("Flags=-O2\nFlags+=-g\nEmpty=\nEmpty+=x\nNew+=y");
+        assert_eq!(property(&m, "Flags"), "-O2,-g");
+        assert_eq!(property(&m, "Empty"), "x");
+        assert_eq!(property(&m, "New"), "y");
+    }
+
+    #[test]
+    fn takes_the_first_matching_branch() {
+        let text = "Mode=b\n\
+                    @If Mode=a\nPicked=a\n\
+                    @ElseIf Mode=b\nPicked=b\n\
+                    @ElseIf Mode!=c\nPicked=not-c\n\
+                    @Else\nPicked=else\n\
+                    @EndIf";
+        assert_eq!(property(&manifest(text), "Picked"), "b");
+        let text = text.replace("Mode=b\n@If", "Mode=z\n@If");
+        assert_eq!(property(&manifest(&text), "Picked"), "not-c");
+        let text = text.replace("Mode=z\n@If", "Mode=c\n@If");
+        assert_eq!(property(&manifest(&text), "Picked"), "else");
+    }
+
+    #[test]
+    fn skips_nested_blocks_of_inactive_branches() {
+        let m = manifest(
+            "Outer=false\n\
+             @If Outer\n@If !Outer\nInner=yes\n@EndIf\n@Dependency a:b\n@Else\nInner=no\n@EndIf",
+        );
+        assert_eq!(property(&m, "Inner"), "no");
+        assert!(m.directives["Dependency"].is_empty());
+    }
+
+    #[test]
+    fn evaluates_conditions() {
+        let m = manifest("Set=1\nOff=false\nEmpty=\nprofile=asan\nRef=${Set}");
+        assert!(m.evaluate_condition("Set").unwrap());
+        assert!(!m.evaluate_condition("Off").unwrap());
+        assert!(!m.evaluate_condition("Empty").unwrap());
+        assert!(!m.evaluate_condition("Unset").unwrap());
+        assert!(m.evaluate_condition("!Unset").unwrap());
+        assert!(m.evaluate_condition("Ref=1").unwrap());
+        assert!(m.evaluate_condition("profile asan").unwrap());
+        assert!(m.evaluate_condition("target host").unwrap());
+        assert!(m
+            .evaluate_condition(&format!("os {}", std::env::consts::OS))
+            .unwrap());
+        assert!(m.evaluate_condition("env PATH").unwrap());
+        assert!(!m
+            .evaluate_condition("env GREATHELM_TEST_UNSET_VARIABLE")
+            .unwrap());
+        assert!(m.evaluate_condition("Two Words").is_err());
+        assert!(m.evaluate_condition("").is_err());
+    }
+
+    #[test]
+    fn rejects_unbalanced_blocks() {
+        for text in ["@If A\nB=1", "@EndIf", "@Else", "@ElseIf A", "@If"] {
+            let mut manifest = ProjectManifest::new();
+            assert!(manifest.append_from_str(text).is_err(), "{text}");
+        }
+    }
+}
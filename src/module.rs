@@ -1,10 +1,12 @@
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
-    str::FromStr,
 };
 
-use crate::{script, subprocess, term::*};
+use crate::{
+    error::GreathelmError, ibht, lockfile, manifest::ProjectManifest, profile, project, script,
+    state::GreathelmState, term::*,
+};
 
 /**
  * Defines a Module specified with the @Module directive.
@@ -21,13 +23,120 @@ pub struct Module {
      * copied.
      */
     pub files: HashMap<String, String>,
+    /**
+     * Names of other modules that have to be built before this one, declared with the
+     * @ModuleDependency directive.
+     */
+    pub dependencies: Vec<String>,
+    /**
+     * The directory of the project this module belongs to, see `ProjectManifest::project_dir`.
+     */
+    pub project_dir: PathBuf,
 }
 
 impl Module {
     /**
-     * Builds this module and copies its files to their locations in the parent project.
+     * Builds this module (in-process, using the builders in `state`) with the profile and target
+     * of the parent project's `manifest` and copies its files to their locations in the parent
+     * project.
+     */
+    pub fn build(
+        &self,
+        state: &GreathelmState,
+        manifest: &ProjectManifest,
+    ) -> Result<(), GreathelmError> {
+        self.announce();
+
+        script::run_script(
+            &self.project_dir,
+            "prebuild-module",
+            vec![self.module_name.clone()],
+        );
+        // smaller greathelm, lesserhelm if you will
+        project::build_at(
+            state,
+            &self.get_root(),
+            profile::nested_build_args(manifest),
+        )?;
+        script::run_script(
+            &self.project_dir,
+            "postbuild-module",
+            vec![self.module_name.clone()],
+        );
+
+        self.fetch();
+        return Ok(());
+    }
+
+    /**
+     * Gets the path of this module's project.
+     */
+    pub fn get_root(&self) -> PathBuf {
+        self.parent_path(&format!("modules/{}", self.module_name))
+    }
+
+    /**
+     * The path of `path`, relative to the parent project, from the current directory.
+     */
+    fn parent_path(&self, path: &str) -> PathBuf {
+        if self.project_dir == Path::new(".") {
+            return PathBuf::from(path);
+        }
+        return self.project_dir.join(path);
+    }
+
+    /**
+     * Checks whether this module has to be built again. That is the case if a module it depends
+     * on was just built again (is in `rebuilt`), if its project changed since it was last built
+     * (according to the Module IBHT `table`) or if any file it provides is missing from it.
+     */
+    pub fn needs_build(&self, table: &HashMap<String, String>, rebuilt: &[String]) -> bool {
+        if self.dependencies.iter().any(|d| rebuilt.contains(d)) {
+            return true;
+        }
+        let root = self.get_root();
+        match table.get(&self.module_name) {
+            Some(hash) if *hash == lockfile::hash_package(&root) => {}
+            _ => {
+                return true;
+            }
+        }
+        self.files.values().any(|f| !root.join(f).exists())
+    }
+
+    /**
+     * Checks whether any file this module provides changed in (or disappeared from) the parent
+     * project since it was last fetched.
+     */
+    pub fn needs_fetch(&self, table: &HashMap<String, String>) -> bool {
+        self.files.keys().any(|f| {
+            let recorded = table.get(&format!("{}:{f}", self.module_name));
+            recorded.is_none() || recorded.cloned() != ibht::hash_path(&self.parent_path(f))
+        })
+    }
+
+    /**
+     * Records the current hash of this module's project and of every file it provided in the
+     * Module IBHT `table`.
+     */
+    pub fn record(&self, table: &mut HashMap<String, String>) {
+        table.insert(
+            self.module_name.clone(),
+            lockfile::hash_package(&self.get_root()),
+        );
+        for f in self.files.keys() {
+            let key = format!("{}:{f}", self.module_name);
+            match ibht::hash_path(&self.parent_path(f)) {
+                Some(hash) => table.insert(key, hash),
+                None => table.remove(&key),
+            };
+        }
+    }
+
+    /**
+     * Logs which module is about to be built and what it provides.
      */
-    pub fn build(&self) {
+    pub fn announce(&self) {
         info!("Module \x1bc{}\x1br", self.module_name);
         for f in self.files.keys() {
             info!(
@@ -35,32 +144,28 @@ impl Module {
                 self.files.get(f).unwrap()
             );
         }
+    }
 
-        script::run_script("prebuild-module", vec![self.module_name.clone()]);
-        let module_root = PathBuf::from_str(&format!("modules/{}", self.module_name)).unwrap();
-        // smaller greathelm, lesserhelm if you will
-        subprocess::build_project(&module_root);
-        script::run_script("postbuild-module", vec![self.module_name.clone()]);
-
+    /**
+     * Copies this module's files to their locations in the parent project. The module must have
+     * been built already.
+     */
+    pub fn fetch(&self) {
         for f in self.files.keys() {
-            let path = PathBuf::from_str(&format!(
-                "modules/{}/{}",
-                self.module_name,
-                self.files.get(f).unwrap()
-            ))
-            .unwrap();
+            let path = self.get_root().join(self.files.get(f).unwrap());
+            let dest = self.parent_path(f);
             match path.try_exists() {
                 Ok(exists) => {
                     if exists {
                         if path.is_dir() {
-                            match crate::util::copy_dir(&path, Path::new(f), &vec![], false) {
+                            match crate::util::copy_dir(&path, &dest, &vec![], false) {
                                 Ok(_) => {}
                                 Err(_) => {
                                     error!("Failed getting file \x1bc{f}\x1br from module \x1bc{}\x1br: Failed to copy", self.module_name);
                                 }
                             }
                         } else {
-                            match std::fs::copy(&path, Path::new(f)) {
+                            match std::fs::copy(&path, &dest) {
                                 Ok(_) => {}
                                 Err(_) => {
                                     error!("Failed getting file \x1bc{f}\x1br from module \x1bc{}\x1br: Failed to copy", self.module_name);
@@ -78,6 +183,148 @@ impl Module {
             }
         }
 
-        script::run_script("postfetch-module", vec![self.module_name.clone()]);
+        script::run_script(
+            &self.project_dir,
+            "postfetch-module",
+            vec![self.module_name.clone()],
+        );
+    }
+}
+
+/**
+ * Orders `modules` into groups which can each be built in parallel. Every module is in a later
+ * group than all the modules it depends on. Fails if a module depends on a module that does not
+ * exist or if the dependencies form a cycle.
+ */
+pub fn order_modules(modules: Vec<Module>) -> Result<Vec<Vec<Module>>, GreathelmError> {
+    let names: Vec<String> = modules.iter().map(|m| m.module_name.clone()).collect();
+    for m in &modules {
+        for d in &m.dependencies {
+            if !names.contains(d) {
+                return Err(GreathelmError::ManifestError(format!(
+                    "Module \x1bc{}\x1br depends on unknown module \x1bc{d}\x1br.",
+                    m.module_name
+                )));
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<Module>> = Vec::new();
+    let mut built: Vec<String> = Vec::new();
+    let mut remaining = modules;
+    while !remaining.is_empty() {
+        let (ready, waiting): (Vec<Module>, Vec<Module>) = remaining
+            .into_iter()
+            .partition(|m| m.dependencies.iter().all(|d| built.contains(d)));
+        if ready.is_empty() {
+            let stuck: Vec<String> = waiting.iter().map(|m| m.module_name.clone()).collect();
+            return Err(GreathelmError::ManifestError(format!(
+                "Module dependencies form a cycle between \x1bc{}\x1br.",
+                stuck.join(", ")
+            )));
+        }
+        for m in &ready {
+            built.push(m.module_name.clone());
+        }
+        groups.push(ready);
+        remaining = waiting;
+    }
+
+    return Ok(groups);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(name: &str, dependencies: &[&str]) -> Module {
+        return Module {
+            module_name: name.into(),
+            files: HashMap::new(),
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            project_dir: PathBuf::from("."),
+        };
+    }
+
+    fn names(groups: &[Vec<Module>]) -> Vec<Vec<&str>> {
+        return groups
+            .iter()
+            .map(|g| g.iter().map(|m| m.module_name.as_str()).collect())
+            .collect();
+    }
+
+    #[test]
+    fn groups_modules_by_dependencies() {
+        let groups = order_modules(vec![
+            module("app", &["net", "log"]),
+            module("net", &["log"]),
+            module("log", &[]),
+            module("tools", &[]),
+        ])
+        .unwrap();
+        assert_eq!(
+            names(&groups),
+            [vec!["log", "tools"], vec!["net"], vec!["app"]]
+        );
+        assert!(order_modules(vec![]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_unknown_and_cyclic_dependencies() {
+        let e = order_modules(vec![module("app", &["gone"])]).err().unwrap();
+        assert!(e.message().contains("unknown module \x1bcgone\x1br"));
+
+        let e = order_modules(vec![
+            module("a", &["b"]),
+            module("b", &["a"]),
+            module("c", &[]),
+        ])
+        .err()
+        .unwrap();
+        assert!(e.message().contains("cycle between \x1bca, b\x1br"));
+    }
+
+    #[test]
+    fn skips_unchanged_modules() {
+        let dir = std::env::temp_dir().join(format!("greathelm-modules-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(dir.join("modules/lib/build")).unwrap();
+        std::fs::write(dir.join("modules/lib/Project.ghm"), "Project-Name=lib").unwrap();
+        std::fs::write(dir.join("modules/lib/build/liblib.a"), "1").unwrap();
+        let mut lib = module("lib", &[]);
+        lib.project_dir = dir.clone();
+        lib.files
+            .insert("lib/liblib.a".into(), "build/liblib.a".into());
+        let mut app = module("app", &["lib"]);
+        app.project_dir = dir.clone();
+        std::fs::create_dir_all(app.get_root()).unwrap();
+
+        let mut table = HashMap::new();
+        assert!(lib.needs_build(&table, &[]));
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        lib.fetch();
+        lib.record(&mut table);
+        app.record(&mut table);
+        assert!(!lib.needs_build(&table, &[]));
+        assert!(!lib.needs_fetch(&table));
+        assert!(!app.needs_build(&table, &[]));
+
+        // dependents of a rebuilt module are built again
+        assert!(app.needs_build(&table, &["lib".into()]));
+        assert!(!app.needs_build(&table, &["other".into()]));
+
+        // fetched files that changed are fetched again
+        std::fs::write(dir.join("lib/liblib.a"), "changed").unwrap();
+        assert!(lib.needs_fetch(&table));
+        assert!(!lib.needs_build(&table, &[]));
+
+        // as are modules whose project changed or lost a provided file
+        std::fs::write(dir.join("modules/lib/src.c"), "int x;").unwrap();
+        assert!(lib.needs_build(&table, &[]));
+        lib.record(&mut table);
+        std::fs::remove_file(dir.join("modules/lib/build/liblib.a")).unwrap();
+        assert!(lib.needs_build(&table, &[]));
+
+        std::fs::remove_dir_all(&dir).ok();
     }
 }
@@ -0,0 +1,1308 @@
+use std::{
+    ffi::{c_char, c_void, CStr, CString},
+    panic::AssertUnwindSafe,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        Condvar, Mutex,
+    },
+    thread::ThreadId,
+};
+
+use crate::{
+    action::Action, builder::ProjectBuilder, error::GreathelmError, generator::ProjectGenerator,
+    identify::NamespacedIdentifier, manifest::ProjectManifest, state::GreathelmState, term::*,
+    version::Version,
+};
+
+use super::{
+    hooks::{HookContext, HookPoint, PluginHook},
+    GreathelmPlugin,
+};
+
+/**
+ * Version of the C plugin ABI implemented by this Greathelm. Bumped whenever any structure in this
+ * module changes in an incompatible way.
+ *
+ * A plugin using the C ABI exports a `u32` named `GHPI_ABI_VERSION` holding the version it was
+ * built against, optionally a NUL-terminated `GHPI_MIN_HOST_VERSION` string with the oldest
+ * Greathelm it runs on and an `extern "C" fn GHPI_PluginInitC(*const GHPIHost) -> *const GHPIPlugin`.
+ * Strings are NUL-terminated UTF-8 and manifests are passed as .ghm text. Everything a plugin
+ * hands to Greathelm must stay valid as long as the plugin is loaded (plugins are never unloaded).
+ * `include/greathelm_plugin.h` declares the same structures for plugins written in C.
+ */
+pub const GHPI_ABI_VERSION: u32 = 3;
+
+/**
+ * The version of Greathelm a plugin was built against. `greathelm_plugin!` uses this as the
+ * plugin's minimum host version unless it is given one.
+ */
+pub const HOST_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/**
+ * Status returned by plugin calls that succeeded.
+ */
+pub const GHPI_OK: i32 = 0;
+/**
+ * Status returned by plugin calls that failed. The plugin is expected to have logged why.
+ */
+pub const GHPI_ERROR: i32 = 1;
+/**
+ * Status returned by optional calls the plugin does not support.
+ */
+pub const GHPI_UNSUPPORTED: i32 = 2;
+
+/**
+ * Log levels of `GHPIHost::log`. Unknown levels are logged as info.
+ */
+#[allow(dead_code)] // only passed by plugins
+pub const GHPI_LOG_INFO: u32 = 0;
+pub const GHPI_LOG_WARNING: u32 = 1;
+pub const GHPI_LOG_ERROR: u32 = 2;
+pub const GHPI_LOG_OK: u32 = 3;
+
+/**
+ * Receives the reply to a message. Called at most once, before the call delivering the message
+ * returns. `data` is only valid during the call, text replies are UTF-8 without a NUL.
+ */
+pub type GHPIReplyFn = extern "C" fn(context: *mut c_void, data: *const u8, len: usize);
+
+/**
+ * Functions Greathelm provides to plugins. Passed to `GHPI_PluginInitC` and valid for the whole
+ * process.
+ */
+#[repr(C)]
+pub struct GHPIHost {
+    pub abi_version: u32,
+    /**
+     * Prints `text` as an [INFO], [WARN], [ERROR] or [OK] line (see the GHPI_LOG_* constants).
+     */
+    pub log: extern "C" fn(level: u32, text: *const c_char),
+    /**
+     * Sends `data` to the plugin identified by `to` (`namespace:Identifier`). Its reply, if any,
+     * is passed to `reply` (which may be NULL) along with `reply_context`. Returns GHPI_ERROR if
+     * there is no such plugin or it failed to handle the message.
+     */
+    pub send_message: extern "C" fn(
+        to: *const c_char,
+        data: *const u8,
+        len: usize,
+        reply: Option<GHPIReplyFn>,
+        reply_context: *mut c_void,
+    ) -> i32,
+    /**
+     * Same as send_message but for text.
+     */
+    pub send_text_message: extern "C" fn(
+        to: *const c_char,
+        text: *const c_char,
+        reply: Option<GHPIReplyFn>,
+        reply_context: *mut c_void,
+    ) -> i32,
+}
+
+/**
+ * A builder. `manifest` is the combined project manifest as .ghm text.
+ */
+#[repr(C)]
+pub struct GHPIBuilder {
+    pub name: *const c_char,
+    /**
+     * `namespace:Identifier`
+     */
+    pub identifier: *const c_char,
+    /**
+     * NULL-terminated array of aliases.
+     */
+    pub aliases: *const *const c_char,
+    /**
+     * Passed back to every function below.
+     */
+    pub userdata: *mut c_void,
+    pub build: extern "C" fn(userdata: *mut c_void, manifest: *const c_char) -> i32,
+    pub validate: extern "C" fn(userdata: *mut c_void, manifest: *const c_char) -> bool,
+    pub cleanup: extern "C" fn(userdata: *mut c_void, manifest: *const c_char) -> i32,
+    /**
+     * Optional. Returns GHPI_UNSUPPORTED if the builder can't write compilation databases.
+     */
+    pub write_compile_commands:
+        Option<extern "C" fn(userdata: *mut c_void, manifest: *const c_char) -> i32>,
+}
+
+/**
+ * A generator. `cwd` is the directory to generate the project in.
+ */
+#[repr(C)]
+pub struct GHPIGenerator {
+    pub name: *const c_char,
+    pub identifier: *const c_char,
+    pub aliases: *const *const c_char,
+    pub userdata: *mut c_void,
+    pub generate: extern "C" fn(userdata: *mut c_void, cwd: *const c_char) -> i32,
+}
+
+/**
+ * An action. It gets the manifest as .ghm text and the command line arguments.
+ */
+#[repr(C)]
+pub struct GHPIAction {
+    pub name: *const c_char,
+    pub identifier: *const c_char,
+    pub aliases: *const *const c_char,
+    pub userdata: *mut c_void,
+    pub execute: extern "C" fn(
+        userdata: *mut c_void,
+        manifest: *const c_char,
+        argc: usize,
+        argv: *const *const c_char,
+    ) -> i32,
+}
+
+/**
+ * A build hook (see `hooks::HookPoint` for the points). `source` and `output` are NULL unless the
+ * hook point sets them. Returns GHPI_OK to continue the build.
+ */
+#[repr(C)]
+pub struct GHPIHook {
+    pub point: u32,
+    pub userdata: *mut c_void,
+    pub run: extern "C" fn(
+        userdata: *mut c_void,
+        point: u32,
+        manifest: *const c_char,
+        source: *const c_char,
+        output: *const c_char,
+    ) -> i32,
+}
+
+/**
+ * The plugin descriptor returned by `GHPI_PluginInitC`. See GreathelmPlugin for what the fields
+ * mean. The callbacks get `userdata` as their first argument.
+ */
+#[repr(C)]
+pub struct GHPIPlugin {
+    pub name: *const c_char,
+    pub vendor: *const c_char,
+    pub description: *const c_char,
+    pub identifier: *const c_char,
+    pub version: *const c_char,
+
+    pub builders: *const GHPIBuilder,
+    pub builder_count: usize,
+    pub generators: *const GHPIGenerator,
+    pub generator_count: usize,
+    pub actions: *const GHPIAction,
+    pub action_count: usize,
+    /**
+     * NULL-terminated array of `namespace:Identifier` template identifiers.
+     */
+    pub provides_templates: *const *const c_char,
+
+    pub userdata: *mut c_void,
+    pub create_template: Option<
+        extern "C" fn(
+            userdata: *mut c_void,
+            identifier: *const c_char,
+            path: *const c_char,
+        ) -> bool,
+    >,
+    pub first_time_setup: Option<extern "C" fn(userdata: *mut c_void)>,
+    pub uninstall: Option<extern "C" fn(userdata: *mut c_void)>,
+    /**
+     * Handles a message from the host or another plugin. The plugin may answer by calling `reply`
+     * with `reply_context` before returning GHPI_OK.
+     */
+    pub plugin_message: Option<
+        extern "C" fn(
+            userdata: *mut c_void,
+            data: *const u8,
+            len: usize,
+            reply: GHPIReplyFn,
+            reply_context: *mut c_void,
+        ) -> i32,
+    >,
+    pub plugin_text_message: Option<
+        extern "C" fn(
+            userdata: *mut c_void,
+            text: *const c_char,
+            reply: GHPIReplyFn,
+            reply_context: *mut c_void,
+        ) -> i32,
+    >,
+
+    pub hooks: *const GHPIHook,
+    pub hook_count: usize,
+}
+
+/**
+ * The host functions handed to every plugin.
+ */
+pub static HOST: GHPIHost = GHPIHost {
+    abi_version: GHPI_ABI_VERSION,
+    log: host_log,
+    send_message: host_send_message,
+    send_text_message: host_send_text_message,
+};
+
+extern "C" fn host_log(level: u32, text: *const c_char) {
+    let text = unsafe { from_c_string(text) };
+    for line in text.lines() {
+        match level {
+            GHPI_LOG_WARNING => warning!("{line}"),
+            GHPI_LOG_ERROR => error!("{line}"),
+            GHPI_LOG_OK => ok!("{line}"),
+            _ => info!("{line}"),
+        }
+    }
+}
+
+extern "C" fn host_send_message(
+    to: *const c_char,
+    data: *const u8,
+    len: usize,
+    reply: Option<GHPIReplyFn>,
+    reply_context: *mut c_void,
+) -> i32 {
+    let result = unsafe { parse_identifier(to) }
+        .map_err(GreathelmError::PluginError)
+        .and_then(|to| super::bus::send_message(&to, unsafe { from_c_slice(data, len) }));
+    return match result {
+        Ok(answer) => {
+            if let (Some(r), Some(a)) = (reply, answer) {
+                r(reply_context, a.as_ptr(), a.len());
+            }
+            GHPI_OK
+        }
+        Err(e) => {
+            e.report();
+            GHPI_ERROR
+        }
+    };
+}
+
+extern "C" fn host_send_text_message(
+    to: *const c_char,
+    text: *const c_char,
+    reply: Option<GHPIReplyFn>,
+    reply_context: *mut c_void,
+) -> i32 {
+    let result = unsafe { parse_identifier(to) }
+        .map_err(GreathelmError::PluginError)
+        .and_then(|to| super::bus::send_text_message(&to, &unsafe { from_c_string(text) }));
+    return match result {
+        Ok(answer) => {
+            if let (Some(r), Some(a)) = (reply, answer) {
+                r(reply_context, a.as_ptr(), a.len());
+            }
+            GHPI_OK
+        }
+        Err(e) => {
+            e.report();
+            GHPI_ERROR
+        }
+    };
+}
+
+/**
+ * A GHPIReplyFn storing the reply in the `Option<Vec<u8>>` its context points to.
+ */
+extern "C" fn collect_reply(context: *mut c_void, data: *const u8, len: usize) {
+    let out = unsafe { &mut *(context as *mut Option<Vec<u8>>) };
+    *out = Some(unsafe { from_c_slice(data, len) }.to_vec());
+}
+
+fn reply_text(reply: Option<Vec<u8>>) -> Option<String> {
+    return reply.map(|r| String::from_utf8_lossy(&r).into_owned());
+}
+
+// Host side: wrapping a loaded GHPIPlugin into a GreathelmPlugin.
+
+/**
+ * Copies a C string into a String. NULL becomes an empty string.
+ */
+unsafe fn from_c_string(s: *const c_char) -> String {
+    if s.is_null() {
+        return String::new();
+    }
+    return CStr::from_ptr(s).to_string_lossy().into_owned();
+}
+
+/**
+ * Copies a NULL-terminated array of C strings.
+ */
+unsafe fn from_c_string_array(mut arr: *const *const c_char) -> Vec<String> {
+    let mut v = Vec::new();
+    if arr.is_null() {
+        return v;
+    }
+    while !(*arr).is_null() {
+        v.push(from_c_string(*arr));
+        arr = arr.add(1);
+    }
+    return v;
+}
+
+unsafe fn from_c_slice<'a, T>(ptr: *const T, len: usize) -> &'a [T] {
+    if ptr.is_null() || len == 0 {
+        return &[];
+    }
+    return std::slice::from_raw_parts(ptr, len);
+}
+
+unsafe fn parse_identifier(s: *const c_char) -> Result<NamespacedIdentifier, String> {
+    let text = from_c_string(s);
+    return match NamespacedIdentifier::parse_text(&text) {
+        Some(id) => Ok(id),
+        None => Err(format!(
+            "\x1bc{text}\x1br is not a valid namespaced identifier."
+        )),
+    };
+}
+
+fn optional_c_path(path: &Option<PathBuf>) -> Option<CString> {
+    return path
+        .as_ref()
+        .map(|p| CString::new(p.to_string_lossy().into_owned()).unwrap_or_default());
+}
+
+fn manifest_text(manifest: &ProjectManifest) -> Result<CString, GreathelmError> {
+    return CString::new(manifest.to_ghm_string()).map_err(|_| {
+        GreathelmError::ManifestError(
+            "The manifest contains a NUL character and can't be passed to a plugin.".into(),
+        )
+    });
+}
+
+/**
+ * Serializes the calls into one C ABI plugin. C plugins don't have to be thread safe but compile
+ * hooks run on the compiler threads, so every call into a plugin holds its lock. The lock is
+ * reentrant so a plugin can still be called again on a thread that is already inside it (i.e.
+ * when it messages itself). Out-of-process plugins are serialized the same way (see `stdio`).
+ */
+pub(super) struct PluginLock {
+    /**
+     * The thread inside the plugin and how many calls deep it is.
+     */
+    owner: Mutex<(Option<ThreadId>, usize)>,
+    released: Condvar,
+}
+
+pub(super) struct PluginLockGuard<'a> {
+    lock: &'a PluginLock,
+}
+
+impl PluginLock {
+    pub(super) fn create() -> &'static PluginLock {
+        return Box::leak(Box::new(PluginLock {
+            owner: Mutex::new((None, 0)),
+            released: Condvar::new(),
+        }));
+    }
+
+    /**
+     * Waits until no other thread is inside the plugin.
+     */
+    pub(super) fn enter(&self) -> PluginLockGuard<'_> {
+        let me = std::thread::current().id();
+        let mut owner = self.owner.lock().unwrap();
+        loop {
+            match owner.0 {
+                None => {
+                    *owner = (Some(me), 1);
+                    break;
+                }
+                Some(t) if t == me => {
+                    owner.1 += 1;
+                    break;
+                }
+                Some(_) => owner = self.released.wait(owner).unwrap(),
+            }
+        }
+        return PluginLockGuard { lock: self };
+    }
+}
+
+impl Drop for PluginLockGuard<'_> {
+    fn drop(&mut self) {
+        let mut owner = self.lock.owner.lock().unwrap();
+        owner.1 -= 1;
+        if owner.1 == 0 {
+            owner.0 = None;
+            self.lock.released.notify_one();
+        }
+    }
+}
+
+struct AbiBuilder {
+    name: String,
+    identifier: NamespacedIdentifier,
+    aliases: Vec<String>,
+    lock: &'static PluginLock,
+    vtable: &'static GHPIBuilder,
+}
+
+/**
+ * The vtable is static plugin data and every call into it holds the plugin's lock.
+ */
+unsafe impl Send for AbiBuilder {}
+unsafe impl Sync for AbiBuilder {}
+
+impl ProjectBuilder for AbiBuilder {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        self.aliases.clone()
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        self.identifier.clone()
+    }
+    fn build(&self, manifest: &ProjectManifest) -> Result<(), GreathelmError> {
+        let text = manifest_text(manifest)?;
+        let _inside = self.lock.enter();
+        if (self.vtable.build)(self.vtable.userdata, text.as_ptr()) != GHPI_OK {
+            return Err(GreathelmError::BuildError(format!(
+                "Builder \x1bc{}\x1br failed.",
+                self.identifier
+            )));
+        }
+        return Ok(());
+    }
+    fn validate(&self, manifest: &ProjectManifest) -> bool {
+        let text = match manifest_text(manifest) {
+            Ok(t) => t,
+            Err(_) => return false,
+        };
+        let _inside = self.lock.enter();
+        return (self.vtable.validate)(self.vtable.userdata, text.as_ptr());
+    }
+    fn cleanup(&self, manifest: &ProjectManifest) -> Result<(), GreathelmError> {
+        let text = manifest_text(manifest)?;
+        let _inside = self.lock.enter();
+        if (self.vtable.cleanup)(self.vtable.userdata, text.as_ptr()) != GHPI_OK {
+            return Err(GreathelmError::BuildError(format!(
+                "Builder \x1bc{}\x1br failed to clean up.",
+                self.identifier
+            )));
+        }
+        return Ok(());
+    }
+    fn write_compile_commands(&self, manifest: &ProjectManifest) -> Result<bool, GreathelmError> {
+        let func = match self.vtable.write_compile_commands {
+            Some(f) => f,
+            None => return Ok(false),
+        };
+        let text = manifest_text(manifest)?;
+        let _inside = self.lock.enter();
+        return match func(self.vtable.userdata, text.as_ptr()) {
+            GHPI_OK => Ok(true),
+            GHPI_UNSUPPORTED => Ok(false),
+            _ => Err(GreathelmError::BuildError(format!(
+                "Builder \x1bc{}\x1br failed to write a compilation database.",
+                self.identifier
+            ))),
+        };
+    }
+}
+
+struct AbiGenerator {
+    name: String,
+    identifier: NamespacedIdentifier,
+    aliases: Vec<String>,
+    lock: &'static PluginLock,
+    vtable: &'static GHPIGenerator,
+}
+
+/**
+ * The vtable is static plugin data and every call into it holds the plugin's lock.
+ */
+unsafe impl Send for AbiGenerator {}
+unsafe impl Sync for AbiGenerator {}
+
+impl ProjectGenerator for AbiGenerator {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        self.aliases.clone()
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        self.identifier.clone()
+    }
+    fn generate(&self, cwd: PathBuf) -> Result<(), GreathelmError> {
+        let path = CString::new(cwd.to_string_lossy().into_owned()).unwrap_or_default();
+        let _inside = self.lock.enter();
+        if (self.vtable.generate)(self.vtable.userdata, path.as_ptr()) != GHPI_OK {
+            return Err(GreathelmError::TemplateError(format!(
+                "Generator \x1bc{}\x1br failed.",
+                self.identifier
+            )));
+        }
+        return Ok(());
+    }
+}
+
+struct AbiAction {
+    name: String,
+    identifier: NamespacedIdentifier,
+    aliases: Vec<String>,
+    lock: &'static PluginLock,
+    vtable: &'static GHPIAction,
+}
+
+/**
+ * The vtable is static plugin data and every call into it holds the plugin's lock.
+ */
+unsafe impl Send for AbiAction {}
+unsafe impl Sync for AbiAction {}
+
+impl Action for AbiAction {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        self.aliases.clone()
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        self.identifier.clone()
+    }
+    fn execute(&self, state: &GreathelmState) -> Result<(), GreathelmError> {
+        let text = manifest_text(&state.manifest)?;
+        let args: Vec<CString> = state
+            .cli_args
+            .iter()
+            .map(|a| CString::new(a.as_str()).unwrap_or_default())
+            .collect();
+        let argv: Vec<*const c_char> = args.iter().map(|a| a.as_ptr()).collect();
+        let _inside = self.lock.enter();
+        if (self.vtable.execute)(
+            self.vtable.userdata,
+            text.as_ptr(),
+            argv.len(),
+            argv.as_ptr(),
+        ) != GHPI_OK
+        {
+            return Err(GreathelmError::ActionError(format!(
+                "Action \x1bc{}\x1br failed.",
+                self.identifier
+            )));
+        }
+        return Ok(());
+    }
+}
+
+/**
+ * Turns the descriptor returned by a C ABI plugin into a GreathelmPlugin whose builders,
+ * generators, actions and callbacks call through to the plugin.
+ *
+ * # Safety
+ * `pl` must be a descriptor returned by the `GHPI_PluginInitC` of a plugin compatible with this
+ * Greathelm (see `check_compatibility`) whose library stays loaded for the rest of the process.
+ * Every pointer in it is dereferenced and every function pointer is called.
+ */
+pub unsafe fn wrap_plugin(pl: &'static GHPIPlugin) -> Result<GreathelmPlugin, String> {
+    let lock = PluginLock::create();
+
+    let mut builders: Vec<Box<dyn ProjectBuilder>> = Vec::new();
+    for b in from_c_slice(pl.builders, pl.builder_count) {
+        builders.push(Box::new(AbiBuilder {
+            name: from_c_string(b.name),
+            identifier: parse_identifier(b.identifier)?,
+            aliases: from_c_string_array(b.aliases),
+            lock,
+            vtable: b,
+        }));
+    }
+
+    let mut generators: Vec<Box<dyn ProjectGenerator>> = Vec::new();
+    for g in from_c_slice(pl.generators, pl.generator_count) {
+        generators.push(Box::new(AbiGenerator {
+            name: from_c_string(g.name),
+            identifier: parse_identifier(g.identifier)?,
+            aliases: from_c_string_array(g.aliases),
+            lock,
+            vtable: g,
+        }));
+    }
+
+    let mut actions: Vec<Box<dyn Action>> = Vec::new();
+    for a in from_c_slice(pl.actions, pl.action_count) {
+        actions.push(Box::new(AbiAction {
+            name: from_c_string(a.name),
+            identifier: parse_identifier(a.identifier)?,
+            aliases: from_c_string_array(a.aliases),
+            lock,
+            vtable: a,
+        }));
+    }
+
+    let mut provides_templates = Vec::new();
+    for t in from_c_string_array(pl.provides_templates) {
+        match NamespacedIdentifier::parse_text(&t) {
+            Some(id) => provides_templates.push(id),
+            None => {
+                return Err(format!(
+                    "\x1bc{t}\x1br is not a valid namespaced identifier."
+                ))
+            }
+        }
+    }
+
+    let mut hooks = Vec::new();
+    for h in from_c_slice(pl.hooks, pl.hook_count) {
+        let point = match HookPoint::from_u32(h.point) {
+            Some(p) => p,
+            None => return Err(format!("Unknown hook point {}.", h.point)),
+        };
+        let run = h.run;
+        let userdata = h.userdata as usize;
+        hooks.push(PluginHook {
+            point,
+            function: Box::leak(Box::new(move |ctx: &HookContext| {
+                let manifest = manifest_text(ctx.manifest)?;
+                let source = optional_c_path(&ctx.source);
+                let output = optional_c_path(&ctx.output);
+                let _inside = lock.enter();
+                let status = run(
+                    userdata as *mut c_void,
+                    point as u32,
+                    manifest.as_ptr(),
+                    source.as_ref().map_or(std::ptr::null(), |s| s.as_ptr()),
+                    output.as_ref().map_or(std::ptr::null(), |s| s.as_ptr()),
+                );
+                if status != GHPI_OK {
+                    return Err(GreathelmError::BuildError(format!(
+                        "A plugin's \x1bc{point}\x1br hook failed."
+                    )));
+                }
+                Ok(())
+            })),
+        });
+    }
+
+    let create_template = pl.create_template;
+    let first_time_setup = pl.first_time_setup;
+    let uninstall = pl.uninstall;
+    let plugin_message = pl.plugin_message;
+    let plugin_text_message = pl.plugin_text_message;
+    // the pointer itself is only passed back to the plugin
+    let userdata = pl.userdata as usize;
+    let identifier = parse_identifier(pl.identifier)?;
+    let plugin_id = identifier.to_string();
+    let plugin_text_id = plugin_id.clone();
+
+    return Ok(GreathelmPlugin {
+        name: from_c_string(pl.name),
+        vendor: from_c_string(pl.vendor),
+        description: from_c_string(pl.description),
+        identifier,
+        version: Version::parse(from_c_string(pl.version)),
+        builders,
+        generators,
+        actions,
+        provides_templates,
+        hooks,
+
+        ghpi_create_template: Box::leak(Box::new(
+            move |id: NamespacedIdentifier, path: PathBuf| match create_template {
+                Some(f) => {
+                    let id = CString::new(id.to_string()).unwrap_or_default();
+                    let path =
+                        CString::new(path.to_string_lossy().into_owned()).unwrap_or_default();
+                    let _inside = lock.enter();
+                    f(userdata as *mut c_void, id.as_ptr(), path.as_ptr())
+                }
+                None => false,
+            },
+        )),
+        ghpi_first_time_setup: Box::leak(Box::new(move || {
+            if let Some(f) = first_time_setup {
+                let _inside = lock.enter();
+                f(userdata as *mut c_void);
+            }
+        })),
+        ghpi_uninstall: Box::leak(Box::new(move || {
+            if let Some(f) = uninstall {
+                let _inside = lock.enter();
+                f(userdata as *mut c_void);
+            }
+        })),
+        ghpi_pluginmessage: Box::leak(Box::new(move |data: &[u8]| {
+            let f = match plugin_message {
+                Some(f) => f,
+                None => return Ok(None),
+            };
+            let mut reply: Option<Vec<u8>> = None;
+            let _inside = lock.enter();
+            let status = f(
+                userdata as *mut c_void,
+                data.as_ptr(),
+                data.len(),
+                collect_reply,
+                &mut reply as *mut Option<Vec<u8>> as *mut c_void,
+            );
+            if status != GHPI_OK {
+                return Err(GreathelmError::PluginError(format!(
+                    "Plugin \x1bc{plugin_id}\x1br failed to handle a message."
+                )));
+            }
+            Ok(reply)
+        })),
+        ghpi_plugintextmessage: Box::leak(Box::new(move |text: &str| {
+            let f = match plugin_text_message {
+                Some(f) => f,
+                None => return Ok(None),
+            };
+            let text = CString::new(text).map_err(|_| {
+                GreathelmError::PluginError("Text messages can't contain NUL characters.".into())
+            })?;
+            let mut reply: Option<Vec<u8>> = None;
+            let _inside = lock.enter();
+            let status = f(
+                userdata as *mut c_void,
+                text.as_ptr(),
+                collect_reply,
+                &mut reply as *mut Option<Vec<u8>> as *mut c_void,
+            );
+            if status != GHPI_OK {
+                return Err(GreathelmError::PluginError(format!(
+                    "Plugin \x1bc{plugin_text_id}\x1br failed to handle a message."
+                )));
+            }
+            Ok(reply_text(reply))
+        })),
+    });
+}
+
+// Plugin side: exporting a Rust GreathelmPlugin through the C ABI.
+
+fn leak_c_string(s: &str) -> *const c_char {
+    return CString::new(s).unwrap_or_default().into_raw();
+}
+
+fn leak_c_string_array<S: AsRef<str>>(items: &[S]) -> *const *const c_char {
+    let mut v: Vec<*const c_char> = items.iter().map(|s| leak_c_string(s.as_ref())).collect();
+    v.push(std::ptr::null());
+    return Box::leak(v.into_boxed_slice()).as_ptr();
+}
+
+fn leak_slice<T>(v: Vec<T>) -> *const T {
+    return Box::leak(v.into_boxed_slice()).as_ptr();
+}
+
+/**
+ * Runs `f`, turning a returned error or a panic into GHPI_ERROR. Panics must not unwind into
+ * Greathelm.
+ */
+fn guarded<F: FnOnce() -> Result<(), GreathelmError>>(f: F) -> i32 {
+    return match std::panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => GHPI_OK,
+        Ok(Err(e)) => {
+            e.report();
+            GHPI_ERROR
+        }
+        Err(_) => GHPI_ERROR,
+    };
+}
+
+unsafe fn parse_manifest(manifest: *const c_char) -> Result<ProjectManifest, GreathelmError> {
+    let mut m = ProjectManifest::new();
+    m.append_from_str(&from_c_string(manifest))?;
+    return Ok(m);
+}
+
+extern "C" fn export_build(userdata: *mut c_void, manifest: *const c_char) -> i32 {
+    let builder = unsafe { &*(userdata as *const Box<dyn ProjectBuilder>) };
+    return guarded(|| builder.build(&unsafe { parse_manifest(manifest) }?));
+}
+
+extern "C" fn export_validate(userdata: *mut c_void, manifest: *const c_char) -> bool {
+    let builder = unsafe { &*(userdata as *const Box<dyn ProjectBuilder>) };
+    let mut valid = false;
+    guarded(|| {
+        valid = builder.validate(&unsafe { parse_manifest(manifest) }?);
+        Ok(())
+    });
+    return valid;
+}
+
+extern "C" fn export_cleanup(userdata: *mut c_void, manifest: *const c_char) -> i32 {
+    let builder = unsafe { &*(userdata as *const Box<dyn ProjectBuilder>) };
+    return guarded(|| builder.cleanup(&unsafe { parse_manifest(manifest) }?));
+}
+
+extern "C" fn export_write_compile_commands(userdata: *mut c_void, manifest: *const c_char) -> i32 {
+    let builder = unsafe { &*(userdata as *const Box<dyn ProjectBuilder>) };
+    let mut supported = true;
+    let status = guarded(|| {
+        supported = builder.write_compile_commands(&unsafe { parse_manifest(manifest) }?)?;
+        Ok(())
+    });
+    if status == GHPI_OK && !supported {
+        return GHPI_UNSUPPORTED;
+    }
+    return status;
+}
+
+extern "C" fn export_generate(userdata: *mut c_void, cwd: *const c_char) -> i32 {
+    let generator = unsafe { &*(userdata as *const Box<dyn ProjectGenerator>) };
+    let cwd = PathBuf::from(unsafe { from_c_string(cwd) });
+    return guarded(|| generator.generate(cwd));
+}
+
+extern "C" fn export_execute(
+    userdata: *mut c_void,
+    manifest: *const c_char,
+    argc: usize,
+    argv: *const *const c_char,
+) -> i32 {
+    let action = unsafe { &*(userdata as *const Box<dyn Action>) };
+    return guarded(|| {
+        let mut cli_args = Vec::new();
+        for a in unsafe { from_c_slice(argv, argc) } {
+            cli_args.push(unsafe { from_c_string(*a) });
+        }
+        // a plugin only sees its own components, the host's aren't reachable through the ABI
+        let state = GreathelmState {
+            builders: vec![],
+            generators: vec![],
+            actions: vec![],
+            manifest: unsafe { parse_manifest(manifest) }?,
+            plugins: vec![],
+            incompatible_plugins: vec![],
+            failed_plugins: vec![],
+            cli_args,
+        };
+        action.execute(&state)
+    });
+}
+
+extern "C" fn export_create_template(
+    userdata: *mut c_void,
+    identifier: *const c_char,
+    path: *const c_char,
+) -> bool {
+    let pl = unsafe { &*(userdata as *const GreathelmPlugin) };
+    let id = match NamespacedIdentifier::parse_text(&unsafe { from_c_string(identifier) }) {
+        Some(id) => id,
+        None => return false,
+    };
+    let path = PathBuf::from(unsafe { from_c_string(path) });
+    return std::panic::catch_unwind(AssertUnwindSafe(|| (pl.ghpi_create_template)(id, path)))
+        .unwrap_or(false);
+}
+
+extern "C" fn export_first_time_setup(userdata: *mut c_void) {
+    let pl = unsafe { &*(userdata as *const GreathelmPlugin) };
+    let _ = std::panic::catch_unwind(AssertUnwindSafe(|| (pl.ghpi_first_time_setup)()));
+}
+
+extern "C" fn export_uninstall(userdata: *mut c_void) {
+    let pl = unsafe { &*(userdata as *const GreathelmPlugin) };
+    let _ = std::panic::catch_unwind(AssertUnwindSafe(|| (pl.ghpi_uninstall)()));
+}
+
+extern "C" fn export_plugin_message(
+    userdata: *mut c_void,
+    data: *const u8,
+    len: usize,
+    reply: GHPIReplyFn,
+    reply_context: *mut c_void,
+) -> i32 {
+    let pl = unsafe { &*(userdata as *const GreathelmPlugin) };
+    let data = unsafe { from_c_slice(data, len) };
+    return guarded(|| {
+        if let Some(r) = (pl.ghpi_pluginmessage)(data)? {
+            reply(reply_context, r.as_ptr(), r.len());
+        }
+        Ok(())
+    });
+}
+
+extern "C" fn export_plugin_text_message(
+    userdata: *mut c_void,
+    text: *const c_char,
+    reply: GHPIReplyFn,
+    reply_context: *mut c_void,
+) -> i32 {
+    let pl = unsafe { &*(userdata as *const GreathelmPlugin) };
+    let text = unsafe { from_c_string(text) };
+    return guarded(|| {
+        if let Some(r) = (pl.ghpi_plugintextmessage)(&text)? {
+            reply(reply_context, r.as_ptr(), r.len());
+        }
+        Ok(())
+    });
+}
+
+extern "C" fn export_hook(
+    userdata: *mut c_void,
+    point: u32,
+    manifest: *const c_char,
+    source: *const c_char,
+    output: *const c_char,
+) -> i32 {
+    let hook = unsafe { &*(userdata as *const PluginHook) };
+    let optional_path = |p: *const c_char| {
+        if p.is_null() {
+            None
+        } else {
+            Some(PathBuf::from(unsafe { from_c_string(p) }))
+        }
+    };
+    return guarded(|| {
+        let manifest = unsafe { parse_manifest(manifest) }?;
+        let context = HookContext {
+            point: HookPoint::from_u32(point).unwrap_or(hook.point),
+            manifest: &manifest,
+            source: optional_path(source),
+            output: optional_path(output),
+        };
+        (hook.function)(&context)
+    });
+}
+
+/**
+ * The host functions, when running inside a plugin. Null in Greathelm itself.
+ */
+static PLUGIN_HOST: AtomicPtr<GHPIHost> = AtomicPtr::new(std::ptr::null_mut());
+
+/**
+ * Remembers the host functions passed to `GHPI_PluginInitC` so that `bus` can send messages
+ * through Greathelm. Called by `greathelm_plugin!`.
+ */
+#[allow(dead_code)] // only called by plugins
+pub fn set_plugin_host(host: *const GHPIHost) {
+    PLUGIN_HOST.store(host as *mut GHPIHost, Ordering::SeqCst);
+}
+
+/**
+ * The host functions if this code runs inside a plugin.
+ */
+pub fn plugin_host() -> Option<&'static GHPIHost> {
+    return unsafe { PLUGIN_HOST.load(Ordering::SeqCst).as_ref() };
+}
+
+fn sent_message_error(status: i32, to: &NamespacedIdentifier) -> Result<(), GreathelmError> {
+    if status != GHPI_OK {
+        return Err(GreathelmError::PluginError(format!(
+            "Sending a message to plugin \x1bc{to}\x1br failed."
+        )));
+    }
+    return Ok(());
+}
+
+/**
+ * Sends a message through the host's send_message. Used by `bus` inside plugins.
+ */
+pub fn send_through_host(
+    host: &GHPIHost,
+    to: &NamespacedIdentifier,
+    data: &[u8],
+) -> Result<Option<Vec<u8>>, GreathelmError> {
+    let id = CString::new(to.to_string()).unwrap_or_default();
+    let mut reply: Option<Vec<u8>> = None;
+    let status = (host.send_message)(
+        id.as_ptr(),
+        data.as_ptr(),
+        data.len(),
+        Some(collect_reply),
+        &mut reply as *mut Option<Vec<u8>> as *mut c_void,
+    );
+    sent_message_error(status, to)?;
+    return Ok(reply);
+}
+
+/**
+ * Sends a text message through the host's send_text_message. Used by `bus` inside plugins.
+ */
+pub fn send_text_through_host(
+    host: &GHPIHost,
+    to: &NamespacedIdentifier,
+    text: &str,
+) -> Result<Option<String>, GreathelmError> {
+    let id = CString::new(to.to_string()).unwrap_or_default();
+    let text = CString::new(text).map_err(|_| {
+        GreathelmError::PluginError("Text messages can't contain NUL characters.".into())
+    })?;
+    let mut reply: Option<Vec<u8>> = None;
+    let status = (host.send_text_message)(
+        id.as_ptr(),
+        text.as_ptr(),
+        Some(collect_reply),
+        &mut reply as *mut Option<Vec<u8>> as *mut c_void,
+    );
+    sent_message_error(status, to)?;
+    return Ok(reply_text(reply));
+}
+
+/**
+ * Exports a Rust GreathelmPlugin through the C ABI. The plugin and everything built for it is
+ * leaked, as the returned descriptor has to live as long as the plugin is loaded.
+ * Usually called through `greathelm_plugin!` rather than directly.
+ */
+#[allow(dead_code)] // only called by plugins
+pub fn export_plugin(mut plugin: GreathelmPlugin) -> *const GHPIPlugin {
+    let mut builders = Vec::new();
+    for b in plugin.builders.drain(..) {
+        builders.push(GHPIBuilder {
+            name: leak_c_string(&b.get_name()),
+            identifier: leak_c_string(&b.get_identifier().to_string()),
+            aliases: leak_c_string_array(&b.get_aliases()),
+            userdata: Box::into_raw(Box::new(b)) as *mut c_void,
+            build: export_build,
+            validate: export_validate,
+            cleanup: export_cleanup,
+            write_compile_commands: Some(export_write_compile_commands),
+        });
+    }
+
+    let mut generators = Vec::new();
+    for g in plugin.generators.drain(..) {
+        generators.push(GHPIGenerator {
+            name: leak_c_string(&g.get_name()),
+            identifier: leak_c_string(&g.get_identifier().to_string()),
+            aliases: leak_c_string_array(&g.get_aliases()),
+            userdata: Box::into_raw(Box::new(g)) as *mut c_void,
+            generate: export_generate,
+        });
+    }
+
+    let mut actions = Vec::new();
+    for a in plugin.actions.drain(..) {
+        actions.push(GHPIAction {
+            name: leak_c_string(&a.get_name()),
+            identifier: leak_c_string(&a.get_identifier().to_string()),
+            aliases: leak_c_string_array(&a.get_aliases()),
+            userdata: Box::into_raw(Box::new(a)) as *mut c_void,
+            execute: export_execute,
+        });
+    }
+
+    let mut hooks = Vec::new();
+    for h in plugin.hooks.drain(..) {
+        hooks.push(GHPIHook {
+            point: h.point as u32,
+            userdata: Box::into_raw(Box::new(h)) as *mut c_void,
+            run: export_hook,
+        });
+    }
+
+    let templates: Vec<String> = plugin
+        .provides_templates
+        .iter()
+        .map(|t| t.to_string())
+        .collect();
+
+    let descriptor = GHPIPlugin {
+        name: leak_c_string(&plugin.name),
+        vendor: leak_c_string(&plugin.vendor),
+        description: leak_c_string(&plugin.description),
+        identifier: leak_c_string(&plugin.identifier.to_string()),
+        version: leak_c_string(&plugin.version.to_string()),
+
+        builder_count: builders.len(),
+        builders: leak_slice(builders),
+        generator_count: generators.len(),
+        generators: leak_slice(generators),
+        action_count: actions.len(),
+        actions: leak_slice(actions),
+        provides_templates: leak_c_string_array(&templates),
+
+        userdata: Box::into_raw(Box::new(plugin)) as *mut c_void,
+        create_template: Some(export_create_template),
+        first_time_setup: Some(export_first_time_setup),
+        uninstall: Some(export_uninstall),
+        plugin_message: Some(export_plugin_message),
+        plugin_text_message: Some(export_plugin_text_message),
+
+        hook_count: hooks.len(),
+        hooks: leak_slice(hooks),
+    };
+
+    return Box::into_raw(Box::new(descriptor));
+}
+
+/**
+ * Copies `s` into a NUL-terminated byte array at compile time. Used by `greathelm_plugin!` to
+ * export GHPI_MIN_HOST_VERSION.
+ */
+#[allow(dead_code)] // only called by plugins
+pub const fn c_string_bytes<const N: usize>(s: &str) -> [u8; N] {
+    let bytes = s.as_bytes();
+    let mut out = [0u8; N];
+    let mut i = 0;
+    while i < bytes.len() && i + 1 < N {
+        out[i] = bytes[i];
+        i += 1;
+    }
+    return out;
+}
+
+/**
+ * Exports the GreathelmPlugin returned by `$init` through the C ABI by defining the
+ * `GHPI_ABI_VERSION`, `GHPI_MIN_HOST_VERSION` and `GHPI_PluginInitC` symbols. Use it once at the
+ * root of a plugin crate. The minimum host version defaults to the greathelm version the plugin is
+ * built against:
+ *
+ * ```ignore
+ * greathelm::greathelm_plugin!(create_plugin);
+ * greathelm::greathelm_plugin!(create_plugin, "0.1.0");
+ * ```
+ */
+#[macro_export]
+macro_rules! greathelm_plugin {
+    ($init:path) => {
+        $crate::greathelm_plugin!($init, $crate::plugin::abi::HOST_VERSION);
+    };
+    ($init:path, $min_host:expr) => {
+        #[no_mangle]
+        pub static GHPI_ABI_VERSION: u32 = $crate::plugin::abi::GHPI_ABI_VERSION;
+
+        #[no_mangle]
+        pub static GHPI_MIN_HOST_VERSION: [u8; $min_host.len() + 1] =
+            $crate::plugin::abi::c_string_bytes($min_host);
+
+        #[no_mangle]
+        pub extern "C" fn GHPI_PluginInitC(
+            host: *const $crate::plugin::abi::GHPIHost,
+        ) -> *const $crate::plugin::abi::GHPIPlugin {
+            $crate::plugin::abi::set_plugin_host(host);
+            $crate::plugin::abi::export_plugin($init())
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestBuilder;
+
+    impl ProjectBuilder for TestBuilder {
+        fn get_name(&self) -> String {
+            "Test".into()
+        }
+        fn get_aliases(&self) -> Vec<String> {
+            vec!["test".into(), "t".into()]
+        }
+        fn get_identifier(&self) -> NamespacedIdentifier {
+            NamespacedIdentifier::parse_text(&"t.plugin:Test".into()).unwrap()
+        }
+        fn build(&self, manifest: &ProjectManifest) -> Result<(), GreathelmError> {
+            if manifest.get_bool_property("fail", false) {
+                return Err(GreathelmError::BuildError("failing on purpose".into()));
+            }
+            if manifest.get_bool_property("panic", false) {
+                panic!("panicking on purpose");
+            }
+            return Ok(());
+        }
+        fn validate(&self, manifest: &ProjectManifest) -> bool {
+            manifest.get_string_property("Project-Type", "") == "test"
+        }
+        fn cleanup(&self, _manifest: &ProjectManifest) -> Result<(), GreathelmError> {
+            Ok(())
+        }
+    }
+
+    fn plugin() -> GreathelmPlugin {
+        return GreathelmPlugin {
+            name: "Test Plugin".into(),
+            vendor: "Tests".into(),
+            description: "Exported and wrapped again.".into(),
+            identifier: NamespacedIdentifier::parse_text(&"t.plugin:Plugin".into()).unwrap(),
+            version: Version::new(1, 2, 3),
+            builders: vec![Box::new(TestBuilder)],
+            generators: vec![],
+            actions: vec![],
+            provides_templates: vec![
+                NamespacedIdentifier::parse_text(&"t.plugin:Template".into()).unwrap(),
+            ],
+            hooks: vec![PluginHook {
+                point: HookPoint::BeforeCompile,
+                function: Box::leak(Box::new(|ctx: &HookContext| {
+                    if ctx.source != Some(PathBuf::from("src/main.c")) {
+                        return Err(GreathelmError::BuildError("wrong source".into()));
+                    }
+                    Ok(())
+                })),
+            }],
+            ghpi_create_template: Box::leak(Box::new(|id: NamespacedIdentifier, _| {
+                id.identifier == "Template"
+            })),
+            ghpi_first_time_setup: Box::leak(Box::new(|| {})),
+            ghpi_uninstall: Box::leak(Box::new(|| {})),
+            ghpi_pluginmessage: Box::leak(Box::new(|data: &[u8]| {
+                if data.is_empty() {
+                    return Ok(None);
+                }
+                Ok(Some(data.iter().rev().copied().collect()))
+            })),
+            ghpi_plugintextmessage: Box::leak(Box::new(|text: &str| {
+                if text == "fail" {
+                    return Err(GreathelmError::PluginError("failing on purpose".into()));
+                }
+                Ok(Some(text.to_uppercase()))
+            })),
+        };
+    }
+
+    #[test]
+    fn wraps_exported_plugins() {
+        let plugin = unsafe { wrap_plugin(&*export_plugin(plugin())) }.unwrap();
+        assert_eq!(plugin.name, "Test Plugin");
+        assert_eq!(plugin.vendor, "Tests");
+        assert_eq!(plugin.identifier.to_string(), "t.plugin:Plugin");
+        assert_eq!(plugin.version, Version::new(1, 2, 3));
+        assert_eq!(plugin.provides_templates.len(), 1);
+        assert!((plugin.ghpi_create_template)(
+            plugin.provides_templates[0].clone(),
+            PathBuf::from(".")
+        ));
+
+        let builder = &plugin.builders[0];
+        assert_eq!(builder.get_name(), "Test");
+        assert_eq!(builder.get_aliases(), ["test", "t"]);
+        assert_eq!(builder.get_identifier().to_string(), "t.plugin:Test");
+
+        let mut manifest = ProjectManifest::new();
+        manifest.append_from_str("Project-Type=test").unwrap();
+        assert!(builder.validate(&manifest));
+        assert!(builder.build(&manifest).is_ok());
+        manifest.append_from_str("fail=true").unwrap();
+        assert!(builder.build(&manifest).is_err());
+        manifest.append_from_str("fail=false\npanic=true").unwrap();
+        assert!(builder.build(&manifest).is_err());
+        assert!(!builder.validate(&ProjectManifest::new()));
+    }
+
+    #[test]
+    fn passes_hooks_and_messages_through() {
+        let plugin = unsafe { wrap_plugin(&*export_plugin(plugin())) }.unwrap();
+        let manifest = ProjectManifest::new();
+        let hook = &plugin.hooks[0];
+        assert_eq!(hook.point, HookPoint::BeforeCompile);
+        let mut context = HookContext {
+            point: HookPoint::BeforeCompile,
+            manifest: &manifest,
+            source: Some(PathBuf::from("src/main.c")),
+            output: None,
+        };
+        assert!((hook.function)(&context).is_ok());
+        context.source = None;
+        assert!((hook.function)(&context).is_err());
+
+        assert_eq!(
+            (plugin.ghpi_pluginmessage)(&[1, 2, 3]).unwrap(),
+            Some(vec![3, 2, 1])
+        );
+        assert_eq!((plugin.ghpi_pluginmessage)(&[]).unwrap(), None);
+        assert_eq!(
+            (plugin.ghpi_plugintextmessage)("ping").unwrap(),
+            Some("PING".into())
+        );
+        assert!((plugin.ghpi_plugintextmessage)("fail").is_err());
+        assert!((plugin.ghpi_plugintextmessage)("nul\0").is_err());
+    }
+}
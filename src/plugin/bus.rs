@@ -0,0 +1,124 @@
+use std::sync::Mutex;
+
+use crate::{error::GreathelmError, identify::NamespacedIdentifier};
+
+use super::{abi, MessageHandler, PluginInfo, TextMessageHandler};
+
+/**
+ * A plugin that can be messaged.
+ */
+struct Endpoint {
+    identifier: NamespacedIdentifier,
+    message: &'static MessageHandler,
+    text_message: &'static TextMessageHandler,
+}
+
+// shared by every thread so compile hooks and parallel builds can send messages too, plugins that
+// aren't thread safe are protected by their wrappers (see `abi::wrap_plugin`)
+static ENDPOINTS: Mutex<Vec<Endpoint>> = Mutex::new(Vec::new());
+
+/**
+ * Makes a plugin reachable by its identifier. Greathelm registers every loaded plugin at startup.
+ */
+pub fn register(info: &PluginInfo) {
+    ENDPOINTS.lock().unwrap().push(Endpoint {
+        identifier: info.identifier.clone(),
+        message: info.ghpi_pluginmessage,
+        text_message: info.ghpi_plugintextmessage,
+    });
+}
+
+fn find(
+    to: &NamespacedIdentifier,
+) -> Result<(&'static MessageHandler, &'static TextMessageHandler), GreathelmError> {
+    // the lock is released before the plugin runs, it may send messages itself
+    let found = ENDPOINTS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|e| &e.identifier == to)
+        .map(|e| (e.message, e.text_message));
+    return match found {
+        Some(f) => Ok(f),
+        None => Err(GreathelmError::PluginError(format!(
+            "No plugin \x1bc{to}\x1br is loaded."
+        ))),
+    };
+}
+
+/**
+ * Sends `data` to the plugin identified by `to` and returns its reply, if it sent one.
+ * Works from the host as well as from inside a plugin (where it goes through the host).
+ */
+pub fn send_message(
+    to: &NamespacedIdentifier,
+    data: &[u8],
+) -> Result<Option<Vec<u8>>, GreathelmError> {
+    if let Some(host) = abi::plugin_host() {
+        return abi::send_through_host(host, to, data);
+    }
+    let (message, _) = find(to)?;
+    return message(data);
+}
+
+/**
+ * Same as send_message but for text.
+ */
+pub fn send_text_message(
+    to: &NamespacedIdentifier,
+    text: &str,
+) -> Result<Option<String>, GreathelmError> {
+    if let Some(host) = abi::plugin_host() {
+        return abi::send_text_through_host(host, to, text);
+    }
+    let (_, text_message) = find(to)?;
+    return text_message(text);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::version::Version;
+
+    fn id(text: &str) -> NamespacedIdentifier {
+        return NamespacedIdentifier::parse_text(&text.to_string()).unwrap();
+    }
+
+    #[test]
+    fn delivers_messages() {
+        register(&PluginInfo {
+            name: "Echo".into(),
+            vendor: "Tests".into(),
+            description: String::new(),
+            identifier: id("t.bus:Echo"),
+            version: Version::new(1, 0, 0),
+            builder_ids: vec![],
+            generator_ids: vec![],
+            action_ids: vec![],
+            provides_templates: vec![],
+            ghpi_create_template: &|_, _| false,
+            ghpi_first_time_setup: &|| {},
+            ghpi_uninstall: &|| {},
+            ghpi_pluginmessage: &|data| Ok(Some(data.iter().map(|b| b + 1).collect())),
+            // answers by messaging itself, which has to work while it is handling a message
+            ghpi_plugintextmessage: &|text| match text.strip_prefix("again ") {
+                Some(rest) => send_text_message(&id("t.bus:Echo"), rest),
+                None if text.is_empty() => Ok(None),
+                None => Ok(Some(text.to_uppercase())),
+            },
+        });
+
+        let echo = id("t.bus:Echo");
+        assert_eq!(send_message(&echo, &[1, 2]).unwrap(), Some(vec![2, 3]));
+        assert_eq!(
+            send_text_message(&echo, "again again hi").unwrap(),
+            Some("HI".into())
+        );
+        assert_eq!(send_text_message(&echo, "").unwrap(), None);
+
+        let e = send_message(&id("t.bus:Missing"), &[]).unwrap_err();
+        assert!(e
+            .message()
+            .contains("No plugin \x1bct.bus:Missing\x1br is loaded."));
+    }
+}
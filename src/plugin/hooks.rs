@@ -0,0 +1,212 @@
+use std::{fmt::Display, path::PathBuf, sync::Mutex};
+
+use crate::{error::GreathelmError, manifest::ProjectManifest};
+
+/**
+ * Points in the build pipeline plugins can hook into.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum HookPoint {
+    /**
+     * Before the project's @Modules are built. The first thing a build does, so this is the place
+     * to generate sources.
+     */
+    BeforeModules = 0,
+    /**
+     * After the project's @Modules have been built and fetched.
+     */
+    AfterModules = 1,
+    /**
+     * Before a single source file is compiled. `source` and `output` are set.
+     */
+    BeforeCompile = 2,
+    /**
+     * After a single source file compiled successfully. `source` and `output` are set.
+     */
+    AfterCompile = 3,
+    /**
+     * After the artifact has been linked (or bundled). `output` is the artifact.
+     */
+    AfterLink = 4,
+    /**
+     * After @Export directives have been handled. The last thing a build does.
+     */
+    AfterExport = 5,
+}
+
+impl HookPoint {
+    pub const ALL: [HookPoint; 6] = [
+        HookPoint::BeforeModules,
+        HookPoint::AfterModules,
+        HookPoint::BeforeCompile,
+        HookPoint::AfterCompile,
+        HookPoint::AfterLink,
+        HookPoint::AfterExport,
+    ];
+
+    /**
+     * The name of this hook point as used by out-of-process plugins (i.e. `after_link`).
+     */
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookPoint::BeforeModules => "before_modules",
+            HookPoint::AfterModules => "after_modules",
+            HookPoint::BeforeCompile => "before_compile",
+            HookPoint::AfterCompile => "after_compile",
+            HookPoint::AfterLink => "after_link",
+            HookPoint::AfterExport => "after_export",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<HookPoint> {
+        HookPoint::ALL.into_iter().find(|p| p.name() == name)
+    }
+
+    pub fn from_u32(value: u32) -> Option<HookPoint> {
+        HookPoint::ALL.into_iter().find(|p| *p as u32 == value)
+    }
+}
+
+impl Display for HookPoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/**
+ * What a hook gets to see. Hooks run in the directory of the project being built.
+ */
+#[allow(dead_code)] // only read by plugins
+pub struct HookContext<'a> {
+    pub point: HookPoint,
+    pub manifest: &'a ProjectManifest,
+    /**
+     * The source file for BeforeCompile and AfterCompile.
+     */
+    pub source: Option<PathBuf>,
+    /**
+     * The object file for BeforeCompile and AfterCompile, the artifact for AfterLink.
+     */
+    pub output: Option<PathBuf>,
+}
+
+/**
+ * A function a plugin wants called at a point in the build pipeline. Compile hooks are called
+ * from the compiler threads so hooks have to be Send + Sync. Returning an error fails the build.
+ */
+pub struct PluginHook {
+    pub point: HookPoint,
+    pub function: &'static (dyn Fn(&HookContext) -> Result<(), GreathelmError> + Send + Sync),
+}
+
+static HOOKS: Mutex<Vec<PluginHook>> = Mutex::new(Vec::new());
+
+/**
+ * Registers a hook. Greathelm registers the hooks of every loaded plugin at startup.
+ */
+pub fn register(hook: PluginHook) {
+    HOOKS.lock().unwrap().push(hook);
+}
+
+/**
+ * Runs every hook registered for `point` in registration order, stopping at the first error.
+ */
+pub fn run_hooks(
+    point: HookPoint,
+    manifest: &ProjectManifest,
+    source: Option<PathBuf>,
+    output: Option<PathBuf>,
+) -> Result<(), GreathelmError> {
+    // don't hold the lock while the hooks run, they may take a while
+    let functions: Vec<_> = HOOKS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|h| h.point == point)
+        .map(|h| h.function)
+        .collect();
+    if functions.is_empty() {
+        return Ok(());
+    }
+
+    let context = HookContext {
+        point,
+        manifest,
+        source,
+        output,
+    };
+    for f in functions {
+        f(&context)?;
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static CALLS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    #[test]
+    fn names_hook_points() {
+        for point in HookPoint::ALL {
+            assert_eq!(HookPoint::from_name(point.name()), Some(point));
+            assert_eq!(HookPoint::from_u32(point as u32), Some(point));
+        }
+        assert_eq!(HookPoint::AfterLink.to_string(), "after_link");
+        assert_eq!(HookPoint::from_name("after-link"), None);
+        assert_eq!(HookPoint::from_u32(99), None);
+    }
+
+    #[test]
+    fn runs_hooks_in_order() {
+        register(PluginHook {
+            point: HookPoint::AfterExport,
+            function: &|ctx| {
+                CALLS.lock().unwrap().push(format!(
+                    "first {}",
+                    ctx.manifest.get_string_property("Project-Name", "")
+                ));
+                if ctx.manifest.get_bool_property("fail-hook", false) {
+                    return Err(GreathelmError::BuildError("hook failed".into()));
+                }
+                Ok(())
+            },
+        });
+        register(PluginHook {
+            point: HookPoint::AfterExport,
+            function: &|ctx| {
+                CALLS
+                    .lock()
+                    .unwrap()
+                    .push(format!("second {:?}", ctx.output));
+                Ok(())
+            },
+        });
+
+        let mut manifest = ProjectManifest::new();
+        manifest.append_from_str("Project-Name=app").unwrap();
+        run_hooks(HookPoint::BeforeModules, &manifest, None, None).unwrap();
+        assert!(CALLS.lock().unwrap().is_empty());
+
+        run_hooks(
+            HookPoint::AfterExport,
+            &manifest,
+            None,
+            Some(PathBuf::from("build/app")),
+        )
+        .unwrap();
+        assert_eq!(
+            *CALLS.lock().unwrap(),
+            ["first app", "second Some(\"build/app\")"]
+        );
+
+        // the first error stops the rest
+        CALLS.lock().unwrap().clear();
+        manifest.append_from_str("fail-hook=true").unwrap();
+        let e = run_hooks(HookPoint::AfterExport, &manifest, None, None).unwrap_err();
+        assert_eq!(e.message(), "hook failed");
+        assert_eq!(*CALLS.lock().unwrap(), ["first app"]);
+    }
+}
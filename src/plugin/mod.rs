@@ -1,14 +1,41 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    ffi::{c_char, CStr},
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use crate::{
-    action::Action, builder::ProjectBuilder, config, generator::ProjectGenerator,
-    identify::NamespacedIdentifier, term::*, version::Version,
+    action::Action, builder::ProjectBuilder, config, error::GreathelmError,
+    generator::ProjectGenerator, identify::NamespacedIdentifier, term::*, version::Version,
 };
 
+pub mod abi;
+pub mod bus;
+pub mod hooks;
+pub mod package;
+pub mod require;
+pub mod stdio;
+
+use hooks::PluginHook;
+
+/**
+ * Handles a message sent to a plugin and optionally replies.
+ */
+pub type MessageHandler = dyn Fn(&[u8]) -> Result<Option<Vec<u8>>, GreathelmError> + Send + Sync;
+/**
+ * Handles a text message sent to a plugin and optionally replies.
+ */
+pub type TextMessageHandler = dyn Fn(&str) -> Result<Option<String>, GreathelmError> + Send + Sync;
+
 // this is just here to keep things loaded because libloading automatically
 // unloads them when dropped.
 static mut FORCEKEEPLOAD: Vec<libloading::Library> = Vec::new();
 
+unsafe fn keep_loaded(library: libloading::Library) {
+    FORCEKEEPLOAD.push(library);
+}
+
 /**
  * Greathelm is still alpha software.
  * This interface CAN and WILL change before 1.0.
@@ -65,30 +92,36 @@ pub struct GreathelmPlugin {
      * be asked to create it.
      */
     pub provides_templates: Vec<NamespacedIdentifier>,
+    /**
+     * Functions to call at points in the build pipeline of any project, whichever builder builds
+     * it. Registered globally after plugin init.
+     */
+    pub hooks: Vec<PluginHook>,
 
     // Plugin Function Pointers
     /**
      * Called when the plugin needs to create a template that does not exist.
      */
-    pub ghpi_create_template: &'static dyn Fn(NamespacedIdentifier, PathBuf) -> bool,
+    pub ghpi_create_template:
+        &'static (dyn Fn(NamespacedIdentifier, PathBuf) -> bool + Send + Sync),
     /**
      * Called when the plugin is `greathelm install`ed.
      */
-    pub ghpi_first_time_setup: &'static dyn Fn(),
+    pub ghpi_first_time_setup: &'static (dyn Fn() + Send + Sync),
     /**
      * Called when the plugin is `greathelm uninstall`ed.
      * The plugin should remove all templates and clean up any other files it may have created.
      */
-    pub ghpi_uninstall: &'static dyn Fn(),
+    pub ghpi_uninstall: &'static (dyn Fn() + Send + Sync),
     /**
      * This method entails a message being passed to a plugin from some other portion of the
-     * software.
+     * software (see `bus::send_message`). The plugin may answer with a reply.
      */
-    pub ghpi_pluginmessage: &'static dyn Fn(&[u8]),
+    pub ghpi_pluginmessage: &'static MessageHandler,
     /**
      * Same as ghpi_pluginmessage but explicitly for text rather than arbitrary data.
      */
-    pub ghpi_plugintextmessage: &'static dyn Fn(&str),
+    pub ghpi_plugintextmessage: &'static TextMessageHandler,
 }
 
 impl GreathelmPlugin {
@@ -148,19 +181,33 @@ pub struct PluginInfo {
     pub action_ids: Vec<NamespacedIdentifier>,
     pub provides_templates: Vec<NamespacedIdentifier>,
 
-    pub ghpi_create_template: &'static dyn Fn(NamespacedIdentifier, PathBuf) -> bool,
-    pub ghpi_first_time_setup: &'static dyn Fn(),
-    pub ghpi_uninstall: &'static dyn Fn(),
-    pub ghpi_pluginmessage: &'static dyn Fn(&[u8]),
-    pub ghpi_plugintextmessage: &'static dyn Fn(&str),
+    pub ghpi_create_template:
+        &'static (dyn Fn(NamespacedIdentifier, PathBuf) -> bool + Send + Sync),
+    pub ghpi_first_time_setup: &'static (dyn Fn() + Send + Sync),
+    pub ghpi_uninstall: &'static (dyn Fn() + Send + Sync),
+    pub ghpi_pluginmessage: &'static MessageHandler,
+    pub ghpi_plugintextmessage: &'static TextMessageHandler,
 }
 
 /**
  * Loads all plugins in the (CONFIGROOT)/plugins directory. Called at startup. Do not call past
- * then.
+ * then. Plugins this Greathelm can't load safely and plugins that failed to load are returned
+ * separately.
+ *
+ * With `only` set, just those plugins and the plugins their packages depend on are returned.
+ * Other packages are skipped without running any of their code, plain plugin files have to be
+ * loaded to learn their identifier and are dropped afterwards.
  */
-pub fn load_plugins() -> Vec<GreathelmPlugin> {
+pub fn load_plugins(
+    only: Option<&[NamespacedIdentifier]>,
+) -> (
+    Vec<GreathelmPlugin>,
+    Vec<IncompatiblePlugin>,
+    Vec<FailedPlugin>,
+) {
     let mut plugins = Vec::new();
+    let mut incompatible = Vec::new();
+    let mut failed = Vec::new();
 
     let plugins_dir = PathBuf::from_str(
         format!(
@@ -171,11 +218,26 @@ pub fn load_plugins() -> Vec<GreathelmPlugin> {
     )
     .unwrap();
 
+    let wanted = only.map(|o| with_package_dependencies(&plugins_dir, o));
+
     for plugin_file in plugins_dir.read_dir().unwrap() {
         match plugin_file {
             Ok(f) => unsafe {
-                match load_plugin_rs(f.path()) {
+                if let Some(w) = &wanted {
+                    if package::is_package(&f.path()) {
+                        match package::PluginPackage::read(&f.path()) {
+                            Ok(p) if !w.contains(&p.identifier) => continue,
+                            _ => {}
+                        }
+                    }
+                }
+                match load_plugin(f.path()) {
                     Ok(pl) => {
+                        if let Some(w) = &wanted {
+                            if !w.contains(&pl.identifier) {
+                                continue;
+                            }
+                        }
                         for t in &pl.provides_templates {
                             let tp = crate::template::get_template_path(t);
                             if !tp.exists() {
@@ -187,40 +249,268 @@ pub fn load_plugins() -> Vec<GreathelmPlugin> {
 
                         plugins.push(pl);
                     }
-                    Err(_) => {}
+                    Err(PluginLoadError::Incompatible(compatibility)) => {
+                        incompatible.push(IncompatiblePlugin {
+                            path: f.path(),
+                            compatibility,
+                        });
+                    }
+                    Err(PluginLoadError::Invalid(reason)) => {
+                        failed.push(FailedPlugin {
+                            path: f.path(),
+                            reason,
+                        });
+                    }
                 }
             },
             Err(_) => {}
         }
     }
-    return plugins;
+    return (plugins, incompatible, failed);
 }
 
 /**
- * Loads a plugin using the pure Rust interface.
+ * Adds the @Dependency plugins of the installed packages in `plugins_dir` to `required`,
+ * recursively.
  */
-pub unsafe fn load_plugin_rs(path: PathBuf) -> Result<GreathelmPlugin, String> {
-    let library = libloading::Library::new(format!("{}", path.display()));
-    if library.is_err() {
-        error!(
-            "Failed to load plugin \x1bc{}\x1br. Could not load library.",
+fn with_package_dependencies(
+    plugins_dir: &Path,
+    required: &[NamespacedIdentifier],
+) -> Vec<NamespacedIdentifier> {
+    let packages: Vec<package::PluginPackage> = match plugins_dir.read_dir() {
+        Ok(rd) => rd
+            .filter_map(|e| e.ok())
+            .filter(|e| package::is_package(&e.path()))
+            .filter_map(|e| package::PluginPackage::read(&e.path()).ok())
+            .collect(),
+        Err(_) => vec![],
+    };
+
+    let mut wanted = required.to_vec();
+    let mut i = 0;
+    while i < wanted.len() {
+        if let Some(p) = packages.iter().find(|p| p.identifier == wanted[i]) {
+            for (d, _) in &p.dependencies {
+                if !wanted.contains(d) {
+                    wanted.push(d.clone());
+                }
+            }
+        }
+        i += 1;
+    }
+    return wanted;
+}
+
+/**
+ * Whether this Greathelm can load a plugin.
+ */
+#[derive(Debug, Clone)]
+pub enum PluginCompatibility {
+    Compatible,
+    /**
+     * The plugin was built for another plugin API version. Holds the plugin's version and the
+     * one this Greathelm provides.
+     */
+    ApiMismatch(u32, u32),
+    /**
+     * The plugin requires a newer Greathelm.
+     */
+    HostTooOld(Version),
+    /**
+     * The plugin does not declare which plugin API version it was built for. This is the case for
+     * plugins built for the old pure Rust interface.
+     */
+    Undeclared,
+}
+
+impl Display for PluginCompatibility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Compatible => write!(f, "compatible"),
+            Self::ApiMismatch(v, host) => write!(
+                f,
+                "was built for plugin API version {v} but this Greathelm provides version {host}"
+            ),
+            Self::HostTooOld(v) => write!(
+                f,
+                "requires Greathelm {v} or newer but this is Greathelm {}",
+                host_version()
+            ),
+            Self::Undeclared => write!(
+                f,
+                "does not declare a plugin API version and was likely built for an older Greathelm"
+            ),
+        }
+    }
+}
+
+/**
+ * A plugin in the plugins directory that was not loaded because it is incompatible.
+ */
+pub struct IncompatiblePlugin {
+    pub path: PathBuf,
+    pub compatibility: PluginCompatibility,
+}
+
+/**
+ * A plugin in the plugins directory that failed to load.
+ */
+pub struct FailedPlugin {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/**
+ * Why a plugin could not be loaded. Either way the reason has already been printed.
+ */
+#[derive(Debug)]
+pub enum PluginLoadError {
+    Incompatible(PluginCompatibility),
+    Invalid(String),
+}
+
+/**
+ * The version of this Greathelm as plugins see it.
+ */
+pub fn host_version() -> Version {
+    return Version::parse(abi::HOST_VERSION.into());
+}
+
+/**
+ * Checks the `GHPI_ABI_VERSION` and `GHPI_MIN_HOST_VERSION` symbols of a plugin library without
+ * running any of its code.
+ *
+ * # Safety
+ * The symbols are read with the types Greathelm exports them with, a library exporting symbols
+ * of these names with other types causes undefined behavior.
+ */
+pub unsafe fn check_compatibility(library: &libloading::Library) -> PluginCompatibility {
+    let api_version: libloading::Symbol<*const u32> = match library.get(b"GHPI_ABI_VERSION") {
+        Ok(s) => s,
+        Err(_) => return PluginCompatibility::Undeclared,
+    };
+    let api_version = **api_version;
+    if api_version != abi::GHPI_ABI_VERSION {
+        return PluginCompatibility::ApiMismatch(api_version, abi::GHPI_ABI_VERSION);
+    }
+
+    // the minimum host version is optional
+    if let Ok(min) = library.get::<*const c_char>(b"GHPI_MIN_HOST_VERSION") {
+        let min = Version::parse(CStr::from_ptr(*min).to_string_lossy().into_owned());
+        if host_version() < min {
+            return PluginCompatibility::HostTooOld(min);
+        }
+    }
+
+    return PluginCompatibility::Compatible;
+}
+
+/**
+ * Loads the plugin at `path`. Package directories load the plugin they name (see `package`),
+ * executables are started as out-of-process plugins (see `stdio`) and anything else is loaded as
+ * a library through the C ABI (see `abi`).
+ *
+ * # Safety
+ * Loading a library runs its initialization code and trusts it to implement the C ABI, see
+ * `load_native_plugin`.
+ */
+pub unsafe fn load_plugin(path: PathBuf) -> Result<GreathelmPlugin, PluginLoadError> {
+    if package::is_package(&path) {
+        return load_package(&path);
+    }
+    if stdio::is_stdio_plugin(&path) {
+        return stdio::load_stdio_plugin(&path);
+    }
+    return load_native_plugin(path);
+}
+
+/**
+ * Loads the plugin in the package directory `path`, refusing it if the package requires a newer
+ * Greathelm.
+ *
+ * # Safety
+ * Same as `load_plugin`.
+ */
+pub unsafe fn load_package(path: &Path) -> Result<GreathelmPlugin, PluginLoadError> {
+    let pkg = match package::PluginPackage::read(path) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Plugin package \x1bc{}\x1br is invalid:", path.display());
+            e.report();
+            return Err(PluginLoadError::Invalid(e.to_string()));
+        }
+    };
+    if let Some(min) = pkg.min_host_version {
+        if host_version() < min {
+            let compatibility = PluginCompatibility::HostTooOld(min);
+            warning!(
+                "Refusing to load plugin \x1bc{}\x1br: It {compatibility}.",
+                path.display()
+            );
+            return Err(PluginLoadError::Incompatible(compatibility));
+        }
+    }
+    let library = pkg.library_path();
+    if stdio::is_stdio_plugin(&library) {
+        return stdio::load_stdio_plugin(&library);
+    }
+    return load_native_plugin(library);
+}
+
+/**
+ * Loads a plugin library through the C ABI (see `abi`). Plugins that are incompatible with this
+ * Greathelm are refused before any of their code runs.
+ *
+ * # Safety
+ * The library at `path` runs its initialization code when loaded and must implement the C ABI
+ * of `abi` correctly, nothing it returns can be checked. It is never unloaded.
+ */
+pub unsafe fn load_native_plugin(path: PathBuf) -> Result<GreathelmPlugin, PluginLoadError> {
+    let library = match libloading::Library::new(format!("{}", path.display())) {
+        Ok(l) => l,
+        Err(_) => {
+            error!(
+                "Failed to load plugin \x1bc{}\x1br. Could not load library.",
+                path.display()
+            );
+            return Err(PluginLoadError::Invalid("Could not load library".into()));
+        }
+    };
+
+    let compatibility = check_compatibility(&library);
+    if !matches!(compatibility, PluginCompatibility::Compatible) {
+        warning!(
+            "Refusing to load plugin \x1bc{}\x1br: It {compatibility}.",
             path.display()
         );
-        return Err("Failed to load".into());
+        return Err(PluginLoadError::Incompatible(compatibility));
     }
-    let library = library.unwrap();
-    let init_sym: libloading::Symbol<unsafe fn() -> GreathelmPlugin> =
-        match library.get(b"GHPI_PluginInit") {
-            Ok(s) => s,
-            Err(_) => {
-                error!(
-                    "Loaded library \x1bc{}\x1br is not a Greathelm plugin or it is invalid.",
-                    path.display()
-                );
-                return Err("Failed to load".into());
-            }
-        };
-    let pl = init_sym();
-    FORCEKEEPLOAD.push(library);
+
+    let init_sym: libloading::Symbol<
+        extern "C" fn(*const abi::GHPIHost) -> *const abi::GHPIPlugin,
+    > = match library.get(b"GHPI_PluginInitC") {
+        Ok(s) => s,
+        Err(_) => {
+            error!(
+                "Loaded library \x1bc{}\x1br is not a Greathelm plugin or it is invalid.",
+                path.display()
+            );
+            return Err(PluginLoadError::Invalid("Missing GHPI_PluginInitC".into()));
+        }
+    };
+    let descriptor = init_sym(&abi::HOST);
+    if descriptor.is_null() {
+        error!("Plugin \x1bc{}\x1br failed to initialize.", path.display());
+        return Err(PluginLoadError::Invalid("Failed to initialize".into()));
+    }
+    let pl = match abi::wrap_plugin(&*descriptor) {
+        Ok(pl) => pl,
+        Err(e) => {
+            error!("Plugin \x1bc{}\x1br is invalid: {e}", path.display());
+            return Err(PluginLoadError::Invalid(e));
+        }
+    };
+
+    keep_loaded(library);
     return Ok(pl);
 }
@@ -0,0 +1,581 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    builder::dependency, config, error::GreathelmError, identify::NamespacedIdentifier,
+    manifest::ProjectManifest, template, util, version::Version, version::VersionRange,
+};
+
+/**
+ * The manifest at the root of a plugin package.
+ *
+ * A plugin package is a directory (or a tarball of one) holding a plugin together with the files
+ * it ships. Its Plugin.ghm looks like this:
+ *
+ * ```text
+ * Plugin-Identifier=com.example:Example
+ * Plugin-Version=1.0.0
+ * Plugin-Library=libexample.so
+ * Minimum-Greathelm-Version=0.1.0
+ * @Template com.example:Lang templates/lang
+ * @Script scripts/example.sh
+ * @Config example.ghm
+ * @Dependency com.example:Other@^1.0
+ * ```
+ */
+pub const PACKAGE_MANIFEST: &str = "Plugin.ghm";
+
+/**
+ * Written into an installed package, lists every file installed outside of it.
+ */
+const INSTALL_RECORD: &str = "Installed.ghm";
+
+/**
+ * A plugin package read from its Plugin.ghm.
+ */
+pub struct PluginPackage {
+    /**
+     * The directory holding Plugin.ghm.
+     */
+    pub root: PathBuf,
+    pub identifier: NamespacedIdentifier,
+    pub version: Version,
+    /**
+     * The plugin library or executable, relative to `root`.
+     */
+    pub library: PathBuf,
+    pub min_host_version: Option<Version>,
+    /**
+     * Templates installed into the template store, with their directory relative to `root`.
+     */
+    pub templates: Vec<(NamespacedIdentifier, PathBuf)>,
+    /**
+     * Scripts installed into (CONFIG)/scripts, relative to `root`.
+     */
+    pub scripts: Vec<PathBuf>,
+    /**
+     * Default configuration files installed into (CONFIG) unless they already exist, relative to
+     * `root`.
+     */
+    pub configs: Vec<PathBuf>,
+    /**
+     * Plugins that have to be installed for this one to work.
+     */
+    pub dependencies: Vec<(NamespacedIdentifier, Option<VersionRange>)>,
+}
+
+impl PluginPackage {
+    /**
+     * Reads the package in `root`.
+     */
+    pub fn read(root: &Path) -> Result<PluginPackage, GreathelmError> {
+        let mut manifest = ProjectManifest::new();
+        manifest.read_and_append(&root.join(PACKAGE_MANIFEST))?;
+
+        let required = |key: &str| match manifest.properties.get(key) {
+            Some(v) if !v.trim().is_empty() => Ok(v.trim().to_string()),
+            _ => Err(GreathelmError::PluginError(format!(
+                "Plugin package \x1bc{}\x1br does not specify \x1bc{key}\x1br.",
+                root.display()
+            ))),
+        };
+
+        let identifier = required("Plugin-Identifier")?;
+        let identifier = match NamespacedIdentifier::parse_text(&identifier) {
+            Some(id) => id,
+            None => {
+                return Err(GreathelmError::PluginError(format!(
+                    "\x1bc{identifier}\x1br is not a valid namespaced identifier."
+                )));
+            }
+        };
+        let version = Version::parse(required("Plugin-Version")?);
+        let library = PathBuf::from(required("Plugin-Library")?);
+        let min_host_version = manifest
+            .properties
+            .get("Minimum-Greathelm-Version")
+            .map(|v| Version::parse(v.trim().into()));
+
+        let directive = |name: &str| match manifest.directives.get(name) {
+            Some(d) => d.to_owned(),
+            None => vec![],
+        };
+
+        let mut templates = Vec::new();
+        for t in directive("Template") {
+            let (id, dir) = match t.split_once(" ") {
+                Some((id, dir)) => (id.trim(), dir.trim()),
+                None => (t.trim(), ""),
+            };
+            let id = match NamespacedIdentifier::parse_text(&id.to_string()) {
+                Some(id) => id,
+                None => {
+                    return Err(GreathelmError::PluginError(format!(
+                        "\x1bc{id}\x1br is not a valid namespaced identifier."
+                    )));
+                }
+            };
+            if dir.is_empty() {
+                return Err(GreathelmError::PluginError(format!(
+                    "@Template \x1bc{id}\x1br does not name a directory."
+                )));
+            }
+            templates.push((id, PathBuf::from(dir)));
+        }
+
+        let mut dependencies = Vec::new();
+        for d in directive("Dependency") {
+            dependencies.push(dependency::parse_dependency_notation(d.trim().into())?);
+        }
+
+        let package = PluginPackage {
+            root: root.to_path_buf(),
+            identifier,
+            version,
+            library,
+            min_host_version,
+            templates,
+            scripts: directive("Script").iter().map(PathBuf::from).collect(),
+            configs: directive("Config").iter().map(PathBuf::from).collect(),
+            dependencies,
+        };
+
+        // everything the package names has to be inside of it
+        let mut files = vec![package.library.clone()];
+        files.extend(package.templates.iter().map(|(_, d)| d.clone()));
+        files.extend(package.scripts.clone());
+        files.extend(package.configs.clone());
+        for f in files {
+            if f.is_absolute() || f.components().any(|c| c.as_os_str() == "..") {
+                return Err(GreathelmError::PluginError(format!(
+                    "Plugin package path \x1bc{}\x1br points outside of the package.",
+                    f.display()
+                )));
+            }
+            if !package.root.join(&f).exists() {
+                return Err(GreathelmError::PluginError(format!(
+                    "Plugin package is missing \x1bc{}\x1br.",
+                    f.display()
+                )));
+            }
+        }
+
+        return Ok(package);
+    }
+
+    /**
+     * The full path of the plugin library or executable.
+     */
+    pub fn library_path(&self) -> PathBuf {
+        return self.root.join(&self.library);
+    }
+}
+
+/**
+ * Whether `path` is a plugin package directory.
+ */
+pub fn is_package(path: &Path) -> bool {
+    return path.is_dir() && path.join(PACKAGE_MANIFEST).is_file();
+}
+
+/**
+ * Whether `path` looks like a tarball `tar` can unpack.
+ */
+pub fn is_archive(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    return path.is_file()
+        && [
+            ".tar", ".tar.gz", ".tgz", ".tar.xz", ".txz", ".tar.bz2", ".tbz2",
+        ]
+        .iter()
+        .any(|e| name.ends_with(e));
+}
+
+/**
+ * Unpacks the package tarball `archive` into a temporary directory and returns the package root.
+ * The root is either the top of the archive or its only directory. Remove the directory returned
+ * by `unpack_dir` once done.
+ */
+pub fn unpack(archive: &Path) -> Result<PathBuf, GreathelmError> {
+    let dir = unpack_dir();
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).ok();
+    }
+    config::ensure_dir(dir.clone())?;
+    // tar runs in the temporary directory
+    let archive_path = match archive.canonicalize() {
+        Ok(p) => p,
+        Err(e) => {
+            return Err(GreathelmError::io(
+                format!("Failed to access \x1bc{}\x1br.", archive.display()),
+                e,
+            ))
+        }
+    };
+
+    let output = match duct::cmd("tar", ["-xf".into(), archive_path.into_os_string()])
+        .dir(&dir)
+        .stderr_to_stdout()
+        .stdout_capture()
+        .unchecked()
+        .run()
+    {
+        Ok(o) => o,
+        Err(e) => return Err(GreathelmError::io("Failed to run tar.", e)),
+    };
+    if !output.status.success() {
+        return Err(GreathelmError::PluginError(format!(
+            "Failed to unpack \x1bc{}\x1br:\n{}",
+            archive.display(),
+            String::from_utf8_lossy(&output.stdout).trim()
+        )));
+    }
+
+    if is_package(&dir) {
+        return Ok(dir);
+    }
+    let entries: Vec<PathBuf> = match std::fs::read_dir(&dir) {
+        Ok(rd) => rd.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(e) => return Err(GreathelmError::io("Failed to read the unpacked plugin.", e)),
+    };
+    if entries.len() == 1 && is_package(&entries[0]) {
+        return Ok(entries[0].clone());
+    }
+    return Err(GreathelmError::PluginError(format!(
+        "\x1bc{}\x1br does not contain a {PACKAGE_MANIFEST}.",
+        archive.display()
+    )));
+}
+
+/**
+ * Where `unpack` unpacks tarballs.
+ */
+pub fn unpack_dir() -> PathBuf {
+    return std::env::temp_dir().join(format!("greathelm-plugin-{}", std::process::id()));
+}
+
+/**
+ * The directory a package with `identifier` is installed to.
+ */
+pub fn install_dir(identifier: &NamespacedIdentifier) -> PathBuf {
+    return PathBuf::from_str(
+        format!(
+            "{}/plugins/{}.{}",
+            config::get_config_base_dir().display(),
+            identifier.namespace,
+            identifier.identifier
+        )
+        .as_str(),
+    )
+    .unwrap();
+}
+
+fn copy(from: &Path, to: &Path) -> Result<(), GreathelmError> {
+    if let Some(parent) = to.parent() {
+        config::ensure_dir(parent.to_path_buf())?;
+    }
+    let result = if from.is_dir() {
+        util::copy_dir(from, to, &vec![], false)
+    } else {
+        std::fs::copy(from, to).map(|_| ())
+    };
+    return result.map_err(|e| {
+        GreathelmError::io(
+            format!("Failed to install \x1bc{}\x1br.", from.display()),
+            e,
+        )
+    });
+}
+
+/**
+ * The files recorded as installed by the package installed in `dir`.
+ */
+fn installed_files(dir: &Path) -> Result<Vec<PathBuf>, GreathelmError> {
+    let record_path = dir.join(INSTALL_RECORD);
+    if !record_path.exists() {
+        return Ok(vec![]);
+    }
+    let mut record = ProjectManifest::new();
+    record.read_and_append(&record_path)?;
+    return Ok(record
+        .directives
+        .get("Installed")
+        .unwrap_or(&vec![])
+        .iter()
+        .map(PathBuf::from)
+        .collect());
+}
+
+/**
+ * Templates and scripts `package` would install over ones that already exist and weren't
+ * installed by (an installed version of) the same package.
+ */
+pub fn find_conflicts(package: &PluginPackage) -> Result<Vec<PathBuf>, GreathelmError> {
+    let owned = installed_files(&install_dir(&package.identifier))?;
+    let config_dir = config::get_config_base_dir();
+    let mut destinations: Vec<PathBuf> = package
+        .templates
+        .iter()
+        .map(|(id, _)| template::get_template_path(id))
+        .collect();
+    for s in &package.scripts {
+        destinations.push(
+            config_dir
+                .join("scripts")
+                .join(s.file_name().unwrap_or_default()),
+        );
+    }
+    return Ok(destinations
+        .into_iter()
+        .filter(|d| d.exists() && !owned.contains(d))
+        .collect());
+}
+
+/**
+ * Installs `package`: copies it into the plugins directory and its templates, scripts and
+ * configuration to where Greathelm looks for them. Everything installed outside of the package
+ * directory is recorded so `uninstall` can remove it again.
+ *
+ * Templates and scripts belonging to something else (see `find_conflicts`) are only replaced
+ * with `force`, otherwise nothing is installed.
+ */
+pub fn install(package: &PluginPackage, force: bool) -> Result<PathBuf, GreathelmError> {
+    let conflicts = find_conflicts(package)?;
+    if !conflicts.is_empty() && !force {
+        return Err(conflict_error(&conflicts));
+    }
+
+    let dir = install_dir(&package.identifier);
+    copy(&package.root, &dir)?;
+
+    let mut record = ProjectManifest::new();
+    let mut installed = Vec::new();
+    for (id, src) in &package.templates {
+        let dest = template::get_template_path(id);
+        if dest.exists() {
+            if let Err(e) = std::fs::remove_dir_all(&dest) {
+                return Err(GreathelmError::io(
+                    format!("Failed to replace \x1bc{}\x1br.", dest.display()),
+                    e,
+                ));
+            }
+        }
+        copy(&package.root.join(src), &dest)?;
+        installed.push(dest);
+    }
+    let config_dir = config::get_config_base_dir();
+    for s in &package.scripts {
+        let dest = config_dir
+            .join("scripts")
+            .join(s.file_name().unwrap_or_default());
+        copy(&package.root.join(s), &dest)?;
+        installed.push(dest);
+    }
+    for c in &package.configs {
+        let dest = config_dir.join(c.file_name().unwrap_or_default());
+        // never overwrite configuration the user already has
+        if dest.exists() {
+            continue;
+        }
+        copy(&package.root.join(c), &dest)?;
+        installed.push(dest);
+    }
+
+    record.directives.insert(
+        "Installed".into(),
+        installed.iter().map(|p| p.display().to_string()).collect(),
+    );
+    if let Err(e) = std::fs::write(dir.join(INSTALL_RECORD), record.to_ghm_string()) {
+        return Err(GreathelmError::io(
+            "Failed to record the installed files.",
+            e,
+        ));
+    }
+    return Ok(dir);
+}
+
+/**
+ * The error for installing over `conflicts` without `--force`.
+ */
+pub fn conflict_error(conflicts: &[PathBuf]) -> GreathelmError {
+    let list: Vec<String> = conflicts
+        .iter()
+        .map(|c| format!("  \x1bc{}\x1br", c.display()))
+        .collect();
+    return GreathelmError::ActionError(format!(
+        "Failed installing plugin: It would replace files it doesn't own:\n{}\nPass \x1bc--force\x1br to replace them anyway.",
+        list.join("\n")
+    ));
+}
+
+/**
+ * Removes the package installed in `dir` along with every file it installed elsewhere.
+ */
+pub fn uninstall(dir: &Path) -> Result<(), GreathelmError> {
+    for p in installed_files(dir)? {
+        let result = if p.is_dir() {
+            std::fs::remove_dir_all(&p)
+        } else {
+            std::fs::remove_file(&p)
+        };
+        if let Err(e) = result {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(GreathelmError::io(
+                    format!("Failed to remove \x1bc{}\x1br.", p.display()),
+                    e,
+                ));
+            }
+        }
+    }
+    if let Err(e) = std::fs::remove_dir_all(dir) {
+        return Err(GreathelmError::io(
+            format!("Failed to remove \x1bc{}\x1br.", dir.display()),
+            e,
+        ));
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+     * A directory for the test `name` holding a package with `manifest` as its Plugin.ghm and an
+     * empty `files`.
+     */
+    fn package(name: &str, manifest: &str, files: &[&str]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("greathelm-package-{}-{name}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(PACKAGE_MANIFEST), manifest).unwrap();
+        for f in files {
+            let path = dir.join(f);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        return dir;
+    }
+
+    const MANIFEST: &str = "Plugin-Identifier=t.pkg:Example
+Plugin-Version=1.2.0
+Plugin-Library=libexample.so
+Minimum-Greathelm-Version=0.1.0
+@Template t.pkg:Lang templates/lang
+@Script scripts/example.sh
+@Config example.ghm
+@Dependency t.pkg:Other@^1.0
+";
+
+    #[test]
+    fn reads_packages() {
+        let dir = package(
+            "read",
+            MANIFEST,
+            &[
+                "libexample.so",
+                "templates/lang/Project.ghm",
+                "scripts/example.sh",
+                "example.ghm",
+            ],
+        );
+        assert!(is_package(&dir));
+        let pkg = PluginPackage::read(&dir).unwrap();
+        assert_eq!(pkg.identifier.to_string(), "t.pkg:Example");
+        assert_eq!(pkg.version, Version::new(1, 2, 0));
+        assert_eq!(pkg.library_path(), dir.join("libexample.so"));
+        assert_eq!(pkg.min_host_version, Some(Version::new(0, 1, 0)));
+        assert_eq!(pkg.templates[0].0.to_string(), "t.pkg:Lang");
+        assert_eq!(pkg.templates[0].1, PathBuf::from("templates/lang"));
+        assert_eq!(pkg.scripts, [PathBuf::from("scripts/example.sh")]);
+        assert_eq!(pkg.configs, [PathBuf::from("example.ghm")]);
+        assert_eq!(pkg.dependencies[0].0.to_string(), "t.pkg:Other");
+        assert_eq!(
+            pkg.dependencies[0].1.as_ref().unwrap().to_string(),
+            ">=1.0.0,<2.0.0"
+        );
+        assert!(!is_package(&dir.join("scripts")));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn rejects_invalid_packages() {
+        let cases = [
+            ("Plugin-Version=1.0.0\nPlugin-Library=lib.so", "Plugin-Identifier"),
+            (
+                "Plugin-Identifier=nonamespace\nPlugin-Version=1.0.0\nPlugin-Library=lib.so",
+                "not a valid namespaced identifier",
+            ),
+            (
+                "Plugin-Identifier=t.pkg:A\nPlugin-Version=1.0.0\nPlugin-Library=../lib.so",
+                "points outside of the package",
+            ),
+            (
+                "Plugin-Identifier=t.pkg:A\nPlugin-Version=1.0.0\nPlugin-Library=missing.so",
+                "missing \x1bcmissing.so\x1br",
+            ),
+            (
+                "Plugin-Identifier=t.pkg:A\nPlugin-Version=1.0.0\nPlugin-Library=lib.so\n@Template t.pkg:T",
+                "does not name a directory",
+            ),
+        ];
+        for (i, (manifest, message)) in cases.iter().enumerate() {
+            let dir = package(&format!("invalid{i}"), manifest, &["lib.so"]);
+            let e = PluginPackage::read(&dir).err().unwrap();
+            assert!(e.message().contains(message), "{}", e.message());
+            std::fs::remove_dir_all(&dir).ok();
+        }
+    }
+
+    #[test]
+    fn unpacks_archives() {
+        let dir = package("archive", MANIFEST, &["libexample.so"]);
+        let archive = dir.with_extension("tar");
+        let status = std::process::Command::new("tar")
+            .arg("-cf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(dir.parent().unwrap())
+            .arg(dir.file_name().unwrap())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert!(is_archive(&archive));
+        assert!(!is_archive(&dir));
+
+        // the package is the only directory in the archive
+        let root = unpack(&archive).unwrap();
+        assert_eq!(root, unpack_dir().join(dir.file_name().unwrap()));
+        assert!(root.join("libexample.so").exists());
+
+        std::fs::write(&archive, "not a tarball").unwrap();
+        assert!(unpack(&archive).is_err());
+
+        std::fs::remove_dir_all(unpack_dir()).ok();
+        std::fs::remove_file(&archive).ok();
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn uninstalls_recorded_files() {
+        let dir = package("installed", MANIFEST, &[]);
+        let outside = dir.with_extension("script");
+        std::fs::write(&outside, "").unwrap();
+        std::fs::write(
+            dir.join(INSTALL_RECORD),
+            format!(
+                "@Installed {}\n@Installed {}\n",
+                outside.display(),
+                dir.with_extension("gone").display()
+            ),
+        )
+        .unwrap();
+        assert_eq!(installed_files(&dir).unwrap().len(), 2);
+        uninstall(&dir).unwrap();
+        assert!(!dir.exists());
+        assert!(!outside.exists());
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt::Display;
+
+use crate::{
+    builder::dependency, error::GreathelmError, identify::NamespacedIdentifier,
+    manifest::ProjectManifest, version::VersionRange,
+};
+
+use super::PluginInfo;
+
+/**
+ * A plugin a project needs, from a `@RequirePlugin namespace:Identifier[@range]` directive.
+ */
+#[derive(Debug, Clone)]
+pub struct PluginRequirement {
+    pub identifier: NamespacedIdentifier,
+    pub range: Option<VersionRange>,
+}
+
+impl Display for PluginRequirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.range {
+            Some(r) => write!(f, "{}@{r}", self.identifier),
+            None => write!(f, "{}", self.identifier),
+        }
+    }
+}
+
+/**
+ * Gets the plugins required by the @RequirePlugin directives of `manifest`.
+ */
+pub fn required_plugins(
+    manifest: &ProjectManifest,
+) -> Result<Vec<PluginRequirement>, GreathelmError> {
+    let mut requirements = Vec::new();
+    for r in manifest.directives.get("RequirePlugin").unwrap_or(&vec![]) {
+        let (identifier, range) =
+            dependency::parse_dependency_notation(r.trim().into()).map_err(|e| {
+                GreathelmError::ManifestError(format!("Invalid @RequirePlugin: {}", e.message()))
+            })?;
+        requirements.push(PluginRequirement { identifier, range });
+    }
+    return Ok(requirements);
+}
+
+/**
+ * Checks that every requirement is met by a loaded plugin. The error lists every plugin that is
+ * missing or too old, not just the first one.
+ */
+pub fn check_requirements(
+    requirements: &[PluginRequirement],
+    plugins: &[PluginInfo],
+) -> Result<(), GreathelmError> {
+    let mut problems = Vec::new();
+    for r in requirements {
+        match plugins.iter().find(|p| p.identifier == r.identifier) {
+            None => problems.push(format!(
+                "Plugin \x1bc{r}\x1br is not installed or could not be loaded."
+            )),
+            Some(p) => {
+                if let Some(range) = &r.range {
+                    if !range.matches(&p.version) {
+                        problems.push(format!(
+                            "Plugin \x1bc{r}\x1br is required but version \x1bc{}\x1br is installed.",
+                            p.version
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    if problems.is_empty() {
+        return Ok(());
+    }
+    return Err(GreathelmError::PluginError(format!(
+        "This project requires plugins that are not available:\n{}\n\
+         Install them with \x1bcgreathelm plugininstall\x1br.",
+        problems.join("\n")
+    )));
+}
@@ -0,0 +1,902 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+use crate::{
+    action::Action, builder::ProjectBuilder, error::GreathelmError, generator::ProjectGenerator,
+    identify::NamespacedIdentifier, json::JsonValue, manifest::ProjectManifest,
+    state::GreathelmState, term::*, version::Version,
+};
+
+use super::{
+    abi::PluginLock,
+    bus,
+    hooks::{HookContext, HookPoint, PluginHook},
+    host_version, GreathelmPlugin, PluginCompatibility, PluginLoadError,
+};
+
+/**
+ * Version of the out-of-process plugin protocol implemented by this Greathelm.
+ *
+ * Out-of-process plugins are executables in the plugins directory. Greathelm starts them once per
+ * run and talks to them with one JSON object per line: requests on the plugin's stdin, responses
+ * on its stdout. The plugin's stderr is passed through. A plugin should exit once its stdin is
+ * closed. See docs/Plugin-API.md for the messages.
+ */
+pub const STDIO_PROTOCOL_VERSION: u32 = 1;
+
+/**
+ * How long a plugin gets to answer the handshake.
+ */
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/**
+ * How many seconds a plugin may go without sending anything while handling a call, 0 for no
+ * limit. See `set_call_timeout`.
+ */
+static CALL_TIMEOUT: AtomicU64 = AtomicU64::new(600);
+
+/**
+ * Sets how many seconds an out-of-process plugin may go without sending anything (a log message
+ * counts) while handling a call before it is considered hung and stopped. 0 waits forever.
+ * Greathelm sets this from the `plugin-timeout` property before loading plugins.
+ */
+pub fn set_call_timeout(seconds: u64) {
+    CALL_TIMEOUT.store(seconds, Ordering::SeqCst);
+}
+
+fn call_timeout() -> Option<Duration> {
+    return match CALL_TIMEOUT.load(Ordering::SeqCst) {
+        0 => None,
+        s => Some(Duration::from_secs(s)),
+    };
+}
+
+/**
+ * A running out-of-process plugin.
+ */
+struct Connection {
+    display_name: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: mpsc::Receiver<String>,
+    next_id: u64,
+}
+
+impl Connection {
+    fn send(&mut self, message: &JsonValue) -> Result<(), String> {
+        return match writeln!(self.stdin, "{message}").and_then(|_| self.stdin.flush()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!(
+                "Could not write to plugin \x1bc{}\x1br: {e}",
+                self.display_name
+            )),
+        };
+    }
+
+    /**
+     * Waits for the next message that isn't a log message, printing log messages on the way. A
+     * plugin that sends nothing for `timeout` is stopped.
+     */
+    fn receive(&mut self, timeout: Option<Duration>) -> Result<JsonValue, String> {
+        loop {
+            let line = match timeout {
+                Some(t) => match self.lines.recv_timeout(t) {
+                    Ok(l) => l,
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        let _ = self.child.kill();
+                        return Err(format!(
+                            "Plugin \x1bc{}\x1br did not answer within \x1bc{}\x1br seconds and was stopped.",
+                            self.display_name,
+                            t.as_secs()
+                        ));
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => {
+                        return Err(self.exited_message());
+                    }
+                },
+                None => self.lines.recv().map_err(|_| self.exited_message())?,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let message = match JsonValue::parse(&line) {
+                Ok(m) => m,
+                Err(e) => {
+                    return Err(format!(
+                        "Plugin \x1bc{}\x1br sent invalid JSON: {e}",
+                        self.display_name
+                    ));
+                }
+            };
+            if message.get("type").and_then(|t| t.as_str()) == Some("log") {
+                let text = message.get("text").and_then(|t| t.as_str()).unwrap_or("");
+                for l in text.lines() {
+                    match message.get("level").and_then(|l| l.as_str()) {
+                        Some("warning") => warning!("{l}"),
+                        Some("error") => error!("{l}"),
+                        Some("ok") => ok!("{l}"),
+                        _ => info!("{l}"),
+                    }
+                }
+                continue;
+            }
+            return Ok(message);
+        }
+    }
+
+    fn exited_message(&mut self) -> String {
+        // the output closes right before the process exits, give it a moment to do so
+        for _ in 0..20 {
+            match self.child.try_wait() {
+                Ok(None) => std::thread::sleep(Duration::from_millis(50)),
+                _ => break,
+            }
+        }
+        return match self.child.try_wait() {
+            Ok(Some(status)) => format!(
+                "Plugin \x1bc{}\x1br exited unexpectedly ({status}).",
+                self.display_name
+            ),
+            _ => format!(
+                "Plugin \x1bc{}\x1br closed its output unexpectedly.",
+                self.display_name
+            ),
+        };
+    }
+}
+
+/**
+ * Delivers a message a plugin sent while handling a call and builds the reply to send back.
+ * `data` messages are answered with a byte array, `text` messages with a string.
+ */
+fn forward_message(request: &JsonValue) -> JsonValue {
+    let id = request.get("id").cloned().unwrap_or(JsonValue::Null);
+    let to = request.get("to").and_then(|t| t.as_str()).unwrap_or("");
+    let result = match NamespacedIdentifier::parse_text(&to.to_string()) {
+        None => Err(GreathelmError::PluginError(format!(
+            "\x1bc{to}\x1br is not a valid namespaced identifier."
+        ))),
+        Some(to) => match request.get("text").and_then(|t| t.as_str()) {
+            Some(text) => bus::send_text_message(&to, text)
+                .map(|r| r.map_or(JsonValue::Null, JsonValue::string)),
+            None => {
+                let data = json_bytes(request.get("data").unwrap_or(&JsonValue::Null));
+                bus::send_message(&to, &data).map(|r| r.map_or(JsonValue::Null, |r| bytes_json(&r)))
+            }
+        },
+    };
+    return match result {
+        Ok(value) => JsonValue::object(vec![
+            ("type", JsonValue::string("reply")),
+            ("id", id),
+            ("ok", JsonValue::Bool(true)),
+            ("value", value),
+        ]),
+        Err(e) => JsonValue::object(vec![
+            ("type", JsonValue::string("reply")),
+            ("id", id),
+            ("ok", JsonValue::Bool(false)),
+            ("error", JsonValue::string(e.to_string())),
+        ]),
+    };
+}
+
+fn bytes_json(data: &[u8]) -> JsonValue {
+    return JsonValue::Array(data.iter().map(|b| JsonValue::Number(*b as f64)).collect());
+}
+
+fn json_bytes(value: &JsonValue) -> Vec<u8> {
+    return match value.as_array() {
+        Some(a) => a
+            .iter()
+            .filter_map(|b| b.as_f64())
+            .map(|b| b as u8)
+            .collect(),
+        None => vec![],
+    };
+}
+
+/**
+ * The connection to a plugin, shared by all of its components. Calls are serialized like the
+ * calls into a C ABI plugin (see `abi::PluginLock`): a call waits until calls on other threads are
+ * done, but a message the plugin sends to itself while handling a call (directly or through other
+ * plugins) is delivered to it right away as a nested call.
+ */
+#[derive(Clone)]
+struct SharedConnection {
+    conn: Arc<Mutex<Connection>>,
+    lock: &'static PluginLock,
+}
+
+impl SharedConnection {
+    /**
+     * The connection itself, only locked for a single send or receive so nested calls can use it
+     * while a call waits.
+     */
+    fn get(&self) -> Result<MutexGuard<'_, Connection>, GreathelmError> {
+        return self
+            .conn
+            .lock()
+            .map_err(|_| GreathelmError::PluginError("Plugin connection is unusable.".into()));
+    }
+}
+
+/**
+ * Calls `method` on the plugin and waits for its result (see `set_call_timeout`). An error the
+ * plugin answers with is turned into a GreathelmError by `failed`, not being able to talk to the
+ * plugin is a PluginError. Messages name the plugin.
+ */
+fn call(
+    shared: &SharedConnection,
+    method: &str,
+    params: Vec<(&str, JsonValue)>,
+    failed: fn(String) -> GreathelmError,
+) -> Result<JsonValue, GreathelmError> {
+    let _inside = shared.lock.enter();
+    let id = {
+        let mut conn = shared.get()?;
+        let id = conn.next_id;
+        conn.next_id += 1;
+
+        let mut message = vec![
+            ("type", JsonValue::string("call")),
+            ("id", JsonValue::Number(id as f64)),
+            ("method", JsonValue::string(method)),
+        ];
+        message.extend(params);
+        conn.send(&JsonValue::object(message))
+            .map_err(GreathelmError::PluginError)?;
+        id
+    };
+
+    loop {
+        let response = shared
+            .get()?
+            .receive(call_timeout())
+            .map_err(GreathelmError::PluginError)?;
+        if response.get("type").and_then(|t| t.as_str()) == Some("send") {
+            // the connection isn't locked while the message is delivered, it may be for us
+            let reply = forward_message(&response);
+            shared
+                .get()?
+                .send(&reply)
+                .map_err(GreathelmError::PluginError)?;
+            continue;
+        }
+        if response.get("type").and_then(|t| t.as_str()) != Some("result")
+            || response.get("id").and_then(|i| i.as_f64()) != Some(id as f64)
+        {
+            continue;
+        }
+        if response.get("ok").and_then(|o| o.as_bool()) == Some(true) {
+            return Ok(response.get("value").cloned().unwrap_or(JsonValue::Null));
+        }
+        let error = response
+            .get("error")
+            .and_then(|e| e.as_str())
+            .unwrap_or("Unknown error.");
+        return Err(failed(format!(
+            "Plugin \x1bc{}\x1br: {error}",
+            shared.get()?.display_name
+        )));
+    }
+}
+
+/**
+ * The absolute directory of the project of `manifest`, which isn't always the working directory
+ * (see `ProjectManifest::project_dir`).
+ */
+fn cwd(manifest: &ProjectManifest) -> JsonValue {
+    return match std::fs::canonicalize(manifest.project_dir()) {
+        Ok(p) => JsonValue::string(p.display().to_string()),
+        Err(_) => JsonValue::Null,
+    };
+}
+
+/**
+ * Parameters shared by all calls about a component working on a project.
+ */
+fn project_params(
+    target: &NamespacedIdentifier,
+    manifest: &ProjectManifest,
+) -> Vec<(&'static str, JsonValue)> {
+    return vec![
+        ("target", JsonValue::string(target.to_string())),
+        ("cwd", cwd(manifest)),
+        ("manifest", manifest.to_json()),
+    ];
+}
+
+struct StdioBuilder {
+    name: String,
+    identifier: NamespacedIdentifier,
+    aliases: Vec<String>,
+    compile_commands: bool,
+    conn: SharedConnection,
+}
+
+impl ProjectBuilder for StdioBuilder {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        self.aliases.clone()
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        self.identifier.clone()
+    }
+    fn build(&self, manifest: &ProjectManifest) -> Result<(), GreathelmError> {
+        call(
+            &self.conn,
+            "build",
+            project_params(&self.identifier, manifest),
+            GreathelmError::BuildError,
+        )?;
+        return Ok(());
+    }
+    fn validate(&self, manifest: &ProjectManifest) -> bool {
+        return match call(
+            &self.conn,
+            "validate",
+            project_params(&self.identifier, manifest),
+            GreathelmError::BuildError,
+        ) {
+            Ok(v) => v.as_bool().unwrap_or(false),
+            Err(e) => {
+                e.report();
+                false
+            }
+        };
+    }
+    fn cleanup(&self, manifest: &ProjectManifest) -> Result<(), GreathelmError> {
+        call(
+            &self.conn,
+            "cleanup",
+            project_params(&self.identifier, manifest),
+            GreathelmError::BuildError,
+        )?;
+        return Ok(());
+    }
+    fn write_compile_commands(&self, manifest: &ProjectManifest) -> Result<bool, GreathelmError> {
+        if !self.compile_commands {
+            return Ok(false);
+        }
+        call(
+            &self.conn,
+            "write_compile_commands",
+            project_params(&self.identifier, manifest),
+            GreathelmError::BuildError,
+        )?;
+        return Ok(true);
+    }
+}
+
+struct StdioGenerator {
+    name: String,
+    identifier: NamespacedIdentifier,
+    aliases: Vec<String>,
+    conn: SharedConnection,
+}
+
+impl ProjectGenerator for StdioGenerator {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        self.aliases.clone()
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        self.identifier.clone()
+    }
+    fn generate(&self, cwd: PathBuf) -> Result<(), GreathelmError> {
+        call(
+            &self.conn,
+            "generate",
+            vec![
+                ("target", JsonValue::string(self.identifier.to_string())),
+                ("cwd", JsonValue::string(cwd.display().to_string())),
+            ],
+            GreathelmError::TemplateError,
+        )?;
+        return Ok(());
+    }
+}
+
+struct StdioAction {
+    name: String,
+    identifier: NamespacedIdentifier,
+    aliases: Vec<String>,
+    conn: SharedConnection,
+}
+
+impl Action for StdioAction {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+    fn get_aliases(&self) -> Vec<String> {
+        self.aliases.clone()
+    }
+    fn get_identifier(&self) -> NamespacedIdentifier {
+        self.identifier.clone()
+    }
+    fn execute(&self, state: &GreathelmState) -> Result<(), GreathelmError> {
+        let mut params = project_params(&self.identifier, &state.manifest);
+        params.push(("args", JsonValue::string_array(&state.cli_args)));
+        call(&self.conn, "execute", params, GreathelmError::ActionError)?;
+        return Ok(());
+    }
+}
+
+/**
+ * Parses the `name`, `identifier` and `aliases` every declared component has.
+ */
+fn parse_component(
+    component: &JsonValue,
+) -> Result<(String, NamespacedIdentifier, Vec<String>), String> {
+    let identifier = component
+        .get("identifier")
+        .and_then(|i| i.as_str())
+        .unwrap_or("");
+    let identifier = match NamespacedIdentifier::parse_text(&identifier.to_string()) {
+        Some(id) => id,
+        None => {
+            return Err(format!(
+                "\x1bc{identifier}\x1br is not a valid namespaced identifier."
+            ))
+        }
+    };
+    let name = match component.get("name").and_then(|n| n.as_str()) {
+        Some(n) => n.to_string(),
+        None => identifier.identifier.clone(),
+    };
+    let aliases = match component.get("aliases") {
+        Some(a) => a.as_string_vec(),
+        None => vec![],
+    };
+    return Ok((name, identifier, aliases));
+}
+
+fn components(handshake: &JsonValue, key: &str) -> Vec<JsonValue> {
+    return match handshake.get(key).and_then(|c| c.as_array()) {
+        Some(c) => c.clone(),
+        None => vec![],
+    };
+}
+
+/**
+ * Whether `path` should be started as an out-of-process plugin rather than loaded as a library.
+ */
+pub fn is_stdio_plugin(path: &Path) -> bool {
+    if !path.is_file() {
+        return false;
+    }
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("so") | Some("dylib") | Some("dll") => return false,
+        _ => {}
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        return match std::fs::metadata(path) {
+            Ok(m) => m.permissions().mode() & 0o111 != 0,
+            Err(_) => false,
+        };
+    }
+    #[cfg(not(unix))]
+    {
+        return path.extension().and_then(|e| e.to_str()) == Some("exe");
+    }
+}
+
+/**
+ * Starts the out-of-process plugin at `path` and performs the handshake. The plugin is refused if
+ * it speaks another protocol version or requires a newer Greathelm.
+ */
+pub fn load_stdio_plugin(path: &Path) -> Result<GreathelmPlugin, PluginLoadError> {
+    let mut child = match Command::new(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+    {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Failed to start plugin \x1bc{}\x1br: {e}", path.display());
+            return Err(PluginLoadError::Invalid(e.to_string()));
+        }
+    };
+
+    let stdin = child.stdin.take().unwrap();
+    let stdout = child.stdout.take().unwrap();
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            match line {
+                Ok(l) => {
+                    if tx.send(l).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+
+    let mut conn = Connection {
+        display_name: path.display().to_string(),
+        child,
+        stdin,
+        lines: rx,
+        next_id: 1,
+    };
+
+    let handshake = conn
+        .send(&JsonValue::object(vec![
+            ("type", JsonValue::string("handshake")),
+            ("protocol", JsonValue::Number(STDIO_PROTOCOL_VERSION as f64)),
+            (
+                "host_version",
+                JsonValue::string(host_version().to_string()),
+            ),
+        ]))
+        .and_then(|_| conn.receive(Some(HANDSHAKE_TIMEOUT)));
+    let handshake = match handshake {
+        Ok(h) if h.get("type").and_then(|t| t.as_str()) == Some("handshake") => h,
+        Ok(_) => {
+            let _ = conn.child.kill();
+            error!(
+                "Plugin \x1bc{}\x1br did not answer the handshake.",
+                path.display()
+            );
+            return Err(PluginLoadError::Invalid("No handshake".into()));
+        }
+        Err(e) => {
+            let _ = conn.child.kill();
+            error!("{e}");
+            return Err(PluginLoadError::Invalid(e));
+        }
+    };
+
+    let compatibility = match handshake.get("protocol").and_then(|p| p.as_f64()) {
+        None => PluginCompatibility::Undeclared,
+        Some(p) if p != STDIO_PROTOCOL_VERSION as f64 => {
+            PluginCompatibility::ApiMismatch(p as u32, STDIO_PROTOCOL_VERSION)
+        }
+        Some(_) => match handshake.get("min_host_version").and_then(|v| v.as_str()) {
+            Some(min) if host_version() < Version::parse(min.into()) => {
+                PluginCompatibility::HostTooOld(Version::parse(min.into()))
+            }
+            _ => PluginCompatibility::Compatible,
+        },
+    };
+    if !matches!(compatibility, PluginCompatibility::Compatible) {
+        let _ = conn.child.kill();
+        warning!(
+            "Refusing to load plugin \x1bc{}\x1br: It {compatibility}.",
+            path.display()
+        );
+        return Err(PluginLoadError::Incompatible(compatibility));
+    }
+
+    match wrap_handshake(&handshake, conn) {
+        Ok(pl) => Ok(pl),
+        Err(e) => {
+            error!("Plugin \x1bc{}\x1br is invalid: {e}", path.display());
+            Err(PluginLoadError::Invalid(e))
+        }
+    }
+}
+
+/**
+ * Builds the GreathelmPlugin declared in a handshake, with every component calling into `conn`.
+ */
+fn wrap_handshake(handshake: &JsonValue, conn: Connection) -> Result<GreathelmPlugin, String> {
+    let (name, identifier, _) = parse_component(handshake)?;
+    let mut conn = conn;
+    conn.display_name = identifier.to_string();
+    let conn = SharedConnection {
+        conn: Arc::new(Mutex::new(conn)),
+        lock: PluginLock::create(),
+    };
+
+    let mut builders: Vec<Box<dyn ProjectBuilder>> = Vec::new();
+    for b in components(handshake, "builders") {
+        let (name, identifier, aliases) = parse_component(&b)?;
+        builders.push(Box::new(StdioBuilder {
+            name,
+            identifier,
+            aliases,
+            compile_commands: b
+                .get("compile_commands")
+                .and_then(|c| c.as_bool())
+                .unwrap_or(false),
+            conn: conn.clone(),
+        }));
+    }
+
+    let mut generators: Vec<Box<dyn ProjectGenerator>> = Vec::new();
+    for g in components(handshake, "generators") {
+        let (name, identifier, aliases) = parse_component(&g)?;
+        generators.push(Box::new(StdioGenerator {
+            name,
+            identifier,
+            aliases,
+            conn: conn.clone(),
+        }));
+    }
+
+    let mut actions: Vec<Box<dyn Action>> = Vec::new();
+    for a in components(handshake, "actions") {
+        let (name, identifier, aliases) = parse_component(&a)?;
+        actions.push(Box::new(StdioAction {
+            name,
+            identifier,
+            aliases,
+            conn: conn.clone(),
+        }));
+    }
+
+    let mut provides_templates = Vec::new();
+    if let Some(t) = handshake.get("templates") {
+        for t in t.as_string_vec() {
+            match NamespacedIdentifier::parse_text(&t) {
+                Some(id) => provides_templates.push(id),
+                None => {
+                    return Err(format!(
+                        "\x1bc{t}\x1br is not a valid namespaced identifier."
+                    ))
+                }
+            }
+        }
+    }
+
+    let mut hooks = Vec::new();
+    if let Some(h) = handshake.get("hooks") {
+        for name in h.as_string_vec() {
+            let point = match HookPoint::from_name(&name) {
+                Some(p) => p,
+                None => return Err(format!("Unknown hook point \x1bc{name}\x1br.")),
+            };
+            let c = conn.clone();
+            hooks.push(PluginHook {
+                point,
+                function: Box::leak(Box::new(move |ctx: &HookContext| {
+                    let path = |p: &Option<PathBuf>| match p {
+                        Some(p) => JsonValue::string(p.display().to_string()),
+                        None => JsonValue::Null,
+                    };
+                    call(
+                        &c,
+                        "hook",
+                        vec![
+                            ("point", JsonValue::string(point.name())),
+                            ("cwd", cwd(ctx.manifest)),
+                            ("manifest", ctx.manifest.to_json()),
+                            ("source", path(&ctx.source)),
+                            ("output", path(&ctx.output)),
+                        ],
+                        GreathelmError::BuildError,
+                    )?;
+                    Ok(())
+                })),
+            });
+        }
+    }
+
+    let text = |key: &str| {
+        handshake
+            .get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+
+    let c = conn.clone();
+    let create_template = move |id: NamespacedIdentifier, path: PathBuf| match call(
+        &c,
+        "create_template",
+        vec![
+            ("identifier", JsonValue::string(id.to_string())),
+            ("path", JsonValue::string(path.display().to_string())),
+        ],
+        GreathelmError::TemplateError,
+    ) {
+        Ok(v) => v.as_bool().unwrap_or(true),
+        Err(e) => {
+            e.report();
+            false
+        }
+    };
+    let c = conn.clone();
+    let first_time_setup = move || {
+        if let Err(e) = call(&c, "first_time_setup", vec![], GreathelmError::PluginError) {
+            e.report();
+        }
+    };
+    let c = conn.clone();
+    let uninstall = move || {
+        if let Err(e) = call(&c, "uninstall", vec![], GreathelmError::PluginError) {
+            e.report();
+        }
+    };
+    let c = conn.clone();
+    let plugin_message = move |data: &[u8]| {
+        let reply = call(
+            &c,
+            "plugin_message",
+            vec![("data", bytes_json(data))],
+            GreathelmError::PluginError,
+        )?;
+        return match reply {
+            JsonValue::Null => Ok(None),
+            r => Ok(Some(json_bytes(&r))),
+        };
+    };
+    let c = conn.clone();
+    let plugin_text_message = move |message: &str| {
+        let reply = call(
+            &c,
+            "plugin_text_message",
+            vec![("text", JsonValue::string(message))],
+            GreathelmError::PluginError,
+        )?;
+        return Ok(reply.as_str().map(|r| r.to_string()));
+    };
+
+    return Ok(GreathelmPlugin {
+        name,
+        vendor: text("vendor"),
+        description: text("description"),
+        identifier,
+        version: Version::parse(text("version")),
+        builders,
+        generators,
+        actions,
+        provides_templates,
+        hooks,
+
+        ghpi_create_template: Box::leak(Box::new(create_template)),
+        ghpi_first_time_setup: Box::leak(Box::new(first_time_setup)),
+        ghpi_uninstall: Box::leak(Box::new(uninstall)),
+        ghpi_pluginmessage: Box::leak(Box::new(plugin_message)),
+        ghpi_plugintextmessage: Box::leak(Box::new(plugin_text_message)),
+    });
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /**
+     * A plugin answering handshakes and calls to its builder. `build` fails if the project sets
+     * `fail` and hangs if it sets `hang`.
+     */
+    const PLUGIN: &str = r#"#!/bin/sh
+while IFS= read -r line; do
+    id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+    case "$line" in
+    *'"type":"handshake"'*)
+        echo '{"type":"handshake","protocol":1,"name":"Test","identifier":"t.stdio:Test","version":"1.2.0","builders":[{"identifier":"t.stdio:Builder","aliases":["sb"]}],"hooks":["after_link"]}' ;;
+    *'"method":"validate"'*)
+        echo '{"type":"result","id":'"$id"',"ok":true,"value":true}' ;;
+    *'"method":"plugin_text_message"'*)
+        echo '{"type":"result","id":'"$id"',"ok":true,"value":"pong"}' ;;
+    *'"method":"build"'*)
+        case "$line" in
+        *'"message-self":"true"'*)
+            echo '{"type":"send","id":1,"to":"t.stdio:Test","text":"ping"}'
+            IFS= read -r call
+            nested=$(printf '%s' "$call" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+            echo '{"type":"result","id":'"$nested"',"ok":true,"value":"pong"}'
+            IFS= read -r reply
+            case "$reply" in
+            *'"value":"pong"'*) echo '{"type":"result","id":'"$id"',"ok":true}' ;;
+            *) echo '{"type":"result","id":'"$id"',"ok":false,"error":"no reply"}' ;;
+            esac ;;
+        *'"fail":"true"'*)
+            echo '{"type":"result","id":'"$id"',"ok":false,"error":"broken"}' ;;
+        *'"hang":"true"'*)
+            sleep 2 ;;
+        *)
+            echo '{"type":"log","level":"info","text":"building"}'
+            echo ''
+            echo '{"type":"result","id":999,"ok":true}'
+            echo '{"type":"result","id":'"$id"',"ok":true,"value":null}' ;;
+        esac ;;
+    esac
+done
+"#;
+
+    fn write_plugin(name: &str, script: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path =
+            std::env::temp_dir().join(format!("greathelm-stdio-{}-{name}", std::process::id()));
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        return path;
+    }
+
+    fn manifest(text: &str) -> ProjectManifest {
+        let mut manifest = ProjectManifest::new();
+        manifest.append_from_str(text).unwrap();
+        return manifest;
+    }
+
+    #[test]
+    fn talks_to_plugins() {
+        let path = write_plugin("plugin", PLUGIN);
+        assert!(is_stdio_plugin(&path));
+        let plugin = match load_stdio_plugin(&path) {
+            Ok(p) => p,
+            Err(e) => panic!("{e:?}"),
+        };
+        assert_eq!(plugin.identifier.to_string(), "t.stdio:Test");
+        assert_eq!(plugin.version, Version::new(1, 2, 0));
+        assert_eq!(plugin.hooks[0].point, HookPoint::AfterLink);
+
+        let builder = &plugin.builders[0];
+        assert_eq!(builder.get_aliases(), ["sb"]);
+        assert!(builder.validate(&manifest("")));
+        assert!(builder.build(&manifest("")).is_ok());
+        match builder.build(&manifest("fail=true")) {
+            Err(GreathelmError::BuildError(m)) => assert!(m.ends_with(": broken")),
+            _ => panic!("build did not fail"),
+        }
+        assert_eq!(
+            (plugin.ghpi_plugintextmessage)("ping").unwrap(),
+            Some("pong".into())
+        );
+
+        // messages to itself while building are delivered, other threads wait their turn
+        bus::register(&plugin.as_info());
+        std::thread::scope(|s| {
+            let other = s.spawn(|| builder.build(&manifest("")).is_ok());
+            assert!(builder.build(&manifest("message-self=true")).is_ok());
+            assert!(other.join().unwrap());
+        });
+
+        // hung plugins are stopped
+        set_call_timeout(1);
+        match builder.build(&manifest("hang=true")) {
+            Err(GreathelmError::PluginError(m)) => assert!(m.contains("was stopped")),
+            _ => panic!("build did not time out"),
+        }
+        set_call_timeout(600);
+        assert!(matches!(
+            builder.build(&manifest("")),
+            Err(GreathelmError::PluginError(_))
+        ));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn refuses_incompatible_plugins() {
+        let path = write_plugin(
+            "incompatible",
+            "#!/bin/sh\nread line\necho '{\"type\":\"handshake\",\"protocol\":99,\"identifier\":\"t.stdio:Old\"}'\n",
+        );
+        assert!(matches!(
+            load_stdio_plugin(&path),
+            Err(PluginLoadError::Incompatible(
+                PluginCompatibility::ApiMismatch(99, STDIO_PROTOCOL_VERSION)
+            ))
+        ));
+        std::fs::remove_file(&path).ok();
+
+        let path = write_plugin("silent", "#!/bin/sh\nread line\necho 'not json'\n");
+        assert!(matches!(
+            load_stdio_plugin(&path),
+            Err(PluginLoadError::Invalid(_))
+        ));
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+use crate::{config, error::GreathelmError, ibht};
+
+/**
+ * A program looked up on this machine.
+ */
+#[derive(Clone, Debug)]
+pub struct Tool {
+    /**
+     * The program as it was asked for, like `cc` or `/opt/cross/bin/aarch64-linux-gnu-gcc`.
+     */
+    pub program: String,
+    /**
+     * Where the program was found. None if it could not be found.
+     */
+    pub path: Option<PathBuf>,
+    /**
+     * The first line the program prints for `--version`, if it prints anything.
+     */
+    pub version: Option<String>,
+}
+
+impl Tool {
+    pub fn is_available(&self) -> bool {
+        return self.path.is_some();
+    }
+}
+
+/**
+ * A program a builder needs, with what it is needed for (`C compiler`, `archiver`, ...).
+ */
+#[derive(Clone, Debug)]
+pub struct ToolRequirement {
+    pub role: String,
+    pub program: String,
+}
+
+impl ToolRequirement {
+    pub fn create(role: &str, program: &str) -> Self {
+        return Self {
+            role: role.into(),
+            program: program.into(),
+        };
+    }
+}
+
+/**
+ * Tools probed by this process.
+ */
+static PROBED: Mutex<Vec<Tool>> = Mutex::new(Vec::new());
+
+/**
+ * Where the versions of probed tools are cached between runs.
+ */
+pub fn get_cache_path() -> PathBuf {
+    return config::get_data_base_dir().join("Tools.ghd");
+}
+
+/**
+ * Looks up `program` and its version. Programs containing a `/` are taken as paths, others are
+ * searched for in `PATH`.
+ *
+ * Results are cached for the rest of the process, and versions are cached in Tools.ghd (see
+ * `get_cache_path`) by the path and modification time of the program so `--version` only runs
+ * again once a tool changes.
+ */
+pub fn probe(program: &str) -> Tool {
+    if let Some(t) = PROBED.lock().unwrap().iter().find(|t| t.program == program) {
+        return t.clone();
+    }
+
+    let path = find_program(program);
+    let version = path.as_ref().and_then(|p| cached_version(p));
+    let tool = Tool {
+        program: program.into(),
+        path,
+        version,
+    };
+    PROBED.lock().unwrap().push(tool.clone());
+    return tool;
+}
+
+/**
+ * Probes every tool in `requirements`. Fails if any of them is missing, naming all that are.
+ */
+pub fn require(requirements: &[ToolRequirement]) -> Result<Vec<Tool>, GreathelmError> {
+    let mut tools = Vec::new();
+    let mut missing = Vec::new();
+    for r in requirements {
+        let tool = probe(&r.program);
+        if !tool.is_available() {
+            missing.push(format!(
+                "Could not find the {} \x1bc{}\x1br.",
+                r.role, r.program
+            ));
+        }
+        tools.push(tool);
+    }
+    if missing.is_empty() {
+        return Ok(tools);
+    }
+    return Err(GreathelmError::BuildError(format!(
+        "{}\nInstall the missing tools or point the project at others, \
+         \x1bcgreathelm doctor\x1br shows what was found.",
+        missing.join("\n")
+    )));
+}
+
+/**
+ * Finds the executable `program` runs.
+ */
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    if program.contains("/") {
+        let path = PathBuf::from(program);
+        return if is_executable(&path) {
+            Some(path)
+        } else {
+            None
+        };
+    }
+    let paths = std::env::var_os("PATH")?;
+    return std::env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|p| is_executable(p));
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    return match std::fs::metadata(path) {
+        Ok(m) => m.is_file() && m.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    };
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    return path.is_file();
+}
+
+/**
+ * The version of the program at `path`, from the cache if it hasn't changed since it was cached.
+ */
+fn cached_version(path: &Path) -> Option<String> {
+    let modified = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs().to_string())
+        .unwrap_or_default();
+    let key = path.display().to_string();
+    let cache_path = get_cache_path();
+    let mut cache: HashMap<String, String> = ibht::read_hashtable(&cache_path).unwrap_or_default();
+
+    // <modification time>;<version>
+    if let Some((time, version)) = cache.get(&key).and_then(|v| v.split_once(";")) {
+        if time == modified && !modified.is_empty() {
+            return if version.is_empty() {
+                None
+            } else {
+                Some(version.to_string())
+            };
+        }
+    }
+
+    let version = run_version(path);
+    cache.insert(
+        key,
+        format!("{modified};{}", version.clone().unwrap_or_default()),
+    );
+    // the cache only saves time, not being able to write it is fine
+    ibht::write_hashtable(&cache_path, &cache).ok();
+    return version;
+}
+
+fn run_version(path: &Path) -> Option<String> {
+    let output = Command::new(path)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    return text
+        .lines()
+        .map(|l| l.trim())
+        .find(|l| !l.is_empty())
+        .map(|l| l.to_string());
+}
@@ -0,0 +1,360 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    error::GreathelmError,
+    manifest::{ProjectManifest, SourceLocation},
+    target,
+};
+
+/**
+ * The profile used when no `profile` is set.
+ */
+pub const DEFAULT_PROFILE: &str = "debug";
+
+/**
+ * Settings of the built-in profiles. @Profile directives of the same name are applied on top.
+ * The default profile changes nothing, so a plain build compiles exactly like it did before
+ * profiles existed and the objects it keeps in `build/` stay valid.
+ */
+fn builtin_settings(name: &str) -> Option<Vec<&'static str>> {
+    return match name {
+        DEFAULT_PROFILE => Some(vec![]),
+        "release" => Some(vec!["Compiler-Opt-Level=3", "debug-info=false"]),
+        _ => None,
+    };
+}
+
+/**
+ * The name of the active build profile.
+ */
+pub fn active_profile(manifest: &ProjectManifest) -> String {
+    return manifest.get_string_property("profile", DEFAULT_PROFILE);
+}
+
+/**
+ * Every profile that can be selected: the built-in ones and those declared with @Profile.
+ */
+pub fn profile_names(manifest: &ProjectManifest) -> Vec<String> {
+    let mut names: Vec<String> = vec!["debug".into(), "release".into()];
+    for name in declared_names(manifest, "Profile") {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    return names;
+}
+
+/**
+ * Applies the settings of the active profile to `manifest`. Called once the manifests are read
+ * and before command line properties are set, so those still override a profile.
+ *
+ * A profile is declared with `@Profile <name> Key=value Key+=value ...`.
+ */
+pub fn apply(manifest: &mut ProjectManifest) -> Result<(), GreathelmError> {
+    let profile = active_profile(manifest);
+    if !is_valid_name(&profile) {
+        return Err(GreathelmError::ManifestError(format!(
+            "\x1bc{profile}\x1br is not a valid profile name."
+        )));
+    }
+
+    let mut settings: Vec<(String, SourceLocation)> = match builtin_settings(&profile) {
+        Some(s) => s
+            .into_iter()
+            .map(|s| (s.to_string(), SourceLocation::create("built-in profile", 0)))
+            .collect(),
+        None => vec![],
+    };
+    match declared_settings(manifest, "Profile", &profile)? {
+        Some(s) => settings.extend(s),
+        None if builtin_settings(&profile).is_none() => {
+            return Err(GreathelmError::ManifestError(format!(
+                "Unknown profile \x1bc{profile}\x1br. Available profiles: {}",
+                profile_names(manifest).join(", ")
+            )));
+        }
+        None => {}
+    }
+    assign_settings(manifest, settings);
+    return Ok(());
+}
+
+/**
+ * Declares the active profile and target from `@Profile:` and `@Target:` command line arguments
+ * when nothing else does. Called before `apply`. Nested builds get the declarations of the
+ * project building them this way (see `nested_build_args`), so a module can be built with a
+ * profile only its parent declares while its own declarations still take precedence.
+ */
+pub fn declare_from_cli_args(manifest: &mut ProjectManifest, args: &[String]) {
+    let active = [
+        ("Profile", active_profile(manifest)),
+        ("Target", target::active_target(manifest)),
+    ];
+    for (directive, name) in active {
+        if builtin_settings(&name).is_some()
+            || name == target::HOST_TARGET
+            || declared_names(manifest, directive).contains(&name)
+        {
+            continue;
+        }
+        for arg in args {
+            let value = match arg.strip_prefix(&format!("@{directive}:")) {
+                Some(v) => v,
+                None => continue,
+            };
+            if value.split_whitespace().next() == Some(name.as_str()) {
+                manifest.push_directive(directive, value.into(), SourceLocation::command_line());
+            }
+        }
+    }
+}
+
+/**
+ * The command line arguments that build a nested project (a module or a store dependency) with
+ * the profile and target of `manifest`, along with their declarations in case the nested project
+ * doesn't declare them itself.
+ */
+pub fn nested_build_args(manifest: &ProjectManifest) -> Vec<String> {
+    let profile = active_profile(manifest);
+    let target = target::active_target(manifest);
+    let mut args = vec![format!("--profile={profile}"), format!("--target={target}")];
+    for (directive, name) in [("Profile", &profile), ("Target", &target)] {
+        for d in manifest.directives.get(directive).unwrap_or(&vec![]) {
+            if d.split_whitespace().next() == Some(name.as_str()) {
+                args.push(format!("@{directive}:{d}"));
+            }
+        }
+    }
+    return args;
+}
+
+/**
+ * Whether `name` can name a profile or target, it becomes part of the build directory.
+ */
+pub fn is_valid_name(name: &str) -> bool {
+    return !name.is_empty()
+        && !name.starts_with(".")
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+}
+
+/**
+ * The names declared by `@<directive> <name> ...` directives, in order and without duplicates.
+ */
+pub fn declared_names(manifest: &ProjectManifest, directive: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for d in manifest.directives.get(directive).unwrap_or(&vec![]) {
+        if let Some(name) = d.split_whitespace().next() {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.into());
+            }
+        }
+    }
+    return names;
+}
+
+/**
+ * The `Key=value` settings of every `@<directive> <name> ...` directive, with where they were
+ * declared. None if no such directive declares `name`.
+ */
+pub fn declared_settings(
+    manifest: &ProjectManifest,
+    directive: &str,
+    name: &str,
+) -> Result<Option<Vec<(String, SourceLocation)>>, GreathelmError> {
+    let mut settings = Vec::new();
+    let mut declared = false;
+    for (i, d) in manifest
+        .directives
+        .get(directive)
+        .unwrap_or(&vec![])
+        .iter()
+        .enumerate()
+    {
+        let mut words = d.split_whitespace();
+        if words.next() != Some(name) {
+            continue;
+        }
+        declared = true;
+        let location = match manifest.directive_location(directive, i) {
+            Some(l) => l.clone(),
+            None => SourceLocation::create(&format!("@{directive}"), 0),
+        };
+        for w in words {
+            if !w.contains("=") {
+                return Err(GreathelmError::ManifestError(format!(
+                    "\x1bc{location}\x1br: {directive} setting \x1bc{w}\x1br is not in the form \
+                     \x1bcKey=value\x1br."
+                )));
+            }
+            settings.push((w.to_string(), location.clone()));
+        }
+    }
+    if !declared {
+        return Ok(None);
+    }
+    return Ok(Some(settings));
+}
+
+/**
+ * Assigns `Key=value` and `Key+=value` settings to `manifest`, recording where they came from.
+ */
+pub fn assign_settings(manifest: &mut ProjectManifest, settings: Vec<(String, SourceLocation)>) {
+    for (setting, location) in settings {
+        let (k, v) = setting.split_once("=").unwrap();
+        manifest.assign_property(k, v);
+        manifest
+            .property_locations
+            .entry(k.strip_suffix("+").unwrap_or(k).into())
+            .or_default()
+            .push(location);
+    }
+}
+
+/**
+ * The build directory of the active profile and target: `build/` for the default profile on the
+ * host, `build/<profile>/` for other profiles and `build/<target>/<profile>/` when building for a
+ * target. `ProjectManifest::load` stores it in the `build-dir` property.
+ */
+pub fn default_build_dir(manifest: &ProjectManifest) -> String {
+    return build_dir_for(&active_profile(manifest), &target::active_target(manifest));
+}
+
+/**
+ * The build directory of a project built with `profile` for `target`, relative to the project.
+ */
+pub fn build_dir_for(profile: &str, target: &str) -> String {
+    if target != target::HOST_TARGET {
+        return format!("build/{target}/{profile}");
+    }
+    if profile == DEFAULT_PROFILE {
+        return "build".into();
+    }
+    return format!("build/{profile}");
+}
+
+/**
+ * The directory builders put their output in, `build-dir` (see `default_build_dir`).
+ */
+pub fn build_dir(manifest: &ProjectManifest) -> PathBuf {
+    return PathBuf::from(manifest.get_string_property("build-dir", "build"));
+}
+
+/**
+ * Where the IBHT of the active profile and target is kept. The default profile on the host uses
+ * IBHT.ghd in the project, others keep theirs in their build directory so switching profiles or
+ * targets doesn't rebuild everything.
+ */
+pub fn ibht_path(manifest: &ProjectManifest) -> PathBuf {
+    let dir = build_dir(manifest);
+    if dir == Path::new("build") {
+        return PathBuf::from("IBHT.ghd");
+    }
+    return dir.join("IBHT.ghd");
+}
+
+/**
+ * Where the Module IBHT of the active profile and target is kept, next to its IBHT. Modules are
+ * built with the active profile and target, so switching either builds them again.
+ */
+pub fn mibht_path(manifest: &ProjectManifest) -> PathBuf {
+    return ibht_path(manifest).with_file_name("MIBHT.ghd");
+}
+
+/**
+ * Where the IBDT of the active profile is kept, next to its IBHT.
+ */
+pub fn ibdt_path(manifest: &ProjectManifest) -> PathBuf {
+    return ibht_path(manifest).with_file_name("IBDT.ghd");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(text: &str) -> ProjectManifest {
+        let mut manifest = ProjectManifest::new();
+        manifest.append_from_str(text).unwrap();
+        return manifest;
+    }
+
+    #[test]
+    fn names_build_directories() {
+        assert_eq!(build_dir_for("debug", "host"), "build");
+        assert_eq!(build_dir_for("release", "host"), "build/release");
+        assert_eq!(build_dir_for("debug", "aarch64"), "build/aarch64/debug");
+
+        let m = manifest("");
+        assert_eq!(ibht_path(&m), PathBuf::from("IBHT.ghd"));
+        assert_eq!(ibdt_path(&m), PathBuf::from("IBDT.ghd"));
+        assert_eq!(mibht_path(&m), PathBuf::from("MIBHT.ghd"));
+        let m = manifest("build-dir=build/release");
+        assert_eq!(ibht_path(&m), PathBuf::from("build/release/IBHT.ghd"));
+        assert_eq!(ibdt_path(&m), PathBuf::from("build/release/IBDT.ghd"));
+        assert_eq!(mibht_path(&m), PathBuf::from("build/release/MIBHT.ghd"));
+    }
+
+    #[test]
+    fn applies_profiles() {
+        let mut m = manifest("profile=release");
+        apply(&mut m).unwrap();
+        assert_eq!(m.get_string_property("Compiler-Opt-Level", ""), "3");
+        assert_eq!(m.get_string_property("debug-info", ""), "false");
+
+        // declarations are applied on top of the built-in settings
+        let mut m = manifest(
+            "profile=release\nFlags=-Wall\n@Profile release Compiler-Opt-Level=2 Flags+=-flto",
+        );
+        apply(&mut m).unwrap();
+        assert_eq!(m.get_string_property("Compiler-Opt-Level", ""), "2");
+        assert_eq!(m.get_string_property("Flags", ""), "-Wall,-flto");
+
+        let mut m = manifest("profile=asan\n@Profile asan Sanitize=address");
+        apply(&mut m).unwrap();
+        assert_eq!(m.get_string_property("Sanitize", ""), "address");
+        assert_eq!(profile_names(&m), ["debug", "release", "asan"]);
+
+        let mut m = manifest("");
+        apply(&mut m).unwrap();
+        assert_eq!(
+            m.get_string_property("Compiler-Opt-Level", "<unset>"),
+            "<unset>"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_profiles() {
+        let e = apply(&mut manifest("profile=missing")).unwrap_err();
+        assert!(e.message().contains("Unknown profile"));
+        assert!(apply(&mut manifest("profile=../x")).is_err());
+        assert!(apply(&mut manifest("profile=p\n@Profile p NotASetting")).is_err());
+        assert!(!is_valid_name(".hidden"));
+        assert!(is_valid_name("arm-v7_1.0"));
+    }
+
+    #[test]
+    fn passes_profiles_to_nested_builds() {
+        let m = manifest(
+            "profile=asan\n@Profile asan Sanitize=address\n@Profile other Opt=1\n@Target wasm32 cc=emcc",
+        );
+        assert_eq!(
+            nested_build_args(&m),
+            [
+                "--profile=asan",
+                "--target=host",
+                "@Profile:asan Sanitize=address"
+            ]
+        );
+
+        // a nested project picks up the declaration only if it doesn't have its own
+        let mut nested = manifest("profile=asan");
+        declare_from_cli_args(&mut nested, &nested_build_args(&m));
+        apply(&mut nested).unwrap();
+        assert_eq!(nested.get_string_property("Sanitize", ""), "address");
+        let mut nested = manifest("profile=asan\n@Profile asan Sanitize=thread");
+        declare_from_cli_args(&mut nested, &nested_build_args(&m));
+        apply(&mut nested).unwrap();
+        assert_eq!(nested.get_string_property("Sanitize", ""), "thread");
+    }
+}
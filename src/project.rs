@@ -0,0 +1,469 @@
+use std::{
+    collections::HashSet,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    builder::{
+        dependency::DependencyGraph,
+        jobserver,
+        parallel::{self, ScopedJob},
+        schema, ProjectBuilder,
+    },
+    error::GreathelmError,
+    ibht,
+    identify::NamespacedIdentifier,
+    lockfile::Lockfile,
+    manifest::ProjectManifest,
+    module::{self, Module},
+    plugin::{
+        hooks::{self, HookPoint},
+        require,
+    },
+    profile, script,
+    state::GreathelmState,
+    target,
+    term::{self, *},
+};
+
+/**
+ * Builds the project described by `manifest` (the one in its `project_dir`) with the builders in
+ * `state`. Modules and store dependencies are built in-process first using the same state.
+ * Calls validate() and then build() if that succeeds.
+ * Also handles @Export directives.
+ */
+pub fn build(state: &GreathelmState, manifest: &ProjectManifest) -> Result<(), GreathelmError> {
+    let root = manifest.project_dir();
+    // make sure we have working settings
+    let project_name = manifest.get_string_property("Project-Name", "Unnamed Project");
+    let project_type = manifest.get_string_property("Project-Type", "Unknown");
+    // we cant build a project if it doesnt have a Project-Type.
+    if project_type == "Unknown" {
+        return Err(GreathelmError::ManifestError(
+            "This project does not have a set \x1bcProject-Type\x1br property.".into(),
+        ));
+    }
+
+    // find the builder, fail out if ambiguous.
+    let use_builder = find_builder(state, &project_type)?;
+
+    // catch typos and bad values before spending time on modules and dependencies
+    schema::check_manifest(manifest, use_builder.map(|b| b.as_ref()), &state.builders)?;
+
+    // set up the jobserver before anything runs so modules, dependencies and scripts share it
+    jobserver::init(parallel::get_build_cpus(manifest));
+
+    // modules time
+    info!("Building modules...");
+    script::run_script(&root, "pre-modules", vec![]);
+    hooks::run_hooks(HookPoint::BeforeModules, manifest, None, None)?;
+    build_modules(state, manifest)?;
+    script::run_script(&root, "post-modules", vec![]);
+    hooks::run_hooks(HookPoint::AfterModules, manifest, None, None)?;
+
+    // store dependencies (unless whoever is building us already did)
+    if !manifest.get_bool_property("no-dependency-builds", false) {
+        build_dependencies(state, manifest)?;
+    }
+
+    let target = target::active_target(manifest);
+    if target == target::HOST_TARGET {
+        info!(
+            "Building project \x1bc{project_name}\x1br with profile \x1bc{}\x1br",
+            profile::active_profile(manifest)
+        );
+    } else {
+        info!(
+            "Building project \x1bc{project_name}\x1br with profile \x1bc{}\x1br for target \x1bc{target}\x1br",
+            profile::active_profile(manifest)
+        );
+    }
+
+    // build!
+    match use_builder {
+        Some(builder) => {
+            // create build dir if absent
+            let path = manifest.project_path(profile::build_dir(manifest));
+            if !path.exists() {
+                if let Err(e) = std::fs::create_dir_all(&path) {
+                    return Err(GreathelmError::io("Failed to create build directory.", e));
+                }
+            }
+
+            info!("Validating...");
+            if builder.validate(manifest) {
+                // run the validator
+                info!("Building...");
+                builder.build(manifest)?;
+            } else {
+                return Err(GreathelmError::BuildError(
+                    "Validating project failed.".into(),
+                ));
+            }
+        }
+        None => {
+            return Err(GreathelmError::BuildError(format!(
+                "Could not find the required builder \x1bc{project_type}\x1br.\nAre you missing a plugin?"
+            )));
+        }
+    }
+
+    let export_dir = manifest.project_path("export/");
+    if !export_dir.exists() {
+        match std::fs::create_dir_all(&export_dir) {
+            Ok(_) => {}
+            Err(e) => {
+                return Err(GreathelmError::io("Failed to create export directory.", e));
+            }
+        }
+    }
+
+    // exports
+    let exports = match manifest.directives.get("Export") {
+        Some(s) => s.to_owned(),
+        None => {
+            vec![]
+        }
+    };
+
+    for export in exports {
+        // @Export build/greathelm bin/greathelm
+        // for example
+        // exports build/greathelm to the export/bin/greathelm directory
+        // this is to make packaging easier.
+        let (export, mut to) = export.split_once(" ").unwrap_or((&export, ""));
+        let export_name = match export.split("/").last() {
+            Some(v) => v,
+            None => "unnamed_export",
+        };
+        if to == "" {
+            to = export_name;
+        }
+        // parent directories
+        // cuz we might export to
+        // etc/program/some/more/config/folders/for/some/reason/config.cfg
+        let dest = manifest.project_path(format!("export/{to}"));
+        match std::fs::create_dir_all(dest.parent().unwrap()) {
+            Ok(_) => {}
+            Err(_) => {
+                warning!("Failed exporting \x1bc{export}\x1br");
+                continue;
+            }
+        };
+        match std::fs::copy(manifest.project_path(export), dest) {
+            Ok(_) => {
+                ok!("Successfully exported \x1bc{export}\x1br");
+            }
+            Err(_) => {
+                warning!("Failed exporting \x1bc{export}\x1br");
+            }
+        };
+    }
+
+    hooks::run_hooks(HookPoint::AfterExport, manifest, None, None)?;
+
+    return Ok(());
+}
+
+/**
+ * Finds the builder for `project_type`, which is either one of a builder's aliases or its
+ * namespaced identifier. Fails if the alias is ambiguous.
+ */
+pub fn find_builder<'a>(
+    state: &'a GreathelmState,
+    project_type: &str,
+) -> Result<Option<&'a Box<dyn ProjectBuilder>>, GreathelmError> {
+    let mut use_builder: Option<&Box<dyn ProjectBuilder>> = None;
+    let namespaced = NamespacedIdentifier::parse_text(&project_type.to_string());
+    for b in &state.builders {
+        // check short names.
+        if b.get_aliases().contains(&project_type.to_lowercase()) {
+            // fail out if we already have a builder found
+            if use_builder.is_some() {
+                return Err(GreathelmError::BuildError(format!(
+                    "Builder name \x1bc{project_type}\x1br is ambiguous in your configuration.\n\
+                     Please specify which one you would like to use either on the command line,\n\
+                     like so \x1bc--Project-Type=<full.namespaced:Identifier>\x1br\n\
+                     or in your project manifest."
+                )));
+            } else {
+                use_builder = Some(b);
+            }
+        } else {
+            // check NamespacedIdentifier.
+            match namespaced {
+                Some(ref n) => {
+                    if n == &b.get_identifier() {
+                        use_builder = Some(b);
+                    }
+                }
+                None => {} // if we dont have a NamespacedIdentifier we don't care.
+            }
+        }
+    }
+
+    return Ok(use_builder);
+}
+
+/**
+ * Builds all @Module modules of the project of `manifest`. Modules are built in the order given
+ * by @ModuleDependency directives. Modules that don't depend on each other are built in parallel
+ * with the output of each module printed together once they finish.
+ */
+pub fn build_modules(
+    state: &GreathelmState,
+    manifest: &ProjectManifest,
+) -> Result<(), GreathelmError> {
+    let mibht = manifest.project_path(profile::mibht_path(manifest));
+    let cpus = parallel::get_build_cpus(manifest);
+
+    let force_full_rebuild = manifest.get_bool_property("force-full-rebuild", false);
+    let keep_going = manifest.get_bool_property("keep-going", false);
+    let modules = manifest.get_modules();
+    let mut hashes = ibht::read_mibht(&mibht)?;
+    // forget modules that were removed from the manifest
+    hashes.retain(|k, _| {
+        let name = k.split_once(":").map(|(n, _)| n).unwrap_or(k);
+        modules.iter().any(|m| m.module_name == name)
+    });
+
+    // modules built again so far, whatever depends on them is built again as well
+    let mut rebuilt: Vec<String> = Vec::new();
+    for group in module::order_modules(modules)? {
+        let (group, unchanged): (Vec<Module>, Vec<Module>) = group
+            .into_iter()
+            .partition(|m| force_full_rebuild || m.needs_build(&hashes, &rebuilt));
+        for m in &unchanged {
+            if m.needs_fetch(&hashes) {
+                info!(
+                    "Module \x1bc{}\x1br is up to date. Fetching its files again.",
+                    m.module_name
+                );
+                m.fetch();
+                m.record(&mut hashes);
+            } else {
+                info!("Module \x1bc{}\x1br is up to date.", m.module_name);
+            }
+        }
+
+        // nothing to parallelize
+        if group.len() <= 1 || cpus == 1 {
+            for m in &group {
+                m.build(state, manifest)?;
+                m.record(&mut hashes);
+                ibht::write_mibht(&mibht, &hashes)?;
+                rebuilt.push(m.module_name.clone());
+            }
+            continue;
+        }
+
+        info!("Building \x1bc{}\x1br modules in parallel...", group.len());
+        let mut builds: Vec<(String, ScopedJob)> = Vec::new();
+        for m in &group {
+            builds.push((m.module_name.clone(), Box::new(|| m.build(state, manifest))));
+        }
+        let (built, failed) = build_side_by_side("Module", cpus, keep_going, builds);
+        for m in &group {
+            if built.contains(&m.module_name) {
+                m.record(&mut hashes);
+                rebuilt.push(m.module_name.clone());
+            }
+        }
+        ibht::write_mibht(&mibht, &hashes)?;
+        if !failed.is_empty() {
+            return Err(GreathelmError::BuildError(format!(
+                "Failed to build modules \x1bc{}\x1br.",
+                failed.join(", ")
+            )));
+        }
+    }
+    return ibht::write_mibht(&mibht, &hashes);
+}
+
+/**
+ * Runs `builds` of projects (named like `kind`, a module or dependency) on up to `cpus` threads.
+ * The output of each is captured and printed in one piece once all of them are done, so they
+ * don't interleave. Returns the names of the builds that succeeded and of those that failed.
+ */
+fn build_side_by_side(
+    kind: &str,
+    cpus: usize,
+    keep_going: bool,
+    builds: Vec<(String, ScopedJob)>,
+) -> (Vec<String>, Vec<String>) {
+    let names: Vec<String> = builds.iter().map(|(n, _)| n.clone()).collect();
+    let outputs: Vec<Arc<Mutex<String>>> = names.iter().map(|_| Arc::default()).collect();
+    let mut jobs: Vec<(String, ScopedJob)> = Vec::new();
+    for ((name, build), output) in builds.into_iter().zip(&outputs) {
+        let mut context = term::output_context();
+        context.capture = Some(Arc::clone(output));
+        jobs.push((
+            name,
+            Box::new(move || {
+                return term::with_output_context(context, || {
+                    let result = build();
+                    if let Err(e) = &result {
+                        e.report();
+                    }
+                    return result;
+                });
+            }),
+        ));
+    }
+    let failures = parallel::run_scoped(cpus.min(names.len()), keep_going, jobs);
+
+    let mut built = Vec::new();
+    let mut failed = Vec::new();
+    for (name, output) in names.into_iter().zip(outputs) {
+        let output = output.lock().unwrap();
+        // every build logs something, one that didn't never started
+        if output.is_empty() {
+            warning!("{kind} \x1bc{name}\x1br was skipped because another build failed.");
+            continue;
+        }
+        info!("== Output of {} \x1bc{name}\x1br ==", kind.to_lowercase());
+        term::print_raw(&output, false);
+        if failures.iter().any(|f| f.name == name) {
+            failed.push(name);
+        } else {
+            ok!("{kind} \x1bc{name}\x1br");
+            built.push(name);
+        }
+    }
+    return (built, failed);
+}
+
+/**
+ * Builds the project at `path` in-process. Its manifest is loaded the same way it would be if
+ * Greathelm was started in `path` with `args` on the command line.
+ *
+ * The working directory of the process stays the same, the project's files are resolved against
+ * `path` (see `ProjectManifest::project_dir`), so builds of several projects can run at the same
+ * time. Its log output is marked as nested like the output of a subprocess.
+ *
+ * The project is built with the plugins loaded for the project building it, so its own
+ * @RequirePlugin directives are checked against those before anything is built.
+ */
+pub fn build_at(
+    state: &GreathelmState,
+    path: &Path,
+    args: Vec<String>,
+) -> Result<(), GreathelmError> {
+    let mut context = term::output_context();
+    context.layers += 1;
+    return term::with_output_context(context, || {
+        let manifest = ProjectManifest::load_at(path, args)?;
+        check_plugin_requirements(state, &manifest)?;
+        return build(state, &manifest);
+    });
+}
+
+/**
+ * Checks the @RequirePlugin directives of a project built in-process against the plugins of
+ * `state`. Those were loaded for the top-level project, with `--only-required-plugins` only the
+ * plugins it requires itself.
+ */
+fn check_plugin_requirements(
+    state: &GreathelmState,
+    manifest: &ProjectManifest,
+) -> Result<(), GreathelmError> {
+    let requirements = require::required_plugins(manifest)?;
+    if let Err(e) = require::check_requirements(&requirements, &state.plugins) {
+        let mut message = format!(
+            "Cannot build \x1bc{}\x1br.\n{}",
+            manifest.project_dir().display(),
+            e.message()
+        );
+        if state
+            .manifest
+            .get_bool_property("only-required-plugins", false)
+        {
+            message.push_str(
+                "\nOnly the plugins required by the top-level project were loaded \
+                 (\x1bc--only-required-plugins\x1br), require them there as well.",
+            );
+        }
+        return Err(GreathelmError::PluginError(message));
+    }
+    return Ok(());
+}
+
+/**
+ * Resolves the dependency graph of the project of `manifest` and builds every store dependency in
+ * it in-process, dependencies first. Packages that don't depend on each other are built in
+ * parallel. Every package is built against this project's lockfile.
+ */
+pub fn build_dependencies(
+    state: &GreathelmState,
+    manifest: &ProjectManifest,
+) -> Result<(), GreathelmError> {
+    let mut lock = Lockfile::read(manifest)?;
+    let graph = DependencyGraph::resolve(manifest, &mut lock)?;
+    // whoever builds us with an inherited lockfile also builds our dependencies, so the lockfile
+    // is our own and packages no longer in the graph can go
+    graph.prune_lock(&mut lock);
+    lock.save()?;
+    if graph.order.is_empty() {
+        return Ok(());
+    }
+
+    // dependencies resolve their own dependencies against our lockfile
+    let args = [
+        vec![
+            "--no-dependency-builds".into(),
+            format!("--lockfile={}", lock.path.display()),
+        ],
+        profile::nested_build_args(manifest),
+    ]
+    .concat();
+    let cpus = parallel::get_build_cpus(manifest);
+    let keep_going = manifest.get_bool_property("keep-going", false);
+
+    let mut built: HashSet<&String> = HashSet::new();
+    let mut remaining: Vec<&String> = graph.order.iter().collect();
+    while !remaining.is_empty() {
+        // the order puts every package after its dependencies, so something is always ready
+        let (ready, waiting): (Vec<&String>, Vec<&String>) = remaining.into_iter().partition(|k| {
+            let node = graph.nodes.get(*k).unwrap();
+            node.dependencies.iter().all(|d| built.contains(d))
+        });
+        remaining = waiting;
+
+        if ready.len() <= 1 || cpus == 1 {
+            for key in ready {
+                let node = graph.nodes.get(key).unwrap();
+                info!(
+                    "Building dependency \x1bc{}@{}\x1br",
+                    node.identifier, node.version
+                );
+                build_at(state, &node.path, args.clone())?;
+                built.insert(key);
+            }
+            continue;
+        }
+
+        info!(
+            "Building \x1bc{}\x1br dependencies in parallel...",
+            ready.len()
+        );
+        let mut builds: Vec<(String, ScopedJob)> = Vec::new();
+        for key in &ready {
+            let node = graph.nodes.get(*key).unwrap();
+            let args = args.clone();
+            builds.push((
+                format!("{}@{}", node.identifier, node.version),
+                Box::new(move || build_at(state, &node.path, args)),
+            ));
+        }
+        let (_, failed) = build_side_by_side("Dependency", cpus, keep_going, builds);
+        if !failed.is_empty() {
+            return Err(GreathelmError::BuildError(format!(
+                "Failed to build dependencies \x1bc{}\x1br.",
+                failed.join(", ")
+            )));
+        }
+        built.extend(ready);
+    }
+    return Ok(());
+}
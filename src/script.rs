@@ -1,29 +1,34 @@
 use std::path::Path;
 
-use crate::{config, term::*};
+use crate::{builder::jobserver, config, subprocess, term::*};
 
 /**
  * This method only checks if the script exists in the
- * current project!!!
+ * project in `root`!!!
  * It does NOT check user scripts!
  */
-pub fn has_script(script_name: &str) -> bool {
+pub fn has_script(root: &Path, script_name: &str) -> bool {
     let mut path: String = "scripts/".into();
     path.push_str(script_name);
     path.push_str(".sh");
-    match Path::new(&path).try_exists() {
+    let path = root.join(path);
+    match path.try_exists() {
         Ok(o) => o,
         Err(_) => {
-            error!("Cannot check for script \x1bc{path}\x1br. Assuming it is not present.");
+            error!(
+                "Cannot check for script \x1bc{}\x1br. Assuming it is not present.",
+                path.display()
+            );
             false
         }
     }
 }
 
 /**
- * Runs all instances of `script_name` found.
+ * Runs all instances of `script_name` found for the project in `root`. Scripts run in `root`.
+ * Returns whether all of them succeeded, which is also the case when there are none.
  */
-pub fn run_script(script_name: &str, args: Vec<String>) {
+pub fn run_script(root: &Path, script_name: &str, args: Vec<String>) -> bool {
     let str = format!(
         "{}/scripts/{script_name}.sh",
         config::get_config_base_dir().to_str().unwrap()
@@ -35,11 +40,12 @@ pub fn run_script(script_name: &str, args: Vec<String>) {
     }
     let str = format!("{str}{}", argstr);
 
+    let mut success = true;
     if userpath.exists() {
-        duct::cmd!("sh", "-c", str).stderr_to_stdout().run().ok();
+        success &= succeeded(shell(root, str));
     }
 
-    if has_script(script_name) {
+    if has_script(root, script_name) {
         info!("Running script \x1bc{script_name}\x1br");
         let mut invoke: String = "./scripts/".into();
         invoke.push_str(script_name);
@@ -49,6 +55,50 @@ pub fn run_script(script_name: &str, args: Vec<String>) {
             invoke.push_str(&arg);
         }
 
-        duct::cmd!("sh", "-c", invoke).stderr_to_stdout().run().ok();
+        success &= succeeded(shell(root, invoke));
+    }
+    return success;
+}
+
+fn succeeded(script: duct::Expression) -> bool {
+    return match subprocess::run(script) {
+        Ok(o) => o.status.success(),
+        Err(_) => false,
+    };
+}
+
+/**
+ * `sh -c command` in `root`, sharing the jobserver of this process so scripts running make don't
+ * oversubscribe the CPUs.
+ */
+fn shell(root: &Path, command: String) -> duct::Expression {
+    let cmd = duct::cmd!("sh", "-c", command).dir(root);
+    return match jobserver::makeflags() {
+        Some(flags) => cmd.env("MAKEFLAGS", flags),
+        None => cmd,
+    };
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    #[test]
+    fn reports_failing_scripts() {
+        let root = std::env::temp_dir().join(format!("greathelm-script-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("scripts")).unwrap();
+        for (name, status) in [("greathelm-test-ok", 0), ("greathelm-test-fail", 3)] {
+            let path = root.join(format!("scripts/{name}.sh"));
+            std::fs::write(&path, format!("#!/bin/sh\nexit {status}\n")).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        assert!(run_script(&root, "greathelm-test-ok", vec![]));
+        assert!(!run_script(&root, "greathelm-test-fail", vec![]));
+        // nothing to run is not a failure
+        assert!(run_script(&root, "greathelm-test-missing", vec![]));
+        std::fs::remove_dir_all(&root).ok();
     }
 }
@@ -1,6 +1,10 @@
 use crate::{
-    action::Action, builder::ProjectBuilder, generator::ProjectGenerator,
-    identify::NamespacedIdentifier, manifest::ProjectManifest, plugin::PluginInfo,
+    action::Action,
+    builder::ProjectBuilder,
+    generator::ProjectGenerator,
+    identify::NamespacedIdentifier,
+    manifest::ProjectManifest,
+    plugin::{FailedPlugin, IncompatiblePlugin, PluginInfo},
 };
 
 /**
@@ -12,6 +16,14 @@ pub struct GreathelmState {
     pub actions: Vec<Box<dyn Action>>,
     pub manifest: ProjectManifest,
     pub plugins: Vec<PluginInfo>,
+    /**
+     * Plugins in the plugins directory that were refused because they are incompatible.
+     */
+    pub incompatible_plugins: Vec<IncompatiblePlugin>,
+    /**
+     * Plugins in the plugins directory that failed to load.
+     */
+    pub failed_plugins: Vec<FailedPlugin>,
     pub cli_args: Vec<String>,
 }
 
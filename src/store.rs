@@ -1,6 +1,9 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use crate::{config, identify::NamespacedIdentifier};
+use crate::{config, identify::NamespacedIdentifier, manifest::ProjectManifest};
 
 /**
  * Gets the path of the local store.
@@ -16,7 +19,14 @@ pub fn get_store_path() -> PathBuf {
  * Gets the path at which `identifier` would be located in the store.
  */
 pub fn get_path(identifier: &NamespacedIdentifier) -> PathBuf {
-    let mut path = get_store_path();
+    return path_in(&get_store_path(), identifier);
+}
+
+/**
+ * Gets the path at which `identifier` would be located in the store at `store`.
+ */
+pub fn path_in(store: &Path, identifier: &NamespacedIdentifier) -> PathBuf {
+    let mut path = store.to_path_buf();
     path.push(
         format!("{}", identifier)
             .replace(".", "/")
@@ -24,3 +34,69 @@ pub fn get_path(identifier: &NamespacedIdentifier) -> PathBuf {
     );
     return path;
 }
+
+/**
+ * Checks every package in the store. Returns how many packages (versions of an identifier) there
+ * are and what is wrong with the broken ones: versions without a Project.ghm, manifests that
+ * can't be read and manifests naming another identifier or version than the one they are stored
+ * as.
+ */
+pub fn check_store() -> (usize, Vec<String>) {
+    let mut versions = Vec::new();
+    collect_versions(&get_store_path(), &mut versions);
+    versions.sort();
+
+    let mut problems = Vec::new();
+    for path in &versions {
+        let manifest_path = path.join("Project.ghm");
+        if !manifest_path.is_file() {
+            problems.push(format!("\x1bc{}\x1br has no Project.ghm.", path.display()));
+            continue;
+        }
+        let mut manifest = ProjectManifest::new();
+        if let Err(e) = manifest.read_and_append(&manifest_path) {
+            problems.push(format!(
+                "\x1bc{}\x1br can't be read: {}",
+                manifest_path.display(),
+                e.message()
+            ));
+            continue;
+        }
+        let identifier = NamespacedIdentifier {
+            namespace: manifest.get_string_property("Project-Namespace", "unnamespaced"),
+            identifier: manifest.get_string_property("Project-Name", "unnamed"),
+        };
+        let version = manifest.get_string_property("Project-Version", "unversioned");
+        let expected = get_path(&identifier).join(format!("@{version}"));
+        if &expected != path {
+            problems.push(format!(
+                "\x1bc{}\x1br contains \x1bc{identifier}@{version}\x1br, which belongs in \
+                 \x1bc{}\x1br.",
+                path.display(),
+                expected.display()
+            ));
+        }
+    }
+    return (versions.len(), problems);
+}
+
+/**
+ * Collects the `@<version>` directories below `dir`.
+ */
+fn collect_versions(dir: &Path, versions: &mut Vec<PathBuf>) {
+    let rd = match std::fs::read_dir(dir) {
+        Ok(rd) => rd,
+        Err(_) => return,
+    };
+    for ent in rd.flatten() {
+        let path = ent.path();
+        if !path.is_dir() {
+            continue;
+        }
+        if ent.file_name().to_string_lossy().starts_with("@") {
+            versions.push(path);
+        } else {
+            collect_versions(&path, versions);
+        }
+    }
+}
@@ -1,31 +1,30 @@
 use std::path::Path;
 
-use crate::term::*;
+use crate::{
+    builder::jobserver,
+    error::GreathelmError,
+    term::{self, *},
+};
 
 /**
- * Gets the number of layers deep in subprocess-nesting we are.
+ * Gets the number of layers deep in nesting we are: builds of subprocesses and projects built
+ * in-process on the current thread (see `term::OutputContext`).
  */
 pub fn get_embedding_layers() -> usize {
-    match std::env::var("GREATHELM_EMBEDDED_LAYERS") {
-        Ok(v) => match v.trim().parse() {
-            Ok(v) => v,
-            Err(_) => 0,
-        },
+    let layers = match std::env::var("GREATHELM_EMBEDDED_LAYERS") {
+        Ok(v) => v.trim().parse().unwrap_or(0),
         Err(_) => 0,
-    }
+    };
+    return layers + term::output_context().layers;
 }
 
 /**
- * Builds the project located at `path`.
+ * Spawns a subprocess with the specified arguments. The subprocess runs on a jobserver token (see
+ * `jobserver::token_for_child`) and shares the jobserver through `MAKEFLAGS` (see
+ * `jobserver::makeflags`). Greathelm builds nested projects in-process, this is kept for plugins.
  */
-pub fn build_project(path: &Path) {
-    spawn_with_args(path, vec!["build".into()]);
-}
-
-/**
- * Spawns a subprocess with the specified arguments.
- */
-pub fn spawn_with_args(cwd: &Path, args: Vec<String>) {
+#[allow(dead_code)]
+pub fn spawn_with_args(cwd: &Path, args: Vec<String>) -> Result<(), GreathelmError> {
     info!(
         "Spawning \x1bcgreathelm {}\x1br subprocess.",
         match args.get(0) {
@@ -33,25 +32,54 @@ pub fn spawn_with_args(cwd: &Path, args: Vec<String>) {
                 v
             }
             None => {
-                error!("No action provided to subprocess::spawn_with_args. Abort.");
-                return;
+                return Err(GreathelmError::SubprocessError(
+                    "No action provided to subprocess::spawn_with_args.".into(),
+                ));
             }
         }
     );
-    let status = duct::cmd(std::env::current_exe().unwrap(), args)
-        .dir(cwd)
-        .env(
-            "GREATHELM_EMBEDDED_LAYERS",
-            format!("{}", get_embedding_layers() + 1),
-        )
-        .stderr_to_stdout()
-        .run()
-        .unwrap()
-        .status;
+    let exe = match std::env::current_exe() {
+        Ok(e) => e,
+        Err(e) => {
+            return Err(GreathelmError::io(
+                "Could not find the greathelm executable.",
+                e,
+            ));
+        }
+    };
+    let mut cmd = duct::cmd(exe, args).dir(cwd).env(
+        "GREATHELM_EMBEDDED_LAYERS",
+        format!("{}", get_embedding_layers() + 1),
+    );
+    if let Some(flags) = jobserver::makeflags() {
+        cmd = cmd.env("MAKEFLAGS", flags);
+    }
+    let _token = jobserver::token_for_child()?;
+    let status = match run(cmd) {
+        Ok(o) => o.status,
+        Err(e) => {
+            return Err(GreathelmError::io("Failed to spawn subprocess.", e));
+        }
+    };
     if status.success() {
         ok!("Subprocess succeeded.");
+        return Ok(());
     } else {
-        error!("Subprocess failed.");
-        std::process::exit(1);
+        return Err(GreathelmError::SubprocessError("Subprocess failed.".into()));
+    }
+}
+
+/**
+ * Runs `expression` with stderr sent to stdout, which is printed or, if the output of the current
+ * thread is captured (see `term::OutputContext`), captured with it. A command that fails is not
+ * an error, check the status of the output.
+ */
+pub fn run(expression: duct::Expression) -> std::io::Result<std::process::Output> {
+    let expression = expression.stderr_to_stdout().unchecked();
+    if term::output_context().capture.is_none() {
+        return expression.run();
     }
+    let output = expression.stdout_capture().run()?;
+    term::print_raw(&String::from_utf8_lossy(&output.stdout), false);
+    return Ok(output);
 }
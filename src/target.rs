@@ -0,0 +1,291 @@
+use crate::{
+    error::GreathelmError, manifest::ProjectManifest, probe::ToolRequirement, profile, script,
+};
+
+/**
+ * The target used when no `target` is set, the machine Greathelm runs on.
+ */
+pub const HOST_TARGET: &str = "host";
+
+/**
+ * The name of the active target.
+ */
+pub fn active_target(manifest: &ProjectManifest) -> String {
+    return manifest.get_string_property("target", HOST_TARGET);
+}
+
+/**
+ * Every target that can be selected: the host and those declared with @Target.
+ */
+pub fn target_names(manifest: &ProjectManifest) -> Vec<String> {
+    let mut names: Vec<String> = vec![HOST_TARGET.into()];
+    for name in profile::declared_names(manifest, "Target") {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    return names;
+}
+
+/**
+ * Applies the settings of the active target to `manifest`. Called after the profile is applied,
+ * so a target's toolchain overrides a profile, and before command line properties are set.
+ *
+ * A target is declared with `@Target <name> Key=value Key+=value ...`, usually in
+ * UserManifest.ghm as toolchains are specific to a machine. The host target needs no declaration.
+ */
+pub fn apply(manifest: &mut ProjectManifest) -> Result<(), GreathelmError> {
+    let target = active_target(manifest);
+    if !profile::is_valid_name(&target) {
+        return Err(GreathelmError::ManifestError(format!(
+            "\x1bc{target}\x1br is not a valid target name."
+        )));
+    }
+
+    match profile::declared_settings(manifest, "Target", &target)? {
+        Some(settings) => profile::assign_settings(manifest, settings),
+        None if target != HOST_TARGET => {
+            return Err(GreathelmError::ManifestError(format!(
+                "Unknown target \x1bc{target}\x1br. Available targets: {}",
+                target_names(manifest).join(", ")
+            )));
+        }
+        None => {}
+    }
+    return Ok(());
+}
+
+/**
+ * The programs and flags the C and C++ builders compile, link and archive with for the active
+ * target.
+ */
+#[derive(Clone, Debug)]
+pub struct Toolchain {
+    /**
+     * Target triple, like `aarch64-linux-gnu`. None when building for the host.
+     */
+    pub triple: Option<String>,
+    pub compiler: String,
+    pub linker: String,
+    /**
+     * Used to bundle static libraries.
+     */
+    pub archiver: String,
+    /**
+     * Passed as `--sysroot` to the compiler and linker and used by pkgconf.
+     */
+    pub sysroot: Option<String>,
+    /**
+     * Where pkgconf looks for `.pc` files instead of the host's directories.
+     */
+    pub pkg_config_path: Option<String>,
+    /**
+     * Extra flags passed to the compiler and linker.
+     */
+    pub flags: Vec<String>,
+}
+
+impl Toolchain {
+    /**
+     * Reads the toolchain from `manifest` for `language` (`C` or `C++`).
+     *
+     * The compiler and linker are `Override-<language>-Compiler` and `Override-<language>-Linker`,
+     * the archiver `Override-Archiver`. Without them `cc`/`c++` and `ar` are used, or
+     * `<triple>-gcc`/`<triple>-g++` and `<triple>-ar` if `Target-Triple` is set.
+     */
+    pub fn from_manifest(manifest: &ProjectManifest, language: &str) -> Self {
+        let triple = non_empty(manifest, "Target-Triple");
+        let (host_compiler, gnu_compiler) = match language {
+            "C++" => ("c++", "g++"),
+            _ => ("cc", "gcc"),
+        };
+        let (default_compiler, default_archiver) = match &triple {
+            Some(t) => (format!("{t}-{gnu_compiler}"), format!("{t}-ar")),
+            None => (host_compiler.to_string(), "ar".to_string()),
+        };
+
+        return Self {
+            compiler: manifest
+                .get_string_property(&format!("Override-{language}-Compiler"), &default_compiler),
+            linker: manifest
+                .get_string_property(&format!("Override-{language}-Linker"), &default_compiler),
+            archiver: manifest.get_string_property("Override-Archiver", &default_archiver),
+            sysroot: non_empty(manifest, "Target-Sysroot"),
+            pkg_config_path: non_empty(manifest, "Target-PkgConfig-Path"),
+            flags: match non_empty(manifest, "Target-Flags") {
+                Some(f) => f.split(",").map(|f| f.trim().to_string()).collect(),
+                None => vec![],
+            },
+            triple,
+        };
+    }
+
+    /**
+     * Flags every compiler and linker invocation gets: the target's flags and its sysroot.
+     */
+    pub fn target_flags(&self) -> Vec<String> {
+        let mut flags = self.flags.clone();
+        if let Some(sysroot) = &self.sysroot {
+            flags.push(format!("--sysroot={sysroot}"));
+        }
+        return flags;
+    }
+
+    /**
+     * The programs building `manifest` with this toolchain needs. `language` names the compiler,
+     * like `C compiler`. Programs replaced by `compiler` or `linker` scripts aren't needed, the
+     * archiver only is for static libraries and pkgconf only for `sys/` dependencies.
+     */
+    pub fn requirements(&self, manifest: &ProjectManifest, language: &str) -> Vec<ToolRequirement> {
+        let mut requirements = Vec::new();
+        let root = manifest.project_dir();
+        if !script::has_script(&root, "compiler") {
+            requirements.push(ToolRequirement::create(
+                &format!("{language} compiler"),
+                &self.compiler,
+            ));
+        }
+        if manifest.get_string_property("Emit", "binary") == "staticlib" {
+            requirements.push(ToolRequirement::create("archiver", &self.archiver));
+        } else if !script::has_script(&root, "linker") {
+            requirements.push(ToolRequirement::create("linker", &self.linker));
+        }
+        if manifest
+            .directives
+            .get("Dependency")
+            .is_some_and(|d| d.iter().any(|d| d.starts_with("sys/")))
+        {
+            requirements.push(ToolRequirement::create("package config tool", "pkgconf"));
+        }
+        return requirements;
+    }
+
+    /**
+     * Environment variables pkgconf runs with so it reports the target's packages:
+     * `PKG_CONFIG_LIBDIR` from `Target-PkgConfig-Path` and `PKG_CONFIG_SYSROOT_DIR` from
+     * `Target-Sysroot`.
+     */
+    pub fn pkg_config_env(&self) -> Vec<(&'static str, String)> {
+        let mut env = Vec::new();
+        if let Some(path) = &self.pkg_config_path {
+            env.push(("PKG_CONFIG_LIBDIR", path.clone()));
+        }
+        if let Some(sysroot) = &self.sysroot {
+            env.push(("PKG_CONFIG_SYSROOT_DIR", sysroot.clone()));
+        }
+        return env;
+    }
+}
+
+fn non_empty(manifest: &ProjectManifest, key: &str) -> Option<String> {
+    return match manifest.properties.get(key) {
+        Some(v) if !v.trim().is_empty() => Some(v.trim().to_string()),
+        _ => None,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(text: &str) -> ProjectManifest {
+        let mut manifest = ProjectManifest::new();
+        manifest.append_from_str(text).unwrap();
+        return manifest;
+    }
+
+    const TARGETS: &str = "@Target aarch64 Target-Triple=aarch64-linux-gnu \
+                           Target-Sysroot=/opt/aarch64 Target-Flags=-march=armv8-a\n\
+                           @Target wasm32 Override-C-Compiler=emcc";
+
+    #[test]
+    fn applies_targets() {
+        let mut m = manifest(&format!("target=aarch64\n{TARGETS}"));
+        apply(&mut m).unwrap();
+        assert_eq!(
+            m.get_string_property("Target-Triple", ""),
+            "aarch64-linux-gnu"
+        );
+        assert_eq!(target_names(&m), ["host", "aarch64", "wasm32"]);
+
+        // the host needs no declaration and changes nothing
+        let mut m = manifest(TARGETS);
+        apply(&mut m).unwrap();
+        assert!(!m.properties.contains_key("Target-Triple"));
+
+        let e = apply(&mut manifest(&format!("target=riscv\n{TARGETS}"))).unwrap_err();
+        assert!(e
+            .message()
+            .contains("Available targets: host, aarch64, wasm32"));
+        assert!(apply(&mut manifest("target=a/b")).is_err());
+    }
+
+    #[test]
+    fn builds_in_their_own_directory() {
+        let mut m = manifest(&format!("target=wasm32\nprofile=release\n{TARGETS}"));
+        profile::apply(&mut m).unwrap();
+        apply(&mut m).unwrap();
+        let build_dir = profile::default_build_dir(&m);
+        assert_eq!(build_dir, "build/wasm32/release");
+        m.properties.insert("build-dir".into(), build_dir);
+        assert_eq!(
+            profile::mibht_path(&m),
+            std::path::PathBuf::from("build/wasm32/release/MIBHT.ghd")
+        );
+    }
+
+    #[test]
+    fn reads_toolchains() {
+        let host = Toolchain::from_manifest(&manifest(""), "C++");
+        assert_eq!(host.triple, None);
+        assert_eq!(
+            (host.compiler.as_str(), host.linker.as_str()),
+            ("c++", "c++")
+        );
+        assert_eq!(host.archiver, "ar");
+        assert!(host.target_flags().is_empty());
+        assert!(host.pkg_config_env().is_empty());
+
+        let mut m = manifest(&format!("target=aarch64\n{TARGETS}"));
+        apply(&mut m).unwrap();
+        let cross = Toolchain::from_manifest(&m, "C");
+        assert_eq!(cross.compiler, "aarch64-linux-gnu-gcc");
+        assert_eq!(cross.archiver, "aarch64-linux-gnu-ar");
+        assert_eq!(
+            cross.target_flags(),
+            ["-march=armv8-a", "--sysroot=/opt/aarch64"]
+        );
+        assert_eq!(
+            cross.pkg_config_env(),
+            [("PKG_CONFIG_SYSROOT_DIR", "/opt/aarch64".to_string())]
+        );
+
+        let mut m = manifest(&format!("target=wasm32\n{TARGETS}"));
+        apply(&mut m).unwrap();
+        let wasm = Toolchain::from_manifest(&m, "C");
+        assert_eq!(
+            (wasm.compiler.as_str(), wasm.linker.as_str()),
+            ("emcc", "cc")
+        );
+        let m = manifest("Override-C-Compiler=emcc\nOverride-C-Linker=emcc");
+        assert_eq!(Toolchain::from_manifest(&m, "C").linker, "emcc");
+    }
+
+    #[test]
+    fn requires_the_tools_it_uses() {
+        let roles = |text: &str| -> Vec<String> {
+            let m = manifest(&format!("project-dir=/nonexistent\n{text}"));
+            return Toolchain::from_manifest(&m, "C")
+                .requirements(&m, "C")
+                .into_iter()
+                .map(|r| r.role)
+                .collect();
+        };
+        assert_eq!(roles(""), ["C compiler", "linker"]);
+        assert_eq!(roles("Emit=staticlib"), ["C compiler", "archiver"]);
+        assert_eq!(
+            roles("@Dependency sys/zlib"),
+            ["C compiler", "linker", "package config tool"]
+        );
+    }
+}
@@ -5,7 +5,8 @@ use std::{
 };
 
 use crate::{
-    config, error,
+    config,
+    error::GreathelmError,
     identify::NamespacedIdentifier,
     manifest::ProjectManifest,
     term::*,
@@ -43,12 +44,18 @@ pub fn get_template_path(template: &NamespacedIdentifier) -> PathBuf {
     .unwrap();
 }
 
-pub fn generate_from_template(manifest: &ProjectManifest, template: String) {
+/**
+ * Generates a project in the current directory from `template` (a namespaced identifier or an
+ * alias to one), asking for the values of every @Prompt in its TemplateDef.ghm.
+ */
+pub fn generate_from_template(
+    manifest: &ProjectManifest,
+    template: String,
+) -> Result<(), GreathelmError> {
     let dir = match std::env::current_dir() {
         Ok(d) => d,
-        Err(_) => {
-            error!("Could not get current directory. Abort.");
-            std::process::exit(1);
+        Err(e) => {
+            return Err(GreathelmError::io("Could not get current directory.", e));
         }
     };
 
@@ -58,8 +65,9 @@ pub fn generate_from_template(manifest: &ProjectManifest, template: String) {
         identifier = Some(match NamespacedIdentifier::parse_text(&template) {
             Some(i) => i,
             None => {
-                error!("Failed to parse provided namespaced identifier.");
-                std::process::exit(1);
+                return Err(GreathelmError::TemplateError(
+                    "Failed to parse provided namespaced identifier.".into(),
+                ));
             }
         });
     } else {
@@ -86,23 +94,24 @@ pub fn generate_from_template(manifest: &ProjectManifest, template: String) {
             path = get_template_path(&id);
         }
         None => {
-            error!("Could not resolve a valid identifier from your input \x1bc{template}\x1br.");
-            error!("Please provide a namespaced identifier or a valid alias to one.");
-            std::process::exit(1);
+            return Err(GreathelmError::TemplateError(format!(
+                "Could not resolve a valid identifier from your input \x1bc{template}\x1br.\n\
+                 Please provide a namespaced identifier or a valid alias to one."
+            )));
         }
     }
     if path.exists() {
         info!("Generating a project from template \x1bc{template}\x1br...");
     } else {
-        error!("The requested template \x1bc{template}\x1br could not be found.");
-        std::process::exit(1);
+        return Err(GreathelmError::TemplateError(format!(
+            "The requested template \x1bc{template}\x1br could not be found."
+        )));
     }
 
     match copy_dir(&path, &dir, &vec![], false) {
         Ok(_) => {}
         Err(e) => {
-            print_error_obj(Some(format!("Failed to copy directory.")), Box::new(e));
-            std::process::exit(1);
+            return Err(GreathelmError::io("Failed to copy directory.", e));
         }
     };
 
@@ -110,7 +119,7 @@ pub fn generate_from_template(manifest: &ProjectManifest, template: String) {
     let tdef_path = format!("{}/TemplateDef.ghm", path.display());
     let tdef_path = Path::new(tdef_path.as_str());
     if tdef_path.exists() {
-        tdef.read_and_append(tdef_path);
+        tdef.read_and_append(tdef_path)?;
     }
 
     let prompts = match tdef.directives.get("Prompt") {
@@ -173,7 +182,7 @@ pub fn generate_from_template(manifest: &ProjectManifest, template: String) {
         // the response
     }
 
-    util::run_on_all(&dir, &|p| {
+    if let Err(e) = util::run_on_all(&dir, &|p| {
         let mut str = match std::fs::read_to_string(&p) {
             Ok(s) => s,
             Err(_) => {
@@ -191,12 +200,14 @@ pub fn generate_from_template(manifest: &ProjectManifest, template: String) {
                 print_error_obj(Some("Failed to write a modified file.".into()), Box::new(e));
             }
         };
-    })
-    .unwrap();
+    }) {
+        return Err(GreathelmError::io("Failed to fill in the template.", e));
+    }
 
     std::fs::remove_file(Path::new(
         format!("{}/TemplateDef.ghm", dir.display()).as_str(),
     ))
     .ok();
     ok!("Finished generating project from template");
+    return Ok(());
 }
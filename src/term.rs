@@ -1,4 +1,9 @@
-use std::{error::Error, io::Write};
+use std::{
+    cell::RefCell,
+    error::Error,
+    io::Write,
+    sync::{Arc, Mutex},
+};
 
 #[macro_export]
 macro_rules! info {
@@ -40,6 +45,66 @@ pub(crate) use warning;
 
 use crate::subprocess;
 
+/**
+ * Where the log output of a thread goes. Projects built in-process by another build (see
+ * `project::build_at`) log one level deeper, and builds running next to each other capture their
+ * output so it can be printed in one piece once they're done.
+ */
+#[derive(Clone, Default)]
+pub struct OutputContext {
+    /**
+     * How many in-process builds deep this thread is, added to the subprocess layers.
+     */
+    pub layers: usize,
+    /**
+     * Collects the output instead of printing it when set.
+     */
+    pub capture: Option<Arc<Mutex<String>>>,
+}
+
+thread_local! {
+    static OUTPUT: RefCell<OutputContext> = RefCell::new(OutputContext::default());
+}
+
+/**
+ * The output context of the current thread. ParallelBuild hands it to the jobs submitted from
+ * this thread.
+ */
+pub fn output_context() -> OutputContext {
+    return OUTPUT.with(|o| o.borrow().clone());
+}
+
+/**
+ * Runs `f` with `context` as the output context of the current thread.
+ */
+pub fn with_output_context<T>(context: OutputContext, f: impl FnOnce() -> T) -> T {
+    let previous = OUTPUT.with(|o| o.replace(context));
+    let result = f();
+    OUTPUT.with(|o| o.replace(previous));
+    return result;
+}
+
+/**
+ * Prints `text` as is (like the output of a compiler) to stdout, or stderr if `to_stderr` is
+ * set, unless the output of the current thread is captured.
+ */
+pub fn print_raw(text: &str, to_stderr: bool) {
+    if text.is_empty() {
+        return;
+    }
+    if let Some(capture) = output_context().capture {
+        capture.lock().unwrap().push_str(text);
+        return;
+    }
+    if to_stderr {
+        eprint!("{text}");
+        std::io::stderr().flush().ok();
+    } else {
+        print!("{text}");
+        std::io::stdout().flush().ok();
+    }
+}
+
 /**
  * Prints `text` to the terminal as [INFO ].
  */
@@ -53,9 +118,9 @@ pub fn _info(text: String) {
         embed_pfx.push_str("\x1b[38;5;240m[\x1b[38;5;60mCHILD\x1b[38;5;240m] ");
     }
 
-    println!(
-        "{embed_pfx}\x1b[38;5;240m[\x1b[38;5;68mINFO \x1b[38;5;240m] \x1b[1;0m{}",
-        text
+    print_raw(
+        &format!("{embed_pfx}\x1b[38;5;240m[\x1b[38;5;68mINFO \x1b[38;5;240m] \x1b[1;0m{text}\n"),
+        false,
     );
 }
 
@@ -72,9 +137,9 @@ pub fn _warn(text: String) {
         embed_pfx.push_str("\x1b[38;5;240m[\x1b[38;5;60mCHILD\x1b[38;5;240m] ");
     }
 
-    eprintln!(
-        "{embed_pfx}\x1b[38;5;240m[\x1b[38;5;11mWARN \x1b[38;5;240m] \x1b[1;0m{}",
-        text
+    print_raw(
+        &format!("{embed_pfx}\x1b[38;5;240m[\x1b[38;5;11mWARN \x1b[38;5;240m] \x1b[1;0m{text}\n"),
+        true,
     );
 }
 
@@ -91,9 +156,9 @@ pub fn _error(text: String) {
         embed_pfx.push_str("\x1b[38;5;240m[\x1b[38;5;60mCHILD\x1b[38;5;240m] ");
     }
 
-    eprintln!(
-        "{embed_pfx}\x1b[38;5;240m[\x1b[38;5;210mERROR\x1b[38;5;240m] \x1b[1;0m{}",
-        text
+    print_raw(
+        &format!("{embed_pfx}\x1b[38;5;240m[\x1b[38;5;210mERROR\x1b[38;5;240m] \x1b[1;0m{text}\n"),
+        true,
     );
 }
 
@@ -119,9 +184,9 @@ pub fn _ok(text: String) {
         embed_pfx.push_str("\x1b[38;5;240m[\x1b[38;5;60mCHILD\x1b[38;5;240m] ");
     }
 
-    println!(
-        "{embed_pfx}\x1b[38;5;240m[\x1b[38;5;114mOK   \x1b[38;5;240m] \x1b[1;0m{}",
-        text
+    print_raw(
+        &format!("{embed_pfx}\x1b[38;5;240m[\x1b[38;5;114mOK   \x1b[38;5;240m] \x1b[1;0m{text}\n"),
+        false,
     );
 }
 
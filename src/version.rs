@@ -1,9 +1,10 @@
-use std::{fmt::Display, str::FromStr};
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
 
 /**
  * Contains a version in the form of major.minor.patch(-alpha/-beta)(-rc-<rc_number>)
+ * Prereleases (alpha, beta and release candidates) sort below the release they lead up to.
  */
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Version {
     pub major: u64,
     pub minor: u64,
@@ -13,6 +14,19 @@ pub struct Version {
 }
 
 impl Version {
+    /**
+     * Creates a release version from its major, minor and patch numbers.
+     */
+    pub fn new(major: u64, minor: u64, patch: u64) -> Version {
+        Version {
+            major,
+            minor,
+            patch,
+            pre_state: 0,
+            rc_num: 0,
+        }
+    }
+
     /**
      * Parse out text into a Version.
      */
@@ -41,7 +55,8 @@ impl Version {
                 }
         }
 
-        let segments: Vec<&str> = text.split(".").collect();
+        // only look at major.minor.patch so the -rc-<n> suffix doesn't leak into the patch
+        let segments: Vec<&str> = text.split("-").next().unwrap_or("").split(".").collect();
         for i in 0..segments.len() {
             let s: String = String::from_str(segments.get(i).unwrap())
                 .unwrap()
@@ -66,6 +81,41 @@ impl Version {
 
         return version;
     }
+
+    /**
+     * Whether this is an alpha, beta or release candidate rather than a release.
+     */
+    pub fn is_prerelease(&self) -> bool {
+        return self.pre_state != 0 || self.rc_num != 0;
+    }
+
+    /**
+     * The key versions are ordered by. Releases have to sort above their alphas and betas and
+     * release candidates above nothing but the release itself, which the raw fields don't do.
+     */
+    fn order_key(&self) -> (u64, u64, u64, u8, u64) {
+        let pre_state = match self.pre_state {
+            0 => u8::MAX,
+            s => s,
+        };
+        let rc_num = match self.rc_num {
+            0 => u64::MAX,
+            n => n,
+        };
+        return (self.major, self.minor, self.patch, pre_state, rc_num);
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return self.order_key().cmp(&other.order_key());
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
 }
 
 impl Display for Version {
@@ -87,3 +137,286 @@ impl Display for Version {
 fn sanitize_numeric_string(str: String) -> String {
     return str.chars().filter(|c| c.is_numeric()).collect::<String>();
 }
+
+/**
+ * A single comparison within a VersionRange.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Comparator {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+}
+
+/**
+ * Contains a set of version requirements, all of which must match. Parsed from a comma separated
+ * list like `^1.2`, `~0.3.1`, `>=2.0,<3`, `*` or an exact version like `1.2.3`.
+ */
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct VersionRange {
+    pub requirements: Vec<(Comparator, Version)>,
+}
+
+impl VersionRange {
+    /**
+     * Parse out text into a VersionRange. Returns None if any requirement in the list is empty.
+     */
+    pub fn parse(text: String) -> Option<VersionRange> {
+        let mut requirements = Vec::new();
+
+        for req in text.split(",") {
+            let req = req.trim();
+            if req.is_empty() {
+                return None;
+            }
+            if req == "*" {
+                continue;
+            }
+
+            let (op, v) = if let Some(v) = req.strip_prefix(">=") {
+                (">=", v)
+            } else if let Some(v) = req.strip_prefix("<=") {
+                ("<=", v)
+            } else if let Some(v) = req.strip_prefix(">") {
+                (">", v)
+            } else if let Some(v) = req.strip_prefix("<") {
+                ("<", v)
+            } else if let Some(v) = req.strip_prefix("=") {
+                ("=", v)
+            } else if let Some(v) = req.strip_prefix("^") {
+                ("^", v)
+            } else if let Some(v) = req.strip_prefix("~") {
+                ("~", v)
+            } else {
+                ("=", req)
+            };
+            let v = v.trim();
+            if v.is_empty() {
+                return None;
+            }
+            let version = Version::parse(v.into());
+            // how many of major.minor.patch were actually given (`^1.2` only gives two)
+            let given = v.split("-").next().unwrap_or("").split(".").count();
+
+            match op {
+                ">=" => requirements.push((Comparator::GreaterEq, version)),
+                "<=" => requirements.push((Comparator::LessEq, version)),
+                ">" => requirements.push((Comparator::Greater, version)),
+                "<" => requirements.push((Comparator::Less, version)),
+                "^" => {
+                    let upper = if version.major > 0 || given == 1 {
+                        Version::new(version.major + 1, 0, 0)
+                    } else if version.minor > 0 || given == 2 {
+                        Version::new(0, version.minor + 1, 0)
+                    } else {
+                        Version::new(0, 0, version.patch + 1)
+                    };
+                    requirements.push((Comparator::GreaterEq, version));
+                    requirements.push((Comparator::Less, upper));
+                }
+                "~" => {
+                    let upper = if given == 1 {
+                        Version::new(version.major + 1, 0, 0)
+                    } else {
+                        Version::new(version.major, version.minor + 1, 0)
+                    };
+                    requirements.push((Comparator::GreaterEq, version));
+                    requirements.push((Comparator::Less, upper));
+                }
+                _ => requirements.push((Comparator::Exact, version)),
+            }
+        }
+
+        return Some(VersionRange { requirements });
+    }
+
+    /**
+     * Checks if `version` satisfies every requirement in this range. Prereleases only match if
+     * the range itself names a prerelease of the same major.minor.patch, so `^1.2` never picks
+     * up `2.0.0-beta` or `1.3.0-rc-1`.
+     */
+    pub fn matches(&self, version: &Version) -> bool {
+        if version.is_prerelease()
+            && !self.requirements.iter().any(|(_, v)| {
+                v.is_prerelease()
+                    && (v.major, v.minor, v.patch) == (version.major, version.minor, version.patch)
+            })
+        {
+            return false;
+        }
+        for (cmp, v) in &self.requirements {
+            let ok = match cmp {
+                Comparator::Exact => version == v,
+                Comparator::Greater => version > v,
+                Comparator::GreaterEq => version >= v,
+                Comparator::Less => version < v,
+                Comparator::LessEq => version <= v,
+            };
+            if !ok {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    /**
+     * Returns the version this range pins if it is a single exact requirement.
+     */
+    pub fn as_exact(&self) -> Option<Version> {
+        if self.requirements.len() == 1 && self.requirements[0].0 == Comparator::Exact {
+            return Some(self.requirements[0].1);
+        }
+        None
+    }
+
+    /**
+     * Picks the newest version in `versions` that satisfies this range.
+     */
+    pub fn select(&self, versions: &[Version]) -> Option<Version> {
+        versions.iter().filter(|v| self.matches(v)).max().copied()
+    }
+}
+
+impl Display for VersionRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.requirements.is_empty() {
+            return write!(f, "*");
+        }
+        let mut parts: Vec<String> = Vec::new();
+        for (cmp, v) in &self.requirements {
+            parts.push(match cmp {
+                Comparator::Exact => format!("{v}"),
+                Comparator::Greater => format!(">{v}"),
+                Comparator::GreaterEq => format!(">={v}"),
+                Comparator::Less => format!("<{v}"),
+                Comparator::LessEq => format!("<={v}"),
+            });
+        }
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(text: &str) -> Version {
+        Version::parse(text.into())
+    }
+
+    fn range(text: &str) -> VersionRange {
+        VersionRange::parse(text.into()).unwrap()
+    }
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(v("1.2.3"), Version::new(1, 2, 3));
+        assert_eq!(v("1.2"), Version::new(1, 2, 0));
+        assert_eq!(v("v4"), Version::new(4, 0, 0));
+        assert_eq!(v("0.1.0-alpha").pre_state, 1);
+        assert_eq!(v("0.1.0-beta").pre_state, 2);
+
+        let rc = v("2.0.0-rc-3");
+        assert_eq!((rc.major, rc.minor, rc.patch, rc.rc_num), (2, 0, 0, 3));
+    }
+
+    #[test]
+    fn displays_what_it_parses() {
+        for text in ["1.2.3", "0.1.0-alpha", "3.0.0-beta", "2.0.0-rc-3"] {
+            assert_eq!(v(text).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn prereleases_sort_below_their_release() {
+        let mut versions = [
+            v("1.0.0"),
+            v("1.0.0-rc-2"),
+            v("0.9.9"),
+            v("1.0.0-beta"),
+            v("1.0.0-rc-1"),
+            v("1.0.0-alpha"),
+            v("1.0.1"),
+        ];
+        versions.sort();
+        let sorted: Vec<String> = versions.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            sorted,
+            [
+                "0.9.9",
+                "1.0.0-alpha",
+                "1.0.0-beta",
+                "1.0.0-rc-1",
+                "1.0.0-rc-2",
+                "1.0.0",
+                "1.0.1"
+            ]
+        );
+    }
+
+    #[test]
+    fn caret_ranges() {
+        assert_eq!(range("^1.2").to_string(), ">=1.2.0,<2.0.0");
+        assert_eq!(range("^0.3.1").to_string(), ">=0.3.1,<0.4.0");
+        assert_eq!(range("^0.0.4").to_string(), ">=0.0.4,<0.0.5");
+        assert_eq!(range("^0").to_string(), ">=0.0.0,<1.0.0");
+        assert!(range("^1.2").matches(&v("1.9.0")));
+        assert!(!range("^1.2").matches(&v("2.0.0")));
+        assert!(!range("^1.2").matches(&v("1.1.9")));
+    }
+
+    #[test]
+    fn tilde_ranges() {
+        assert_eq!(range("~0.3.1").to_string(), ">=0.3.1,<0.4.0");
+        assert_eq!(range("~1").to_string(), ">=1.0.0,<2.0.0");
+        assert!(range("~1.4").matches(&v("1.4.7")));
+        assert!(!range("~1.4").matches(&v("1.5.0")));
+    }
+
+    #[test]
+    fn comparator_lists() {
+        let r = range(">=2.0, <3");
+        assert!(r.matches(&v("2.0.0")));
+        assert!(r.matches(&v("2.99.0")));
+        assert!(!r.matches(&v("3.0.0")));
+        assert!(range(">1.0.0").matches(&v("1.0.1")));
+        assert!(!range(">1.0.0").matches(&v("1.0.0")));
+        assert!(range("<=1.0.0").matches(&v("1.0.0")));
+        assert!(range("*").matches(&v("9.9.9")));
+        assert!(range("*").requirements.is_empty());
+    }
+
+    #[test]
+    fn rejects_empty_requirements() {
+        assert!(VersionRange::parse("".into()).is_none());
+        assert!(VersionRange::parse(">=1.0,".into()).is_none());
+        assert!(VersionRange::parse(">=".into()).is_none());
+    }
+
+    #[test]
+    fn exact_versions() {
+        assert_eq!(range("1.2.3").as_exact(), Some(Version::new(1, 2, 3)));
+        assert_eq!(range("=1.2.3").as_exact(), Some(Version::new(1, 2, 3)));
+        assert_eq!(range("^1.2.3").as_exact(), None);
+        assert!(!range("1.2.3").matches(&v("1.2.4")));
+    }
+
+    #[test]
+    fn prereleases_only_match_ranges_naming_them() {
+        assert!(!range("^1.2").matches(&v("1.3.0-rc-1")));
+        assert!(!range(">=1.0").matches(&v("2.0.0-beta")));
+        assert!(range("^0.1.0-alpha").matches(&v("0.1.0-alpha")));
+        assert!(range("^0.1.0-alpha").matches(&v("0.1.0-beta")));
+        assert!(!range("^0.1.0-alpha").matches(&v("0.1.1-alpha")));
+    }
+
+    #[test]
+    fn selects_the_newest_match() {
+        let versions = [v("1.0.0"), v("1.4.2"), v("2.0.0"), v("1.5.0-rc-1")];
+        assert_eq!(range("^1").select(&versions), Some(v("1.4.2")));
+        assert_eq!(range("*").select(&versions), Some(v("2.0.0")));
+        assert_eq!(range("^3").select(&versions), None);
+    }
+}